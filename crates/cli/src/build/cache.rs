@@ -1,13 +1,28 @@
 //! This module defines the cache used to cache files across builds.
 
-use std::{fs, io::Write, path::PathBuf, time::SystemTime};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{Read, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
 
 use anyhow::{Result, bail};
-use tracing::instrument;
+use chrono::{DateTime, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use tempfile::NamedTempFile;
+use tracing::{debug, instrument};
 
 /// Path where the cache files will be written to inside the cache directory.
 const CACHE_PATH: &str = "landscape";
 
+/// Extension of the cache files that are stored compressed (gzip).
+const GZIP_EXTENSION: &str = ".gz";
+
+/// Extension of the cache files containing json data.
+const JSON_EXTENSION: &str = ".json";
+
 /// Cache used to store data collected from external services.
 #[derive(Debug, Clone, Default)]
 pub(crate) struct Cache {
@@ -36,7 +51,25 @@ impl Cache {
         );
     }
 
-    /// Read data from the cache file provided if available.
+    /// Return a new Cache instance whose files are stored in the namespace
+    /// provided (a subdirectory of the current cache directory), so that
+    /// builds sharing the same cache directory (i.e. different landscapes)
+    /// don't overwrite each other's files.
+    pub(crate) fn namespaced(&self, namespace: &str) -> Result<Self> {
+        let valid_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.';
+        if namespace.is_empty() || namespace.starts_with('.') || !namespace.chars().all(valid_char) {
+            bail!("invalid cache namespace: {namespace}");
+        }
+
+        let cache_dir = self.cache_dir.join(namespace);
+        if !cache_dir.exists() {
+            fs::create_dir_all(&cache_dir)?;
+        }
+        Ok(Self { cache_dir })
+    }
+
+    /// Read data from the cache file provided if available. Files with the
+    /// `.gz` extension are decompressed transparently.
     #[instrument(skip(self), err)]
     pub(crate) fn read(&self, file_name: &str) -> Result<Option<(Option<SystemTime>, Vec<u8>)>> {
         // Check if the path exists
@@ -49,15 +82,234 @@ impl Cache {
         let md = fs::metadata(&path)?;
         let modified_at = md.modified().ok();
 
-        Ok(Some((modified_at, fs::read(&path)?)))
+        // Read data, decompressing it if needed
+        let mut data = fs::read(&path)?;
+        if file_name.ends_with(GZIP_EXTENSION) {
+            let mut decompressed_data = vec![];
+            GzDecoder::new(data.as_slice()).read_to_end(&mut decompressed_data)?;
+            data = decompressed_data;
+        }
+
+        Ok(Some((modified_at, data)))
     }
 
-    /// Write provided data to cache file.
+    /// Write provided data to cache file. Files with the `.gz` extension are
+    /// compressed transparently.
+    ///
+    /// Data is written to a temporary file first, which is then renamed to the
+    /// cache file, so that an interrupted write never leaves a partial file.
     #[instrument(skip(self, data), err)]
     pub(crate) fn write(&self, file_name: &str, data: &[u8]) -> Result<()> {
-        let path = self.cache_dir.join(file_name);
-        let mut file = fs::File::create(path)?;
-        file.write_all(data)?;
+        let mut tmp_file = NamedTempFile::new_in(&self.cache_dir)?;
+        if file_name.ends_with(GZIP_EXTENSION) {
+            let mut encoder = GzEncoder::new(&mut tmp_file, Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()?;
+        } else {
+            tmp_file.write_all(data)?;
+        }
+        tmp_file.as_file().sync_all()?;
+        tmp_file.persist(self.cache_dir.join(file_name))?;
+        Ok(())
+    }
+
+    /// Build an index of the json files in the cache, summarizing the entries
+    /// in each of them. Files whose content is not a json object (keyed by
+    /// entry) are skipped.
+    #[instrument(skip(self), err)]
+    pub(crate) fn index(&self) -> Result<Vec<CacheFileIndex>> {
+        let mut file_names: Vec<String> = fs::read_dir(&self.cache_dir)?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|file_name| {
+                file_name.ends_with(JSON_EXTENSION)
+                    || file_name.ends_with(&format!("{JSON_EXTENSION}{GZIP_EXTENSION}"))
+            })
+            .collect();
+        file_names.sort();
+
+        let mut index = vec![];
+        for file_name in file_names {
+            let Some((_, data)) = self.read(&file_name)? else {
+                continue;
+            };
+            match CacheFileIndex::new(&file_name, &data) {
+                Ok(file_index) => index.push(file_index),
+                Err(err) => debug!("skipping cache file {file_name} in index: {err}"),
+            }
+        }
+
+        Ok(index)
+    }
+}
+
+/// Summary of the entries in a cache file.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CacheFileIndex {
+    pub file_name: String,
+    pub entries: usize,
+    pub oldest: Option<DateTime<Utc>>,
+    pub newest: Option<DateTime<Utc>>,
+}
+
+impl CacheFileIndex {
+    /// Create a new cache file index from the file's json data provided. The
+    /// oldest and newest entries are those with the oldest and newest
+    /// `generated_at` timestamp (when available).
+    fn new(file_name: &str, data: &[u8]) -> Result<Self> {
+        let entries: BTreeMap<String, serde_json::Value> = serde_json::from_slice(data)?;
+        let generated_at: Vec<DateTime<Utc>> = entries
+            .values()
+            .filter_map(|entry| entry.get("generated_at")?.as_str()?.parse().ok())
+            .collect();
+
+        Ok(Self {
+            file_name: file_name.to_string(),
+            entries: entries.len(),
+            oldest: generated_at.iter().min().copied(),
+            newest: generated_at.iter().max().copied(),
+        })
+    }
+}
+
+impl fmt::Display for CacheFileIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} entries", self.file_name, self.entries)?;
+        if let (Some(oldest), Some(newest)) = (self.oldest, self.newest) {
+            write!(f, " (oldest: {oldest}, newest: {newest})")?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_write_read_round_trip() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let data = br#"{"https://gitlab.com/group/project": {"stars": 10}}"#;
+
+        for file_name in ["data.json", "data.json.gz"] {
+            cache.write(file_name, data).unwrap();
+            let (modified_at, cached_data) = cache.read(file_name).unwrap().unwrap();
+            assert!(modified_at.is_some());
+            assert_eq!(cached_data, data, "{file_name}");
+        }
+    }
+
+    #[test]
+    fn cache_write_gzip_compresses_data() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let data = "{}".repeat(1000);
+
+        cache.write("data.json.gz", data.as_bytes()).unwrap();
+        let raw_data = fs::read(tmp_dir.path().join(CACHE_PATH).join("data.json.gz")).unwrap();
+        assert!(raw_data.len() < data.len());
+        assert_eq!(&raw_data[..2], &[0x1f, 0x8b]);
+    }
+
+    #[test]
+    fn cache_write_is_atomic() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let data = ["a".repeat(1024 * 1024), "b".repeat(1024 * 1024)];
+        cache.write("data.json", data[0].as_bytes()).unwrap();
+
+        std::thread::scope(|s| {
+            let writer = s.spawn(|| {
+                for i in 0..50 {
+                    cache.write("data.json", data[i % 2].as_bytes()).unwrap();
+                }
+            });
+            while !writer.is_finished() {
+                let (_, cached_data) = cache.read("data.json").unwrap().unwrap();
+                assert!(cached_data == data[0].as_bytes() || cached_data == data[1].as_bytes());
+            }
+        });
+
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(tmp_dir.path().join(CACHE_PATH)).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn cache_index_summarizes_json_files() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let gitlab_data = r#"{
+            "https://gitlab.com/group/project1": {"generated_at": "2024-05-01T10:00:00Z"},
+            "https://gitlab.com/group/project2": {"generated_at": "2024-05-03T10:00:00Z"},
+            "https://gitlab.com/group/project3": {"generated_at": "2024-05-02T10:00:00Z"}
+        }"#;
+        cache.write("gitlab.json.gz", gitlab_data.as_bytes()).unwrap();
+        cache
+            .write(
+                "gitlab-errors.json",
+                br#"{"https://gitlab.com/group/project4": {}}"#,
+            )
+            .unwrap();
+        cache.write("invalid.json", b"[]").unwrap();
+        cache.write("logo.svg", b"<svg></svg>").unwrap();
+
+        let index = cache.index().unwrap();
+        assert_eq!(
+            index,
+            vec![
+                CacheFileIndex {
+                    file_name: "gitlab-errors.json".to_string(),
+                    entries: 1,
+                    oldest: None,
+                    newest: None,
+                },
+                CacheFileIndex {
+                    file_name: "gitlab.json.gz".to_string(),
+                    entries: 3,
+                    oldest: Some("2024-05-01T10:00:00Z".parse().unwrap()),
+                    newest: Some("2024-05-03T10:00:00Z".parse().unwrap()),
+                },
+            ]
+        );
+        assert_eq!(
+            index[1].to_string(),
+            "gitlab.json.gz: 3 entries (oldest: 2024-05-01 10:00:00 UTC, newest: 2024-05-03 10:00:00 UTC)"
+        );
+    }
+
+    #[test]
+    fn cache_namespaces_use_distinct_files() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let cache1 = cache.namespaced("landscape1").unwrap();
+        let cache2 = cache.namespaced("landscape2").unwrap();
+
+        cache1.write("gitlab.json.gz", b"data1").unwrap();
+        cache2.write("gitlab.json.gz", b"data2").unwrap();
+        assert_eq!(cache1.read("gitlab.json.gz").unwrap().unwrap().1, b"data1");
+        assert_eq!(cache2.read("gitlab.json.gz").unwrap().unwrap().1, b"data2");
+        assert!(cache.read("gitlab.json.gz").unwrap().is_none());
+
+        let cache_path = tmp_dir.path().join(CACHE_PATH);
+        assert!(cache_path.join("landscape1").join("gitlab.json.gz").exists());
+        assert!(cache_path.join("landscape2").join("gitlab.json.gz").exists());
+    }
+
+    #[test]
+    fn cache_namespaced_rejects_invalid_namespaces() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+
+        for namespace in ["", ".", "..", "../other", "a/b", "a b"] {
+            assert!(cache.namespaced(namespace).is_err(), "{namespace}");
+        }
+    }
+
+    #[test]
+    fn cache_read_missing_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+
+        assert!(cache.read("missing.json.gz").unwrap().is_none());
+    }
+}
@@ -2,11 +2,11 @@
 //! from GitHub for each of the landscape items repositories (when applicable),
 //! as well as the functionality used to collect that information.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, Mutex};
 
-use anyhow::{Result, format_err};
+use anyhow::{Result, bail, format_err};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use deadpool::unmanaged::{Object, Pool};
@@ -18,9 +18,14 @@ use octorust::auth::Credentials;
 use octorust::types::{FullRepository, ParticipationStats};
 use regex::Regex;
 use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde::Deserialize;
 use tracing::{debug, instrument, warn};
 
-use super::{LandscapeData, cache::Cache};
+use super::{
+    LandscapeData,
+    cache::Cache,
+    tokens::{InstanceTokens, parse_instances_tokens},
+};
 
 /// File used to cache data collected from GitHub.
 const GITHUB_CACHE_FILE: &str = "github.json";
@@ -28,13 +33,46 @@ const GITHUB_CACHE_FILE: &str = "github.json";
 /// How long the GitHub data in the cache is valid (in days).
 const GITHUB_CACHE_TTL: i64 = 7;
 
-/// Environment variable containing a comma separated list of GitHub tokens.
+/// Environment variable containing GitHub tokens configuration.
+/// Format: "token1,token2" for github.com or "url1;token1;url2;token2" for
+/// multiple instances (i.e. GitHub Enterprise Server).
 const GITHUB_TOKENS: &str = "GITHUB_TOKENS";
 
+/// Default GitHub instance url.
+const DEFAULT_GITHUB_URL: &str = "https://github.com";
+
+/// GitHub data collection arguments.
+#[derive(clap::Args, Debug, Clone)]
+pub struct GitHubArgs {
+    /// GitHub API used to collect the repositories data.
+    #[arg(
+        id = "github_api",
+        long = "github-api",
+        value_name = "API",
+        value_enum,
+        default_value_t = GitHubApi::Rest
+    )]
+    pub api: GitHubApi,
+}
+
+/// GitHub API used to collect the repositories data.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum GitHubApi {
+    /// Use the GraphQL API when possible, falling back to the REST API for the
+    /// information not available on it.
+    Graphql,
+    /// Use the REST API.
+    Rest,
+}
+
 /// Collect GitHub data for each of the items repositories in the landscape,
 /// reusing cached data whenever possible.
 #[instrument(skip_all, err)]
-pub(crate) async fn collect_github_data(cache: &Cache, landscape_data: &LandscapeData) -> Result<GitData> {
+pub(crate) async fn collect_github_data(
+    cache: &Cache,
+    landscape_data: &LandscapeData,
+    args: &GitHubArgs,
+) -> Result<GitData> {
     debug!("collecting repositories information from github (this may take a while)");
 
     // Read cached data (if available)
@@ -48,29 +86,23 @@ pub(crate) async fn collect_github_data(cache: &Cache, landscape_data: &Landscap
         Err(err) => warn!("error reading github cache file: {err:?}"),
     }
 
-    // Setup GitHub API clients pool if any tokens have been provided
-    let tokens: Option<Vec<String>> = match env::var(GITHUB_TOKENS) {
-        Ok(tokens) if !tokens.is_empty() => Some(tokens.split(',').map(ToString::to_string).collect()),
-        Ok(_) | Err(_) => None,
-    };
-    let gh_pool: Option<Pool<DynGH>> = if let Some(tokens) = &tokens {
-        let mut gh_clients: Vec<DynGH> = vec![];
-        for token in tokens {
-            let gh = Box::new(GHApi::new(token)?);
-            gh_clients.push(gh);
-        }
-        Some(Pool::from(gh_clients))
-    } else {
+    // Setup GitHub API clients pools (one per instance) if any tokens have
+    // been provided
+    let instances_tokens = parse_github_tokens_env();
+    let gh_pools = new_gh_pools(&instances_tokens, args)?;
+    if gh_pools.is_empty() {
         warn!("github tokens not provided: no information will be collected from github");
-        None
-    };
+    }
 
-    // Collect urls of the repositories to process
+    // Collect urls of the repositories to process (github.com ones and the
+    // ones from the instances tokens have been provided for)
     let mut urls = vec![];
     for item in &landscape_data.items {
         if let Some(repositories) = &item.repositories {
             for repo in repositories {
-                if GITHUB_REPO_URL.is_match(&repo.url) {
+                if let Some((base_url, _, _)) = parse_github_url(&repo.url)
+                    && (base_url == DEFAULT_GITHUB_URL || gh_pools.contains_key(&base_url.to_lowercase()))
+                {
                     urls.push(&repo.url);
                 }
             }
@@ -80,11 +112,7 @@ pub(crate) async fn collect_github_data(cache: &Cache, landscape_data: &Landscap
     urls.dedup();
 
     // Collect repositories information from GitHub, reusing cached data when available
-    let concurrency = if let Some(tokens) = tokens {
-        tokens.len()
-    } else {
-        1
-    };
+    let concurrency = instances_tokens.iter().map(|instance| instance.tokens.len()).sum::<usize>().max(1);
     let github_data: GitData = stream::iter(urls)
         .map(|url| async {
             let url = url.clone();
@@ -101,10 +129,16 @@ pub(crate) async fn collect_github_data(cache: &Cache, landscape_data: &Landscap
             }) {
                 (url, Ok(cached_repo.clone()))
             }
-            // Otherwise we pull it from GitHub if any tokens were provided
-            else if let Some(gh_pool) = &gh_pool {
-                let gh = gh_pool.get().await.expect("token -when available-");
-                (url.clone(), collect_repository_data(gh, &url).await)
+            // Otherwise we pull it from GitHub if any tokens were provided for
+            // the repository's instance
+            else if let Some(gh_pool) =
+                parse_github_url(&url).and_then(|(base_url, _, _)| gh_pools.get(&base_url.to_lowercase()))
+            {
+                let result = match get_client(gh_pool).await {
+                    Ok(gh) => collect_repository_data(gh, &url).await,
+                    Err(err) => Err(err),
+                };
+                (url.clone(), result)
             } else {
                 (url.clone(), Err(format_err!("no tokens provided")))
             }
@@ -129,11 +163,88 @@ pub(crate) async fn collect_github_data(cache: &Cache, landscape_data: &Landscap
     Ok(github_data)
 }
 
+/// Setup the GitHub API clients pools (one per instance) for the instances
+/// tokens provided, keyed by the instance base url (lowercased).
+fn new_gh_pools(
+    instances_tokens: &[InstanceTokens],
+    args: &GitHubArgs,
+) -> Result<HashMap<String, Pool<DynGH>>> {
+    let mut gh_clients: HashMap<String, Vec<DynGH>> = HashMap::new();
+    for instance in instances_tokens {
+        for token in &instance.tokens {
+            let gh = GHApi::new(&instance.base_url, token)?;
+            let gh: DynGH = match args.api {
+                GitHubApi::Graphql => Box::new(GHGraphQL::new(gh, &instance.base_url)),
+                GitHubApi::Rest => Box::new(gh),
+            };
+            gh_clients.entry(instance.base_url.to_lowercase()).or_default().push(gh);
+        }
+    }
+    Ok(gh_clients
+        .into_iter()
+        .map(|(base_url, clients)| (base_url, Pool::from(clients)))
+        .collect())
+}
+
+/// Type alias to represent a GitHubCollector trait object.
+pub(crate) type DynGitHubCollector = Box<dyn GitHubCollector + Send + Sync>;
+
+/// Trait that defines the operations a GitHub repositories data collector
+/// must support. It allows other collectors to delegate the collection of
+/// some repositories to GitHub (i.e. GitLab mirrors of GitHub repositories).
+#[async_trait]
+#[cfg_attr(test, automock)]
+pub(crate) trait GitHubCollector {
+    /// Collect the data of the GitHub repository provided.
+    async fn collect_repository_data(&self, repo_url: &str) -> Result<RepositoryGitData>;
+}
+
+/// GitHubCollector implementation backed by the GitHub API clients pools set
+/// up from the tokens available in the environment.
+pub(crate) struct GitHubApiCollector {
+    gh_pools: HashMap<String, Pool<DynGH>>,
+}
+
+impl GitHubApiCollector {
+    /// Create a new GitHubApiCollector instance.
+    pub(crate) fn new(args: &GitHubArgs) -> Result<Self> {
+        let gh_pools = new_gh_pools(&parse_github_tokens_env(), args)?;
+        Ok(Self { gh_pools })
+    }
+
+    /// Check if there are any tokens available to collect data from GitHub.
+    pub(crate) fn has_tokens(&self) -> bool {
+        !self.gh_pools.is_empty()
+    }
+}
+
+#[async_trait]
+impl GitHubCollector for GitHubApiCollector {
+    /// [GitHubCollector::collect_repository_data]
+    async fn collect_repository_data(&self, repo_url: &str) -> Result<RepositoryGitData> {
+        let gh_pool = parse_github_url(repo_url)
+            .and_then(|(base_url, _, _)| self.gh_pools.get(&base_url.to_lowercase()))
+            .ok_or_else(|| format_err!("no tokens provided"))?;
+        let gh = get_client(gh_pool).await?;
+        collect_repository_data(gh, repo_url).await
+    }
+}
+
+/// Get a client from the pool provided, returning an error if none of them
+/// are available.
+async fn get_client(gh_pool: &Pool<DynGH>) -> Result<Object<DynGH>> {
+    if gh_pool.status().size == 0 {
+        bail!("no github clients available for instance");
+    }
+    gh_pool.get().await.map_err(|err| format_err!("error getting github client: {err}"))
+}
+
 /// Collect repository data from GitHub.
 #[instrument(skip_all, err)]
 async fn collect_repository_data(gh: Object<DynGH>, repo_url: &str) -> Result<RepositoryGitData> {
     // Collect some information from GitHub
-    let (owner, repo) = get_owner_and_repo(repo_url)?;
+    let (base_url, owner, repo) =
+        parse_github_url(repo_url).ok_or_else(|| format_err!("invalid repository url"))?;
     let gh_repo = gh.get_repository(&owner, &repo).await?;
     let contributors_count = gh.get_contributors_count(&owner, &repo).await?;
     let first_commit = gh.get_first_commit(&owner, &repo, &gh_repo.default_branch).await?;
@@ -147,20 +258,14 @@ async fn collect_repository_data(gh: Object<DynGH>, repo_url: &str) -> Result<Re
         generated_at: Utc::now(),
         contributors: Contributors {
             count: contributors_count,
-            url: format!("https://github.com/{owner}/{repo}/graphs/contributors"),
+            url: format!("{base_url}/{owner}/{repo}/graphs/contributors"),
         },
         description: gh_repo.description,
         first_commit,
         languages,
         latest_commit,
         latest_release,
-        license: gh_repo.license.and_then(|l| {
-            if l.name == "NOASSERTION" {
-                None
-            } else {
-                Some(l.name)
-            }
-        }),
+        license: gh_repo.license.filter(|name| name != "NOASSERTION"),
         participation_stats: Some(participation_stats),
         stars: gh_repo.stargazers_count,
         topics: gh_repo.topics,
@@ -172,12 +277,44 @@ async fn collect_repository_data(gh: Object<DynGH>, repo_url: &str) -> Result<Re
 /// GitHub API base url.
 const GITHUB_API_URL: &str = "https://api.github.com";
 
+/// GitHub GraphQL API url.
+const GITHUB_GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+/// Parse GitHub tokens from the corresponding environment variable. Tokens
+/// not preceded by an instance url are used for github.com.
+fn parse_github_tokens_env() -> Vec<InstanceTokens> {
+    match env::var(GITHUB_TOKENS) {
+        Ok(tokens) if !tokens.is_empty() => parse_instances_tokens(&tokens, DEFAULT_GITHUB_URL),
+        Ok(_) | Err(_) => vec![],
+    }
+}
+
+/// Return the API url of the GitHub instance provided. GitHub Enterprise
+/// Server instances serve the REST API under the `/api/v3` path.
+fn get_api_url(base_url: &str) -> String {
+    if base_url.eq_ignore_ascii_case(DEFAULT_GITHUB_URL) {
+        return GITHUB_API_URL.to_string();
+    }
+    format!("{}/api/v3", base_url.trim_end_matches('/'))
+}
+
+/// Return the GraphQL API url of the GitHub instance provided. GitHub
+/// Enterprise Server instances serve it under the `/api/graphql` path.
+fn get_graphql_url(base_url: &str) -> String {
+    if base_url.eq_ignore_ascii_case(DEFAULT_GITHUB_URL) {
+        return GITHUB_GRAPHQL_URL.to_string();
+    }
+    format!("{}/api/graphql", base_url.trim_end_matches('/'))
+}
+
 /// Type alias to represent a GH trait object.
 type DynGH = Box<dyn GH + Send + Sync>;
 
 /// Trait that defines some operations a GH implementation must support.
 #[async_trait]
 #[cfg_attr(test, automock)]
+// The fields of the mock generated share the same prefix
+#[cfg_attr(test, allow(clippy::struct_field_names))]
 trait GH {
     /// Get number of repository contributors.
     async fn get_contributors_count(&self, owner: &str, repo: &str) -> Result<usize>;
@@ -198,25 +335,41 @@ trait GH {
     async fn get_participation_stats(&self, owner: &str, repo: &str) -> Result<ParticipationStats>;
 
     /// Get repository.
-    async fn get_repository(&self, owner: &str, repo: &str) -> Result<FullRepository>;
+    async fn get_repository(&self, owner: &str, repo: &str) -> Result<GitHubRepository>;
+}
+
+/// GitHub repository information.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct GitHubRepository {
+    default_branch: String,
+    description: String,
+    html_url: String,
+    license: Option<String>,
+    stargazers_count: i64,
+    topics: Vec<String>,
 }
 
 /// GH implementation backed by the GitHub API.
 struct GHApi {
+    api_url: String,
     gh_client: octorust::Client,
     http_client: reqwest::Client,
 }
 
 impl GHApi {
-    /// Create a new GHApi instance.
-    fn new(token: &str) -> Result<Self> {
+    /// Create a new GHApi instance for the GitHub instance provided.
+    fn new(base_url: &str, token: &str) -> Result<Self> {
         // Setup octorust GitHub API client
+        let api_url = get_api_url(base_url);
         let user_agent = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
-        let gh_client = octorust::Client::custom(
+        let mut gh_client = octorust::Client::custom(
             user_agent.clone(),
             Credentials::Token(token.to_string()),
             reqwest_middleware::ClientBuilder::new(reqwest::Client::builder().build()?).build(),
         );
+        if api_url != GITHUB_API_URL {
+            gh_client.with_host_override(&api_url);
+        }
 
         // Setup HTTP client ready to make requests to the GitHub API
         // (for some operations that cannot be done with the octorust client)
@@ -237,6 +390,7 @@ impl GHApi {
             reqwest::Client::builder().user_agent(user_agent).default_headers(headers).build()?;
 
         Ok(Self {
+            api_url,
             gh_client,
             http_client,
         })
@@ -248,7 +402,10 @@ impl GH for GHApi {
     /// [GH::get_contributors_count]
     #[instrument(skip(self), err)]
     async fn get_contributors_count(&self, owner: &str, repo: &str) -> Result<usize> {
-        let url = format!("{GITHUB_API_URL}/repos/{owner}/{repo}/contributors?per_page=1&anon=true");
+        let url = format!(
+            "{}/repos/{owner}/{repo}/contributors?per_page=1&anon=true",
+            self.api_url
+        );
         let response = self.http_client.head(url).send().await?;
         let count = get_last_page(response.headers())?.unwrap_or(1);
         Ok(count)
@@ -259,7 +416,10 @@ impl GH for GHApi {
     #[instrument(skip(self), err)]
     async fn get_first_commit(&self, owner: &str, repo: &str, ref_: &str) -> Result<Option<Commit>> {
         // Get last commits page
-        let url = format!("{GITHUB_API_URL}/repos/{owner}/{repo}/commits?sha={ref_}&per_page=1");
+        let url = format!(
+            "{}/repos/{owner}/{repo}/commits?sha={ref_}&per_page=1",
+            self.api_url
+        );
         let response = self.http_client.head(url).send().await?;
         let last_page = get_last_page(response.headers())?.unwrap_or(1);
 
@@ -280,7 +440,7 @@ impl GH for GHApi {
     /// [GH::get_languages]
     #[instrument(skip(self), err)]
     async fn get_languages(&self, owner: &str, repo: &str) -> Result<Option<BTreeMap<String, i64>>> {
-        let url = format!("{GITHUB_API_URL}/repos/{owner}/{repo}/languages");
+        let url = format!("{}/repos/{owner}/{repo}/languages", self.api_url);
         let languages: BTreeMap<String, i64> = self.http_client.get(url).send().await?.json().await?;
         Ok(Some(languages))
     }
@@ -315,15 +475,292 @@ impl GH for GHApi {
 
     /// [GH::get_repository]
     #[instrument(skip(self), err)]
-    async fn get_repository(&self, owner: &str, repo: &str) -> Result<FullRepository> {
+    async fn get_repository(&self, owner: &str, repo: &str) -> Result<GitHubRepository> {
         let response = self.gh_client.repos().get(owner, repo).await?;
-        Ok(response.body)
+        Ok(new_repository_from(response.body))
+    }
+}
+
+/// GraphQL query used to fetch the repository information.
+const REPOSITORY_GRAPHQL_QUERY: &str = r"
+query($owner: String!, $name: String!) {
+    repository(owner: $owner, name: $name) {
+        defaultBranchRef {
+            name
+            target {
+                ... on Commit {
+                    authoredDate
+                    url
+                }
+            }
+        }
+        description
+        languages(first: 100) {
+            edges {
+                size
+                node {
+                    name
+                }
+            }
+        }
+        latestRelease {
+            name
+            publishedAt
+            tagName
+            url
+        }
+        licenseInfo {
+            name
+        }
+        repositoryTopics(first: 100) {
+            nodes {
+                topic {
+                    name
+                }
+            }
+        }
+        stargazerCount
+        url
+    }
+}
+";
+
+/// GH implementation backed by the GitHub GraphQL API. The repository
+/// details, languages, latest commit and latest release are fetched with a
+/// single query when getting the repository, and reused by the corresponding
+/// operations later. The REST API is used for the remaining operations.
+struct GHGraphQL {
+    rest: GHApi,
+    graphql_url: String,
+    repositories: Mutex<HashMap<String, GitHubGraphQLRepository>>,
+}
+
+impl GHGraphQL {
+    /// Create a new GHGraphQL instance for the GitHub instance provided.
+    fn new(rest: GHApi, base_url: &str) -> Self {
+        Self {
+            rest,
+            graphql_url: get_graphql_url(base_url),
+            repositories: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the repository information fetched previously from the GraphQL API.
+    fn cached_repository(&self, owner: &str, repo: &str) -> Option<GitHubGraphQLRepository> {
+        self.repositories
+            .lock()
+            .expect("repositories lock not to be poisoned")
+            .get(&format!("{owner}/{repo}"))
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl GH for GHGraphQL {
+    /// [GH::get_contributors_count]
+    async fn get_contributors_count(&self, owner: &str, repo: &str) -> Result<usize> {
+        self.rest.get_contributors_count(owner, repo).await
+    }
+
+    /// [GH::get_first_commit]
+    async fn get_first_commit(&self, owner: &str, repo: &str, ref_: &str) -> Result<Option<Commit>> {
+        self.rest.get_first_commit(owner, repo, ref_).await
+    }
+
+    /// [GH::get_languages]
+    async fn get_languages(&self, owner: &str, repo: &str) -> Result<Option<BTreeMap<String, i64>>> {
+        match self.cached_repository(owner, repo) {
+            Some(repository) => Ok(Some(repository.languages())),
+            None => self.rest.get_languages(owner, repo).await,
+        }
+    }
+
+    /// [GH::get_latest_commit]
+    async fn get_latest_commit(&self, owner: &str, repo: &str, ref_: &str) -> Result<Commit> {
+        match self.cached_repository(owner, repo).and_then(|repository| repository.latest_commit(ref_)) {
+            Some(commit) => Ok(commit),
+            None => self.rest.get_latest_commit(owner, repo, ref_).await,
+        }
+    }
+
+    /// [GH::get_latest_release]
+    async fn get_latest_release(&self, owner: &str, repo: &str) -> Result<Option<Release>> {
+        match self.cached_repository(owner, repo) {
+            Some(repository) => Ok(repository.latest_release()),
+            None => self.rest.get_latest_release(owner, repo).await,
+        }
+    }
+
+    /// [GH::get_participation_stats]
+    async fn get_participation_stats(&self, owner: &str, repo: &str) -> Result<ParticipationStats> {
+        self.rest.get_participation_stats(owner, repo).await
+    }
+
+    /// [GH::get_repository]
+    #[instrument(skip(self), err)]
+    async fn get_repository(&self, owner: &str, repo: &str) -> Result<GitHubRepository> {
+        let body = serde_json::json!({
+            "query": REPOSITORY_GRAPHQL_QUERY,
+            "variables": { "owner": owner, "name": repo },
+        });
+        let response = self.rest.http_client.post(&self.graphql_url).json(&body).send().await?;
+        if !response.status().is_success() {
+            bail!("error querying graphql api: {}", response.status());
+        }
+
+        let response: GitHubGraphQLResponse = response.json().await?;
+        let Some(repository) = response.data.and_then(|data| data.repository) else {
+            if let Some(errors) = response.errors {
+                bail!("error querying graphql api: {errors:?}");
+            }
+            bail!("repository not found");
+        };
+        self.repositories
+            .lock()
+            .expect("repositories lock not to be poisoned")
+            .insert(format!("{owner}/{repo}"), repository.clone());
+
+        Ok(repository.into_repository())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLResponse {
+    data: Option<GitHubGraphQLData>,
+    errors: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLData {
+    repository: Option<GitHubGraphQLRepository>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitHubGraphQLRepository {
+    default_branch_ref: Option<GitHubGraphQLRef>,
+    description: Option<String>,
+    languages: Option<GitHubGraphQLLanguages>,
+    latest_release: Option<GitHubGraphQLRelease>,
+    license_info: Option<GitHubGraphQLLicense>,
+    repository_topics: Option<GitHubGraphQLTopics>,
+    stargazer_count: i64,
+    url: String,
+}
+
+impl GitHubGraphQLRepository {
+    /// Return the repository information.
+    fn into_repository(self) -> GitHubRepository {
+        GitHubRepository {
+            default_branch: self.default_branch_ref.map(|r| r.name).unwrap_or_default(),
+            description: self.description.unwrap_or_default(),
+            html_url: self.url,
+            license: self.license_info.map(|l| l.name),
+            stargazers_count: self.stargazer_count,
+            topics: self
+                .repository_topics
+                .map(|topics| topics.nodes.into_iter().map(|node| node.topic.name).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Return the languages used in the repository (size in bytes).
+    fn languages(&self) -> BTreeMap<String, i64> {
+        self.languages
+            .iter()
+            .flat_map(|languages| &languages.edges)
+            .map(|edge| (edge.node.name.clone(), edge.size))
+            .collect()
+    }
+
+    /// Return the latest commit of the reference provided, when it is the
+    /// default branch (the only one whose latest commit is fetched).
+    fn latest_commit(&self, ref_: &str) -> Option<Commit> {
+        let branch = self.default_branch_ref.as_ref().filter(|branch| branch.name == ref_)?;
+        let target = branch.target.as_ref()?;
+        Some(Commit {
+            url: target.url.clone()?,
+            ts: target.authored_date,
+            ..Default::default()
+        })
+    }
+
+    /// Return the latest release of the repository.
+    fn latest_release(&self) -> Option<Release> {
+        self.latest_release.as_ref().map(|release| Release {
+            name: release.name.clone().filter(|name| !name.is_empty()),
+            tag_name: Some(release.tag_name.clone()),
+            ts: release.published_at,
+            url: release.url.clone(),
+            assets_count: None,
+        })
     }
 }
 
-/// GitHub repository url regular expression.
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLRef {
+    name: String,
+    target: Option<GitHubGraphQLCommit>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitHubGraphQLCommit {
+    authored_date: Option<DateTime<Utc>>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLLanguages {
+    #[serde(default)]
+    edges: Vec<GitHubGraphQLLanguageEdge>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLLanguageEdge {
+    size: i64,
+    node: GitHubGraphQLLanguage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLLanguage {
+    name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitHubGraphQLRelease {
+    name: Option<String>,
+    published_at: Option<DateTime<Utc>>,
+    tag_name: String,
+    url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLLicense {
+    name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLTopics {
+    #[serde(default)]
+    nodes: Vec<GitHubGraphQLTopicNode>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLTopicNode {
+    topic: GitHubGraphQLTopic,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubGraphQLTopic {
+    name: String,
+}
+
+/// GitHub repository url regular expression (github.com or GitHub Enterprise
+/// Server instances).
 pub(crate) static GITHUB_REPO_URL: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new("^https://github.com/(?P<owner>[^/]+)/(?P<repo>[^/]+)/?$")
+    Regex::new("^(?P<base>https?://[^/]+)/(?P<owner>[^/]+)/(?P<repo>[^/]+)/?$")
         .expect("exprs in GITHUB_REPO_URL to be valid")
 });
 
@@ -340,10 +777,16 @@ fn get_last_page(headers: &HeaderMap) -> Result<Option<usize>> {
     Ok(None)
 }
 
-/// Extract the owner and repository from the repository url provided.
-fn get_owner_and_repo(repo_url: &str) -> Result<(String, String)> {
-    let c = GITHUB_REPO_URL.captures(repo_url).ok_or_else(|| format_err!("invalid repository url"))?;
-    Ok((c["owner"].to_string(), c["repo"].to_string()))
+/// Extract the instance base url, the owner and the repository from the
+/// repository url provided.
+fn parse_github_url(repo_url: &str) -> Option<(String, String, String)> {
+    GITHUB_REPO_URL.captures(repo_url).map(|c| {
+        (
+            c["base"].to_string(),
+            c["owner"].to_string(),
+            c["repo"].to_string(),
+        )
+    })
 }
 
 /// Create a new commit instance from the octorust commit data provided.
@@ -351,6 +794,7 @@ fn new_commit_from(value: octorust::types::CommitDataType) -> Commit {
     let mut commit = Commit {
         url: value.html_url,
         ts: None,
+        ..Default::default()
     };
     if let Some(author) = value.commit.author {
         commit.ts = Some(DateTime::parse_from_rfc3339(&author.date).expect("date to be valid").into());
@@ -358,10 +802,286 @@ fn new_commit_from(value: octorust::types::CommitDataType) -> Commit {
     commit
 }
 
+/// Create a new repository instance from the octorust repository data
+/// provided.
+fn new_repository_from(value: FullRepository) -> GitHubRepository {
+    GitHubRepository {
+        default_branch: value.default_branch,
+        description: value.description,
+        html_url: value.html_url,
+        license: value.license.map(|l| l.name),
+        stargazers_count: value.stargazers_count,
+        topics: value.topics,
+    }
+}
+
 /// Create a new release instance from the octorust release data provided.
 fn new_release_from(value: octorust::types::Release) -> Release {
     Release {
+        name: Some(value.name).filter(|name| !name.is_empty()),
+        tag_name: Some(value.tag_name).filter(|tag_name| !tag_name.is_empty()),
         ts: value.published_at,
         url: value.html_url,
+        assets_count: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_github_url_github_com() {
+        assert_eq!(
+            parse_github_url("https://github.com/owner/repo/"),
+            Some((
+                "https://github.com".to_string(),
+                "owner".to_string(),
+                "repo".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_github_url_ghes() {
+        assert_eq!(
+            parse_github_url("https://github.example.com/owner/repo"),
+            Some((
+                "https://github.example.com".to_string(),
+                "owner".to_string(),
+                "repo".to_string()
+            ))
+        );
+        assert_eq!(parse_github_url("https://github.example.com/owner"), None);
+        assert_eq!(
+            parse_github_url("https://github.example.com/owner/repo/tree/main"),
+            None
+        );
+    }
+
+    #[test]
+    fn get_api_url_github_com_and_ghes() {
+        assert_eq!(get_api_url("https://github.com"), GITHUB_API_URL);
+        assert_eq!(
+            get_api_url("https://github.example.com/"),
+            "https://github.example.com/api/v3"
+        );
+    }
+
+    #[test]
+    fn get_graphql_url_github_com_and_ghes() {
+        assert_eq!(get_graphql_url("https://github.com"), GITHUB_GRAPHQL_URL);
+        assert_eq!(
+            get_graphql_url("https://github.example.com/"),
+            "https://github.example.com/api/graphql"
+        );
+    }
+
+    #[test]
+    fn github_graphql_response_parsing() {
+        let response: GitHubGraphQLResponse = serde_json::from_str(GRAPHQL_RESPONSE).unwrap();
+        let repository = response.data.and_then(|data| data.repository).unwrap();
+
+        assert_eq!(
+            repository.languages(),
+            BTreeMap::from([("Go".to_string(), 2048), ("Rust".to_string(), 4096)])
+        );
+        assert_eq!(
+            repository.latest_commit("main"),
+            Some(Commit {
+                ts: Some("2024-05-01T10:00:00Z".parse().unwrap()),
+                url: "https://github.com/owner/repo/commit/abc".to_string(),
+                ..Default::default()
+            })
+        );
+        assert_eq!(repository.latest_commit("other"), None);
+        assert_eq!(
+            repository.latest_release(),
+            Some(Release {
+                name: Some("Release 1.0.0".to_string()),
+                tag_name: Some("v1.0.0".to_string()),
+                ts: Some("2024-04-01T10:00:00Z".parse().unwrap()),
+                url: "https://github.com/owner/repo/releases/tag/v1.0.0".to_string(),
+                assets_count: None,
+            })
+        );
+        assert_eq!(
+            repository.into_repository(),
+            GitHubRepository {
+                default_branch: "main".to_string(),
+                description: "Repository description".to_string(),
+                html_url: "https://github.com/owner/repo".to_string(),
+                license: Some("Apache License 2.0".to_string()),
+                stargazers_count: 42,
+                topics: vec!["topic1".to_string(), "topic2".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn github_graphql_response_parsing_empty_repository() {
+        let response: GitHubGraphQLResponse = serde_json::from_str(
+            r#"{
+                "data": {
+                    "repository": {
+                        "defaultBranchRef": null,
+                        "description": null,
+                        "languages": { "edges": [] },
+                        "latestRelease": null,
+                        "licenseInfo": null,
+                        "repositoryTopics": { "nodes": [] },
+                        "stargazerCount": 0,
+                        "url": "https://github.com/owner/repo"
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let repository = response.data.and_then(|data| data.repository).unwrap();
+
+        assert!(repository.languages().is_empty());
+        assert_eq!(repository.latest_commit("main"), None);
+        assert_eq!(repository.latest_release(), None);
+        assert_eq!(
+            repository.into_repository(),
+            GitHubRepository {
+                html_url: "https://github.com/owner/repo".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn ghgraphql_get_repository_reuses_query_results() {
+        let mut server = mockito::Server::new_async().await;
+        let graphql_mock = server
+            .mock("POST", "/api/graphql")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "variables": { "owner": "owner", "name": "repo" }
+            })))
+            .with_body(GRAPHQL_RESPONSE)
+            .expect(1)
+            .create_async()
+            .await;
+        let rest_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/api/v3/".into()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let gh = GHGraphQL::new(GHApi::new(&server.url(), "token").unwrap(), &server.url());
+        let repository = gh.get_repository("owner", "repo").await.unwrap();
+        assert_eq!(repository.default_branch, "main");
+        assert_eq!(repository.stargazers_count, 42);
+        assert_eq!(
+            gh.get_languages("owner", "repo").await.unwrap(),
+            Some(BTreeMap::from([
+                ("Go".to_string(), 2048),
+                ("Rust".to_string(), 4096)
+            ]))
+        );
+        assert_eq!(
+            gh.get_latest_commit("owner", "repo", "main").await.unwrap().url,
+            "https://github.com/owner/repo/commit/abc"
+        );
+        assert_eq!(
+            gh.get_latest_release("owner", "repo").await.unwrap().map(|r| r.url),
+            Some("https://github.com/owner/repo/releases/tag/v1.0.0".to_string())
+        );
+
+        graphql_mock.assert_async().await;
+        rest_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn ghgraphql_get_repository_not_found() {
+        let mut server = mockito::Server::new_async().await;
+        let _graphql_mock = server
+            .mock("POST", "/api/graphql")
+            .with_body(r#"{"data": {"repository": null}, "errors": [{"type": "NOT_FOUND"}]}"#)
+            .create_async()
+            .await;
+
+        let gh = GHGraphQL::new(GHApi::new(&server.url(), "token").unwrap(), &server.url());
+        let err = gh.get_repository("owner", "repo").await.unwrap_err();
+        assert!(err.to_string().contains("NOT_FOUND"));
+        assert!(gh.cached_repository("owner", "repo").is_none());
+    }
+
+    #[tokio::test]
+    async fn get_client_fails_when_no_clients_available() {
+        let gh_pool: Pool<DynGH> = Pool::from(Vec::<DynGH>::new());
+        let Err(err) = get_client(&gh_pool).await else {
+            panic!("expected an error getting a client");
+        };
+        assert_eq!(err.to_string(), "no github clients available for instance");
+
+        let gh_pool: Pool<DynGH> = Pool::from(vec![Box::new(MockGH::new()) as DynGH]);
+        gh_pool.close();
+        assert!(get_client(&gh_pool).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn github_api_collector_returns_error_when_pool_is_closed() {
+        let gh_pool: Pool<DynGH> = Pool::from(vec![Box::new(MockGH::new()) as DynGH]);
+        gh_pool.close();
+        let collector = GitHubApiCollector {
+            gh_pools: HashMap::from([("https://github.com".to_string(), gh_pool)]),
+        };
+        assert!(collector.collect_repository_data("https://github.com/owner/repo").await.is_err());
+    }
+
+    /// GraphQL API response used in the tests.
+    const GRAPHQL_RESPONSE: &str = r#"{
+        "data": {
+            "repository": {
+                "defaultBranchRef": {
+                    "name": "main",
+                    "target": {
+                        "authoredDate": "2024-05-01T10:00:00Z",
+                        "url": "https://github.com/owner/repo/commit/abc"
+                    }
+                },
+                "description": "Repository description",
+                "languages": {
+                    "edges": [
+                        { "size": 4096, "node": { "name": "Rust" } },
+                        { "size": 2048, "node": { "name": "Go" } }
+                    ]
+                },
+                "latestRelease": {
+                    "name": "Release 1.0.0",
+                    "publishedAt": "2024-04-01T10:00:00Z",
+                    "tagName": "v1.0.0",
+                    "url": "https://github.com/owner/repo/releases/tag/v1.0.0"
+                },
+                "licenseInfo": { "name": "Apache License 2.0" },
+                "repositoryTopics": {
+                    "nodes": [{ "topic": { "name": "topic1" } }, { "topic": { "name": "topic2" } }]
+                },
+                "stargazerCount": 42,
+                "url": "https://github.com/owner/repo"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn parse_instances_tokens_defaults_to_github_com() {
+        assert_eq!(
+            parse_instances_tokens(
+                "token1,token2;https://github.example.com;token3",
+                DEFAULT_GITHUB_URL
+            ),
+            vec![
+                InstanceTokens {
+                    base_url: DEFAULT_GITHUB_URL.to_string(),
+                    tokens: vec!["token1".to_string(), "token2".to_string()],
+                },
+                InstanceTokens {
+                    base_url: "https://github.example.com".to_string(),
+                    tokens: vec!["token3".to_string()],
+                },
+            ]
+        );
     }
 }
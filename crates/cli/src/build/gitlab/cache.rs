@@ -0,0 +1,230 @@
+//! This module provides the helpers used to read and write the GitLab data
+//! cached across builds.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use landscape2_core::data::{GitData, RepositoryGitData};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use super::{GITLAB_CACHE_FILE, GITLAB_ERRORS_CACHE_FILE};
+use crate::build::cache::Cache;
+
+/// File used to cache data collected from GitLab in previous versions. It's
+/// only read when the current cache file is not available yet.
+const LEGACY_GITLAB_CACHE_FILE: &str = "gitlab.json";
+
+/// How long a permanent failure fetching a repository is remembered (in
+/// hours), so that the repository is not fetched again until it expires.
+pub(super) const GITLAB_ERRORS_CACHE_TTL: i64 = 24;
+
+/// Writer of the GitLab data collected so far to the cache, used to flush it
+/// periodically during long builds. The data is written by a background task,
+/// so that the collection does not wait for the writes to complete.
+pub(super) struct PartialCacheWriter {
+    tx: tokio::sync::mpsc::UnboundedSender<(String, RepositoryGitData)>,
+    writer: tokio::task::JoinHandle<()>,
+}
+
+impl PartialCacheWriter {
+    /// Create a new partial cache writer that flushes the data collected to
+    /// the cache every time the number of repositories provided have been
+    /// collected. The cached data provided is kept in the partial writes until
+    /// the repositories are collected again.
+    pub fn new(cache: &Cache, cached_data: Option<&GitData>, flush_interval: usize) -> Self {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<(String, RepositoryGitData)>();
+        let cache = cache.clone();
+        let mut data = cached_data.cloned().unwrap_or_default();
+        let writer = tokio::task::spawn_blocking(move || {
+            let mut pending = 0;
+            while let Some((url, repo)) = rx.blocking_recv() {
+                data.insert(url, repo);
+                pending += 1;
+                if pending < flush_interval {
+                    continue;
+                }
+                pending = 0;
+                match serde_json::to_vec_pretty(&data) {
+                    Ok(json_data) => match cache.write(GITLAB_CACHE_FILE, &json_data) {
+                        Ok(()) => debug!("flushed {} gitlab repositories to cache", data.len()),
+                        Err(err) => warn!("error writing partial gitlab cache: {err:?}"),
+                    },
+                    Err(err) => warn!("error serializing partial gitlab cache: {err:?}"),
+                }
+            }
+        });
+        Self { tx, writer }
+    }
+
+    /// Record the result of processing a repository, sending it to the writer.
+    /// Only repositories collected successfully from the instance count
+    /// towards the flush interval.
+    pub fn record(&self, url: &str, result: &Result<RepositoryGitData>) {
+        let Ok(repo) = result else {
+            return;
+        };
+        if repo.from_cache {
+            return;
+        }
+        _ = self.tx.send((url.to_string(), repo.clone()));
+    }
+
+    /// Wait for the pending writes to complete, so that they cannot overwrite
+    /// any later write of the cache.
+    pub async fn finish(self) {
+        drop(self.tx);
+        if let Err(err) = self.writer.await {
+            warn!("error writing partial gitlab cache: {err:?}");
+        }
+    }
+}
+
+/// Read the GitLab data and failed fetches from the cache (if available).
+pub(super) fn read_cache(cache: &Cache) -> (Option<GitData>, FailedFetches) {
+    // Read cached data (if available)
+    let mut cached_data: Option<GitData> = None;
+    let cache_file = match cache.read(GITLAB_CACHE_FILE) {
+        Ok(None) => cache.read(LEGACY_GITLAB_CACHE_FILE),
+        cache_file => cache_file,
+    };
+    match cache_file {
+        Ok(Some((_, json_data))) => match serde_json::from_slice(&json_data) {
+            Ok(gitlab_data) => cached_data = Some(gitlab_data),
+            Err(err) => warn!("error parsing gitlab cache file: {err:?}"),
+        },
+        Ok(None) => {}
+        Err(err) => warn!("error reading gitlab cache file: {err:?}"),
+    }
+
+    // Read cached failed fetches (if available)
+    let mut cached_failed_fetches = FailedFetches::new();
+    match cache.read(GITLAB_ERRORS_CACHE_FILE) {
+        Ok(Some((_, json_data))) => match serde_json::from_slice(&json_data) {
+            Ok(failed_fetches) => cached_failed_fetches = failed_fetches,
+            Err(err) => warn!("error parsing gitlab errors cache file: {err:?}"),
+        },
+        Ok(None) => {}
+        Err(err) => warn!("error reading gitlab errors cache file: {err:?}"),
+    }
+
+    (cached_data, cached_failed_fetches)
+}
+
+/// Return a copy of the cached repository data provided, marked as taken from
+/// the cache.
+pub(super) fn from_cache(cached_repo: &RepositoryGitData) -> RepositoryGitData {
+    RepositoryGitData {
+        from_cache: true,
+        ..cached_repo.clone()
+    }
+}
+
+/// Keep the cached data and failed fetches (when available) of the
+/// repositories provided, which were not collected.
+pub(super) fn keep_cached_data(
+    urls: &[String],
+    cached_data: Option<&GitData>,
+    cached_failed_fetches: &FailedFetches,
+    gitlab_data: &mut GitData,
+    failed_fetches: &mut FailedFetches,
+) {
+    for url in urls {
+        if let Some(cached_repo) = cached_data.and_then(|cache| cache.get(url)) {
+            gitlab_data.insert(url.clone(), from_cache(cached_repo));
+        }
+        if let Some(failed_fetch) = cached_failed_fetches.get(url).filter(|f| !f.has_expired()) {
+            failed_fetches.insert(url.clone(), failed_fetch.clone());
+        }
+    }
+}
+
+/// Return the cached data of the repositories provided, regardless of its age.
+/// Repositories without cached data are skipped.
+pub(super) fn get_offline_data(urls: &[String], cached_data: Option<&GitData>) -> GitData {
+    let mut gitlab_data = GitData::new();
+    for url in urls {
+        if let Some(cached_repo) = cached_data.and_then(|cache| cache.get(url)) {
+            gitlab_data.insert(url.clone(), from_cache(cached_repo));
+        } else {
+            warn!("offline mode: no cached gitlab data for {url} (skipped)");
+        }
+    }
+    debug!(
+        "using cached data for {} gitlab repositories (offline mode)",
+        gitlab_data.len()
+    );
+    gitlab_data
+}
+
+/// Type alias to represent the repositories that failed permanently, keyed by
+/// their url.
+pub(super) type FailedFetches = BTreeMap<String, FailedFetch>;
+
+/// Details about a repository fetch that failed permanently.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct FailedFetch {
+    pub failed_at: DateTime<Utc>,
+    pub status: u16,
+}
+
+impl FailedFetch {
+    /// Check if the failed fetch has expired, meaning the repository can be
+    /// fetched again.
+    pub fn has_expired(&self) -> bool {
+        self.failed_at + chrono::Duration::hours(GITLAB_ERRORS_CACHE_TTL) <= Utc::now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use anyhow::format_err;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn partial_cache_writer_flushes_collected_repositories() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let repo = |description: &str| RepositoryGitData {
+            description: description.to_string(),
+            ..Default::default()
+        };
+        let cached_data = GitData::from([
+            ("https://gitlab.com/group/a".to_string(), repo("cached")),
+            ("https://gitlab.com/group/b".to_string(), repo("cached")),
+        ]);
+        let partial_cache = PartialCacheWriter::new(&cache, Some(&cached_data), 2);
+
+        // Failures and cache hits don't count towards the flush interval
+        partial_cache.record("https://gitlab.com/group/a", &Ok(repo("new")));
+        partial_cache.record("https://gitlab.com/group/c", &Err(format_err!("error")));
+        partial_cache.record(
+            "https://gitlab.com/group/b",
+            &Ok(RepositoryGitData {
+                from_cache: true,
+                ..repo("cached")
+            }),
+        );
+        assert!(cache.read(GITLAB_CACHE_FILE).unwrap().is_none());
+
+        // The build is interrupted after the second repository is collected
+        partial_cache.record("https://gitlab.com/group/d", &Ok(repo("new")));
+        partial_cache.record("https://gitlab.com/group/e", &Ok(repo("new")));
+        partial_cache.finish().await;
+
+        let (cached_data, _) = read_cache(&cache);
+        let descriptions: Vec<_> =
+            cached_data.unwrap().into_iter().map(|(url, repo)| (url, repo.description)).collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                ("https://gitlab.com/group/a".to_string(), "new".to_string()),
+                ("https://gitlab.com/group/b".to_string(), "cached".to_string()),
+                ("https://gitlab.com/group/d".to_string(), "new".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,2754 @@
+//! This module defines the client used to interact with the GitLab REST API,
+//! as well as the errors it returns.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail, format_err};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use deadpool::unmanaged::Pool;
+use futures::future;
+use gitlab::api::common::{NameOrId, SortOrder};
+use gitlab::api::groups::projects::GroupProjects;
+use gitlab::api::projects::Project;
+use gitlab::api::projects::releases::{ProjectReleaseOrderBy, ProjectReleases};
+use gitlab::api::projects::repository::commits::Commits;
+use gitlab::api::projects::repository::contributors::Contributors;
+use gitlab::api::{self, ApiError, AsyncClient, AsyncQuery, Pagination, RestClient};
+use gitlab::{AsyncGitlab, Gitlab, GitlabBuilder, RestError};
+use landscape2_core::data::Commit;
+#[cfg(test)]
+use mockall::automock;
+use reqwest::StatusCode;
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue, RETRY_AFTER};
+use tracing::{debug, instrument, warn};
+use url::Url;
+
+use super::{
+    COMMIT_ACTIVITY_WEEKS, ClientOptions, GITLAB_PROXY, GitLabApi, MAX_RECENT_COMMITS_COUNT,
+    bucket_weekly_commit_activity,
+    dto::{
+        GitLabCommit, GitLabContributor, GitLabContributors, GitLabGroupProject, GitLabIssuesStatistics,
+        GitLabPersonalAccessToken, GitLabPipeline, GitLabProject, GitLabRelease, GitLabReleaseAssets,
+        PrereleasesFilter, select_latest_release,
+    },
+    graphql::GLGraphQL,
+    retry::{GLRetry, RequestBudget, RequestBudgetExhausted},
+    tokens::{GLTokenRefresh, mask_token, missing_token_scopes},
+};
+
+/// Prefix used to identify the GitLab tokens that are OAuth2 tokens.
+const OAUTH2_TOKEN_PREFIX: &str = "oauth2:";
+
+/// Maximum time we are willing to wait when GitLab asks us to retry later.
+pub(super) const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// Maximum number of redirects followed by the HTTP client (i.e. when a
+/// project has been renamed or moved to a different namespace).
+const MAX_REDIRECTS: usize = 10;
+
+/// Number of most recent releases fetched to select the latest one from.
+const RELEASES_PAGE_SIZE: usize = 20;
+
+/// Number of contributors requested in the first page of the contributors
+/// endpoint (the top contributors are taken from it).
+const CONTRIBUTORS_PAGE_SIZE: usize = 100;
+
+/// Instances where an endpoint is not available (i.e. it has been disabled by
+/// the administrators), shared by all the clients used during the build so
+/// that it's reported only once per instance.
+#[derive(Debug, Default)]
+pub(super) struct UnavailableEndpointInstances {
+    base_urls: Mutex<HashSet<String>>,
+}
+
+impl UnavailableEndpointInstances {
+    /// Record that the endpoint is not available on the instance provided,
+    /// returning true if it's the first time it's recorded.
+    fn record(&self, base_url: &str) -> bool {
+        self.base_urls.lock().expect("lock not to be poisoned").insert(base_url.to_string())
+    }
+}
+
+/// Create a pool of GitLab API clients for the given instance. Each token is
+/// checked against the instance before being added to the pool, and the ones
+/// that cannot be used (expired, revoked, etc) are discarded. When none of the
+/// tokens provided is valid, no pool is returned. When requested, the scopes
+/// of the valid tokens are checked as well, warning about the missing ones.
+/// The clients use the token provider in the options (if any) to replace the
+/// tokens that expire while the pool is in use. The clients are created
+/// concurrently, keeping the order of the tokens in the pool.
+pub(super) async fn create_gitlab_pool(
+    base_url: &str,
+    tokens: &[String],
+    options: &ClientOptions,
+) -> Option<Pool<DynGL>> {
+    let gl_clients: Vec<DynGL> = future::join_all(tokens.iter().map(|token| async move {
+        match GLApi::new(base_url, token, options).await {
+            Ok(gl) => {
+                if options.check_token_scopes {
+                    match gl.get_token_scopes().await {
+                        Ok(Some(scopes)) => {
+                            for scope in missing_token_scopes(&scopes) {
+                                warn!(
+                                    "gitlab token {} for instance {base_url} is missing the {scope} scope \
+                                    (please add it to the token to collect all repositories information)",
+                                    mask_token(token)
+                                );
+                            }
+                        }
+                        Ok(None) => debug!("scopes of gitlab token {} not available", mask_token(token)),
+                        Err(err) => warn!(
+                            "error checking scopes of gitlab token {}: {err}",
+                            mask_token(token)
+                        ),
+                    }
+                }
+                Some(new_gl_client(gl, Some(token), options))
+            }
+            Err(err) => {
+                warn!(
+                    "invalid gitlab token {} for instance {base_url} (it will not be used): {err}",
+                    mask_token(token)
+                );
+                None
+            }
+        }
+    }))
+    .await
+    .into_iter()
+    .flatten()
+    .collect();
+    if gl_clients.is_empty() {
+        return None;
+    }
+    Some(Pool::from(gl_clients))
+}
+
+/// Create a pool with a single unauthenticated GitLab API client for the given
+/// instance, which can only be used to collect public repositories data.
+pub(super) async fn create_unauthenticated_gitlab_pool(
+    base_url: &str,
+    options: &ClientOptions,
+) -> Option<Pool<DynGL>> {
+    match GLApi::new_unauthenticated(base_url, options).await {
+        Ok(gl) => Some(Pool::from(vec![new_gl_client(gl, None, options)])),
+        Err(err) => {
+            warn!("error setting up unauthenticated gitlab client for instance {base_url}: {err}");
+            None
+        }
+    }
+}
+
+/// Wrap the GitLab API client provided using the API and retry configuration
+/// in the options given. When a token provider is configured, the client will
+/// be rebuilt using a fresh token when the token provided expires.
+fn new_gl_client(gl: GLApi, token: Option<&str>, options: &ClientOptions) -> DynGL {
+    let base_url = gl.base_url.clone();
+    let mut gl = wrap_gl_api(gl, options);
+    if let (Some(token), Some(token_provider)) = (token, &options.token_provider) {
+        gl = Box::new(GLTokenRefresh::new(
+            &base_url,
+            token,
+            gl,
+            options,
+            token_provider.clone(),
+        ));
+    }
+    Box::new(GLRetry::new(gl, options.retry_config))
+}
+
+/// Wrap the GitLab API client provided to use the API in the options given.
+pub(super) fn wrap_gl_api(gl: GLApi, options: &ClientOptions) -> DynGL {
+    match options.api {
+        GitLabApi::Graphql => Box::new(GLGraphQL::new(gl, &options.collection)),
+        GitLabApi::Rest => Box::new(gl),
+    }
+}
+
+/// Create a new HTTP client to make direct requests to the GitLab API using
+/// the token (if any) and requests timeout provided. OAuth2 tokens are sent as
+/// bearer tokens, whereas the rest are sent as private tokens.
+fn new_http_client(
+    token: Option<&str>,
+    timeout: Duration,
+    ca_certs: &[reqwest::Certificate],
+    user_agent: &str,
+    proxy: Option<&str>,
+) -> Result<reqwest::Client> {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = token {
+        if let Some(token) = token.strip_prefix(OAUTH2_TOKEN_PREFIX) {
+            headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {token}"))?);
+        } else {
+            headers.insert("PRIVATE-TOKEN", HeaderValue::from_str(token)?);
+        }
+    }
+    let mut builder = reqwest::Client::builder()
+        .default_headers(headers)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+        .timeout(timeout)
+        .user_agent(user_agent);
+    for cert in ca_certs {
+        builder = builder.add_root_certificate(cert.clone());
+    }
+    if let Some(proxy) = proxy {
+        builder = builder
+            .proxy(reqwest::Proxy::all(proxy).with_context(|| format!("invalid {GITLAB_PROXY} value"))?);
+    }
+    let http_client = builder.build()?;
+    Ok(http_client)
+}
+
+/// Get the numeric project id from the project path provided, when the
+/// repository url references the project by its id (i.e. `-/project/12345`).
+fn project_id(project_path: &str) -> Option<&str> {
+    project_path
+        .strip_prefix("-/project/")
+        .filter(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Check if the project path provided references the project by its id.
+pub(super) fn is_project_id(project_path: &str) -> bool {
+    project_id(project_path).is_some()
+}
+
+/// Return the reference to the project provided to use in the GitLab API
+/// endpoints, which accept both project ids and paths.
+fn project_ref(project_path: &str) -> NameOrId<'_> {
+    match project_id(project_path).and_then(|id| id.parse().ok()) {
+        Some(id) => NameOrId::Id(id),
+        None => NameOrId::Name(Cow::Borrowed(project_path)),
+    }
+}
+
+/// Encode the project provided to be used in the GitLab API urls requested
+/// directly. Numeric project ids are used as they are.
+fn encode_project_path(project_path: &str) -> Cow<'_, str> {
+    if let Some(id) = project_id(project_path) {
+        return Cow::Borrowed(id);
+    }
+    urlencoding::encode(project_path)
+}
+
+/// Decode the project path provided, as repositories urls in the landscape may
+/// include percent-encoded characters, so that it's encoded only once when
+/// used in the GitLab API urls. Paths GitLab would never accept (i.e. empty
+/// segments or containing whitespaces) are rejected.
+pub(super) fn decode_project_path(project_path: &str) -> Result<String> {
+    let path = urlencoding::decode(project_path)
+        .map_err(|err| format_err!("invalid gitlab project path {project_path}: {err}"))?;
+    if path.split('/').any(str::is_empty) || path.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        bail!("invalid gitlab project path: {project_path}");
+    }
+    Ok(path.into_owned())
+}
+
+/// Load the root CA certificates from the PEM bundle provided.
+fn load_ca_bundle(path: &Path) -> Result<Vec<reqwest::Certificate>> {
+    let pem = fs::read(path).with_context(|| format!("error reading gitlab CA bundle {}", path.display()))?;
+    let certs = reqwest::Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("invalid gitlab CA bundle {}", path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificates found in gitlab CA bundle {}", path.display());
+    }
+    Ok(certs)
+}
+
+/// Type alias to represent a GL trait object.
+pub(super) type DynGL = Box<dyn GL + Send + Sync>;
+
+/// Trait that defines some operations a GL implementation must support.
+#[async_trait]
+#[cfg_attr(test, automock)]
+// The fields of the mock generated share the same prefix
+#[cfg_attr(test, allow(clippy::struct_field_names))]
+pub(super) trait GL {
+    /// Get number of repository contributors and the top ones.
+    async fn get_contributors(&self, project_path: &str) -> Result<GitLabContributors, GitlabError>;
+
+    /// Get first commit.
+    async fn get_first_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError>;
+
+    /// Get count of good first issues.
+    async fn get_good_first_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError>;
+
+    /// Get the paths of the projects in a group (including its subgroups).
+    async fn get_group_projects(&self, group_path: &str) -> Result<Vec<String>, GitlabError>;
+
+    /// Get count of help wanted issues.
+    async fn get_help_wanted_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError>;
+
+    /// Get languages used in repository (as percentages).
+    async fn get_languages(&self, project_path: &str) -> Result<Option<BTreeMap<String, f64>>, GitlabError>;
+
+    /// Get latest commit.
+    async fn get_latest_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError>;
+
+    /// Get latest pipeline status.
+    async fn get_latest_pipeline_status(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<String>, GitlabError>;
+
+    /// Get latest release.
+    async fn get_latest_release(
+        &self,
+        project_path: &str,
+    ) -> Result<Option<landscape2_core::data::Release>, GitlabError>;
+
+    /// Get count of open issues.
+    async fn get_open_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError>;
+
+    /// Get count of open merge requests.
+    async fn get_open_mrs_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError>;
+
+    /// Get project.
+    async fn get_project(&self, project_path: &str) -> Result<GitLabProject, GitlabError>;
+
+    /// Get count of commits since the date provided.
+    async fn get_recent_commit_count(
+        &self,
+        project_path: &str,
+        ref_: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<usize>, GitlabError>;
+
+    /// Get number of commits in each of the last weeks (when enabled).
+    async fn get_weekly_commit_activity(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<Vec<u32>>, GitlabError>;
+}
+
+/// GH implementation backed by the GitLab API.
+#[allow(clippy::struct_excessive_bools)]
+pub(super) struct GLApi {
+    authenticated: bool,
+    base_url: String,
+    client: AsyncGitlab,
+    commit_activity: bool,
+    good_first_issues_labels: Vec<String>,
+    help_wanted_labels: Vec<String>,
+    pub http_client: reqwest::Client,
+    languages_unavailable: Arc<UnavailableEndpointInstances>,
+    max_response_size: usize,
+    prereleases_filter: Option<PrereleasesFilter>,
+    project_license: bool,
+    project_statistics: bool,
+    rate_limit_warning_threshold: u64,
+    request_budget: Arc<RequestBudget>,
+}
+
+impl GLApi {
+    /// Create a new GLApi instance.
+    pub async fn new(base_url: &str, token: &str, options: &ClientOptions) -> Result<Self> {
+        Self::new_with_token(base_url, Some(token), options).await
+    }
+
+    /// Create a new GLApi instance that makes unauthenticated requests, so it
+    /// can only access the data of public projects.
+    async fn new_unauthenticated(base_url: &str, options: &ClientOptions) -> Result<Self> {
+        Self::new_with_token(base_url, None, options).await
+    }
+
+    /// Create a new GLApi instance using the token provided (if any).
+    async fn new_with_token(base_url: &str, token: Option<&str>, options: &ClientOptions) -> Result<Self> {
+        // Strip protocol from base_url - gitlab crate adds it automatically, so
+        // we need to tell it explicitly when the instance is served over http
+        let (host, insecure) = if let Some(host) = base_url.strip_prefix("http://") {
+            (host, true)
+        } else {
+            (base_url.trim_start_matches("https://"), false)
+        };
+
+        // Load the custom root CA certificates (if any) before contacting the
+        // instance, so that an invalid bundle is reported clearly
+        let ca_certs = match &options.ca_bundle {
+            Some(path) => load_ca_bundle(path)?,
+            None => vec![],
+        };
+
+        // The gitlab crate builder does not allow setting a timeout, custom
+        // root certificates or a proxy on the async client it uses, so only the
+        // requests made directly are bounded and use the CA bundle and proxy
+        // (the certificates must be trusted by the system for the requests made
+        // by the crate, which only honors the standard proxy variables)
+        let mut builder = match token {
+            Some(token) => Gitlab::builder(host, token.strip_prefix(OAUTH2_TOKEN_PREFIX).unwrap_or(token)),
+            None => GitlabBuilder::new_unauthenticated(host),
+        };
+        builder.user_agent(&options.user_agent);
+        if insecure {
+            builder.insecure();
+        }
+        if token.is_some_and(|token| token.starts_with(OAUTH2_TOKEN_PREFIX)) {
+            builder.oauth2_token();
+        }
+        let client = builder.build_async().await?;
+
+        // Setup HTTP client for direct API calls
+        let http_client = new_http_client(
+            token,
+            options.http_timeout,
+            &ca_certs,
+            &options.user_agent,
+            options.proxy.as_deref(),
+        )?;
+
+        Ok(Self {
+            authenticated: token.is_some(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+            commit_activity: options.commit_activity,
+            good_first_issues_labels: options.good_first_issues_labels.clone(),
+            help_wanted_labels: options.help_wanted_labels.clone(),
+            http_client,
+            languages_unavailable: options.languages_unavailable.clone(),
+            max_response_size: options.max_response_size,
+            prereleases_filter: options.prereleases_filter.clone(),
+            project_license: options.collection.license,
+            project_statistics: options.collection.project_statistics,
+            rate_limit_warning_threshold: options.rate_limit_warning_threshold,
+            request_budget: options.request_budget.clone(),
+        })
+    }
+
+    /// Return the url of the REST API endpoint provided, using the same scheme
+    /// as the instance base url.
+    fn api_url(&self, endpoint: &str) -> String {
+        format!("{}/api/v4/{endpoint}", self.base_url)
+    }
+
+    /// Return the url of the GraphQL API endpoint.
+    pub fn graphql_url(&self) -> String {
+        format!("{}/api/graphql", self.base_url)
+    }
+
+    /// Get the number of opened issues in the project provided matching the
+    /// additional query parameters given (if any), using the issues statistics
+    /// endpoint.
+    async fn get_opened_issues_count(&self, project_path: &str, params: &str) -> Result<Option<usize>> {
+        let encoded_path = encode_project_path(project_path);
+        let url = self.api_url(&format!(
+            "projects/{encoded_path}/issues_statistics?{params}state=opened"
+        ));
+
+        debug!(
+            "Fetching issues statistics for {} from URL: {}",
+            project_path, url
+        );
+
+        let response = self.get(&url).await?;
+
+        if !response.status().is_success() {
+            debug!(
+                "Failed to get issues statistics for {}: status {}",
+                project_path,
+                response.status()
+            );
+            return Ok(None);
+        }
+
+        let Some(response_text) = self.read_body(response).await? else {
+            warn!(
+                "issues statistics response for {} exceeds the maximum size ({} bytes)",
+                project_path, self.max_response_size
+            );
+            return Ok(None);
+        };
+        debug!(
+            "Issues statistics API response for {}: {}",
+            project_path, response_text
+        );
+
+        match serde_json::from_str::<GitLabIssuesStatistics>(&response_text) {
+            Ok(stats) => Ok(Some(stats.statistics.counts.opened)),
+            Err(e) => {
+                debug!(
+                    "Failed to parse issues statistics response for {}: {}",
+                    project_path, e
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Get the number of opened issues in the project provided that have any
+    /// of the labels given.
+    ///
+    /// GitLab only returns the issues that have *all* the labels provided, so
+    /// we query the count for each of the labels and add them up. Issues with
+    /// more than one of those labels will be counted once per label. The
+    /// issues statistics are not available to unauthenticated clients.
+    async fn get_labeled_issues_count(&self, project_path: &str, labels: &[String]) -> Result<Option<usize>> {
+        if !self.authenticated {
+            return Ok(None);
+        }
+        let mut count = None;
+        for label in labels {
+            let params = format!("labels={}&", urlencoding::encode(label));
+            if let Some(label_count) = self.get_opened_issues_count(project_path, &params).await? {
+                count = Some(count.unwrap_or_default() + label_count);
+            }
+        }
+        Ok(count)
+    }
+
+    /// Get the scopes of the token used by this client. When the token details
+    /// are not available (i.e. the endpoint is not supported by the instance
+    /// or the token is not a personal access token), None is returned.
+    async fn get_token_scopes(&self) -> Result<Option<Vec<String>>> {
+        let response = self.get(&self.api_url("personal_access_tokens/self")).await?;
+        if !response.status().is_success() {
+            debug!("failed to get token details: status {}", response.status());
+            return Ok(None);
+        }
+
+        let token: GitLabPersonalAccessToken = response.json().await?;
+        Ok(Some(token.scopes))
+    }
+
+    /// Send a GET request to the url provided using the raw HTTP client. When
+    /// the request is rate limited and GitLab tells us how long to wait, the
+    /// request will be retried once after waiting for that duration.
+    async fn get(&self, url: &str) -> Result<reqwest::Response> {
+        self.use_request_budget()?;
+        let response = self.http_client.get(url).send().await?;
+        self.log_rate_limit(response.headers());
+        if let Some(delay) = respect_retry_after(&response) {
+            debug!(?delay, "request rate limited, retrying after delay: {url}");
+            tokio::time::sleep(delay).await;
+            self.use_request_budget()?;
+            let response = self.http_client.get(url).send().await?;
+            self.log_rate_limit(response.headers());
+            return Ok(response);
+        }
+        Ok(response)
+    }
+
+    /// Run the gitlab crate query provided. Each of the requests made uses one
+    /// request from the request budget (paged queries may make several).
+    async fn query<T, Q>(&self, query: &Q) -> Result<T>
+    where
+        Q: for<'a> AsyncQuery<T, BudgetedClient<'a>> + Sync,
+    {
+        let client = BudgetedClient {
+            client: &self.client,
+            request_budget: &self.request_budget,
+        };
+        query.query_async(&client).await.map_err(|err| match err {
+            ApiError::Client {
+                source: BudgetedClientError::BudgetExhausted(err),
+            } => err.into(),
+            ApiError::Client {
+                source: BudgetedClientError::Rest(err),
+            } => ApiError::client(err).into(),
+            err => err.map_client::<_, RestError>(|_| unreachable!("client errors are handled above")).into(),
+        })
+    }
+
+    /// Use one request from the request budget, failing once it's exhausted.
+    pub fn use_request_budget(&self) -> Result<()> {
+        if !self.request_budget.try_use() {
+            return Err(RequestBudgetExhausted.into());
+        }
+        Ok(())
+    }
+
+    /// Read the body of the response provided as text. None is returned when
+    /// it exceeds the maximum response size, which is checked while it's being
+    /// read so that huge bodies are never loaded in memory.
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<Option<String>> {
+        let exceeds_max_size = |size: usize| size > self.max_response_size;
+        if response
+            .content_length()
+            .is_some_and(|len| exceeds_max_size(usize::try_from(len).unwrap_or(usize::MAX)))
+        {
+            return Ok(None);
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if exceeds_max_size(body.len() + chunk.len()) {
+                return Ok(None);
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(Some(String::from_utf8(body)?))
+    }
+
+    /// Log the rate limit information included in the response headers
+    /// provided (if any), warning when the number of remaining requests drops
+    /// below the configured threshold.
+    fn log_rate_limit(&self, headers: &HeaderMap) {
+        let Some(rate_limit) = RateLimit::from_headers(headers) else {
+            return;
+        };
+        debug!(
+            base_url = self.base_url,
+            limit = rate_limit.limit,
+            remaining = rate_limit.remaining,
+            reset = rate_limit.reset,
+            "gitlab rate limit"
+        );
+        if rate_limit.remaining < self.rate_limit_warning_threshold {
+            warn!(
+                "gitlab rate limit almost exhausted for {}: {} requests remaining (reset at {:?})",
+                self.base_url, rate_limit.remaining, rate_limit.reset
+            );
+        }
+    }
+
+    /// [GL::get_contributors]
+    ///
+    /// Only the first page of contributors (ordered by number of commits) is
+    /// requested, as the total number of contributors is reported in the
+    /// `X-Total` header. Enumerating all of them is very expensive for large
+    /// repositories, so we only do it when the header is not available.
+    #[instrument(skip(self), err)]
+    pub async fn get_contributors(&self, project_path: &str) -> Result<GitLabContributors> {
+        let url = self.api_url(&format!(
+            "projects/{}/repository/contributors?order_by=commits&sort=desc&per_page={CONTRIBUTORS_PAGE_SIZE}",
+            encode_project_path(project_path)
+        ));
+        let response = self.get(&url).await?.error_for_status()?;
+        let contributors = if let Some(count) = get_total_count(response.headers()) {
+            GitLabContributors {
+                count,
+                top: response.json().await?,
+            }
+        } else {
+            // The number of contributors is not available, so we need to go
+            // through all of them
+            let endpoint = Contributors::builder().project(project_ref(project_path)).build()?;
+            let top: Vec<GitLabContributor> = self.query(&api::paged(endpoint, Pagination::All)).await?;
+            GitLabContributors {
+                count: top.len(),
+                top,
+            }
+        };
+
+        debug!("GitLab Contributors Response for {}: {:?}", project_path, contributors);
+
+        Ok(contributors)
+    }
+
+    /// [GL::get_first_commit]
+    #[instrument(skip(self), err)]
+    pub async fn get_first_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>> {
+        // Commits are returned from newest to oldest and cannot be sorted the
+        // other way around, so we request one commit per page: the number of
+        // commits is the number of the last page, which contains the oldest
+        let commits_url = |page: usize| {
+            self.api_url(&format!(
+                "projects/{}/repository/commits?ref_name={}&per_page=1&page={page}",
+                encode_project_path(project_path),
+                urlencoding::encode(ref_)
+            ))
+        };
+        let response = self.get(&commits_url(1)).await?.error_for_status()?;
+        let mut commits: Vec<GitLabCommit> = match get_total_count(response.headers()) {
+            Some(total) if total > 1 => {
+                self.get(&commits_url(total)).await?.error_for_status()?.json().await?
+            }
+            Some(_) => response.json().await?,
+            None => {
+                // The number of commits is not available (GitLab omits it for
+                // large projects), so we look for the last page instead
+                let first_page = response.json().await?;
+                self.probe_last_commits_page(commits_url, first_page).await?
+            }
+        };
+
+        // Get the last commit (oldest)
+        if let Some(commit) = commits.pop() {
+            return Ok(Some(commit.into()));
+        }
+
+        Ok(None)
+    }
+
+    /// Look for the last page of commits (one commit per page) when the number
+    /// of commits is not available. The page number is doubled until an empty
+    /// page is found, and then the range between the last non-empty page and
+    /// that one is bisected, so only a logarithmic number of pages is fetched.
+    async fn probe_last_commits_page(
+        &self,
+        commits_url: impl Fn(usize) -> String,
+        first_page: Vec<GitLabCommit>,
+    ) -> Result<Vec<GitLabCommit>> {
+        if first_page.is_empty() {
+            return Ok(first_page);
+        }
+        let get_page = async |page: usize| -> Result<Vec<GitLabCommit>> {
+            Ok(self.get(&commits_url(page)).await?.error_for_status()?.json().await?)
+        };
+
+        let (mut last_page, mut last_commits) = (1, first_page);
+        let mut empty_page = loop {
+            let page = last_page * 2;
+            let commits = get_page(page).await?;
+            if commits.is_empty() {
+                break page;
+            }
+            (last_page, last_commits) = (page, commits);
+        };
+        while empty_page - last_page > 1 {
+            let page = last_page + (empty_page - last_page) / 2;
+            let commits = get_page(page).await?;
+            if commits.is_empty() {
+                empty_page = page;
+            } else {
+                (last_page, last_commits) = (page, commits);
+            }
+        }
+
+        Ok(last_commits)
+    }
+
+    /// [GL::get_good_first_issues_count]
+    #[instrument(skip(self), err)]
+    pub async fn get_good_first_issues_count(&self, project_path: &str) -> Result<Option<usize>> {
+        self.get_labeled_issues_count(project_path, &self.good_first_issues_labels).await
+    }
+
+    /// [GL::get_group_projects]
+    #[instrument(skip(self), err)]
+    pub async fn get_group_projects(&self, group_path: &str) -> Result<Vec<String>> {
+        let endpoint = GroupProjects::builder().group(group_path).include_subgroups(true).build()?;
+
+        let projects: Vec<GitLabGroupProject> = self.query(&api::paged(endpoint, Pagination::All)).await?;
+
+        Ok(projects.into_iter().map(|project| project.path_with_namespace).collect())
+    }
+
+    /// [GL::get_help_wanted_count]
+    #[instrument(skip(self), err)]
+    pub async fn get_help_wanted_count(&self, project_path: &str) -> Result<Option<usize>> {
+        self.get_labeled_issues_count(project_path, &self.help_wanted_labels).await
+    }
+
+    /// [GL::get_languages]
+    #[instrument(skip(self), err)]
+    pub async fn get_languages(&self, project_path: &str) -> Result<Option<BTreeMap<String, f64>>> {
+        let encoded_path = encode_project_path(project_path);
+        let url = self.api_url(&format!("projects/{encoded_path}/languages"));
+
+        debug!("Fetching languages for {} from URL: {}", project_path, url);
+
+        let response = self.get(&url).await?;
+        
+        debug!("Languages API response status for {}: {}", project_path, response.status());
+        
+        // Some instances have the languages endpoint disabled, so we only warn
+        // about it once per instance to avoid flooding the logs
+        if response.status() == StatusCode::FORBIDDEN {
+            debug!("languages not available for {}: status 403", project_path);
+            if self.languages_unavailable.record(&self.base_url) {
+                warn!(
+                    "languages endpoint not available in {} (status 403): languages won't be collected",
+                    self.base_url
+                );
+            }
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            warn!("failed to get languages for {}: status {}", project_path, response.status());
+            return Ok(None);
+        }
+
+        // Get raw response text for debugging
+        let Some(response_text) = self.read_body(response).await? else {
+            warn!(
+                "languages response for {} exceeds the maximum size ({} bytes)",
+                project_path, self.max_response_size
+            );
+            return Ok(None);
+        };
+        debug!("Languages raw API response for {}: {}", project_path, response_text);
+        
+        // GitLab returns percentages as floats
+        let languages: BTreeMap<String, f64> = serde_json::from_str(&response_text)?;
+
+        debug!("Languages parsed response for {}: {:?}", project_path, languages);
+
+        if languages.is_empty() {
+            debug!("No languages found for {}", project_path);
+            return Ok(None);
+        }
+
+        Ok(Some(languages))
+    }
+
+    /// [GL::get_latest_commit]
+    #[instrument(skip(self), err)]
+    pub async fn get_latest_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>> {
+        let endpoint = Commits::builder().project(project_ref(project_path)).ref_name(ref_).build()?;
+
+        let commits: Vec<GitLabCommit> = self.query(&api::paged(endpoint, Pagination::Limit(1))).await?;
+
+        // Empty repositories have no commits
+        Ok(commits.into_iter().next().map(Commit::from))
+    }
+
+    /// [GL::get_latest_pipeline_status]
+    #[instrument(skip(self), err)]
+    pub async fn get_latest_pipeline_status(&self, project_path: &str, ref_: &str) -> Result<Option<String>> {
+        let url = self.api_url(&format!(
+            "projects/{}/pipelines?ref={}&per_page=1",
+            encode_project_path(project_path),
+            urlencoding::encode(ref_)
+        ));
+
+        // Pipelines are not available when CI/CD is disabled in the project
+        let response = self.get(&url).await?;
+        if response.status() == StatusCode::NOT_FOUND || response.status() == StatusCode::FORBIDDEN {
+            debug!(
+                "pipelines not available for {}: status {}",
+                project_path,
+                response.status()
+            );
+            return Ok(None);
+        }
+
+        let pipelines: Vec<GitLabPipeline> = response.error_for_status()?.json().await?;
+        Ok(pipelines.into_iter().next().map(|pipeline| pipeline.status))
+    }
+
+    /// [GL::get_latest_release]
+    #[instrument(skip(self), err)]
+    pub async fn get_latest_release(
+        &self,
+        project_path: &str,
+    ) -> Result<Option<landscape2_core::data::Release>> {
+        // The sort key is set explicitly as the default one may vary between
+        // instances. Some releases may not have a release date, so a page of
+        // them is fetched and the most recent one is selected from it
+        let endpoint = ProjectReleases::builder()
+            .project(project_ref(project_path))
+            .order_by(ProjectReleaseOrderBy::ReleasedAt)
+            .sort(SortOrder::Descending)
+            .build()?;
+
+        let releases: Vec<GitLabRelease> =
+            self.query(&api::paged(endpoint, Pagination::Limit(RELEASES_PAGE_SIZE))).await?;
+
+        if let Some(release) = select_latest_release(&releases, self.prereleases_filter.as_ref()) {
+            let ts = release.ts();
+            let url = release
+                .links
+                .self_link
+                .clone()
+                .unwrap_or_else(|| format!("{}/{project_path}/-/releases", self.base_url));
+
+            Ok(Some(landscape2_core::data::Release {
+                name: release.name.clone(),
+                tag_name: release.tag_name.clone(),
+                ts,
+                url,
+                assets_count: release.assets.as_ref().map(GitLabReleaseAssets::count),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// [GL::get_open_issues_count]
+    #[instrument(skip(self), err)]
+    pub async fn get_open_issues_count(&self, project_path: &str) -> Result<Option<usize>> {
+        self.get_opened_issues_count(project_path, "").await
+    }
+
+    /// [GL::get_open_mrs_count]
+    #[instrument(skip(self), err)]
+    pub async fn get_open_mrs_count(&self, project_path: &str) -> Result<Option<usize>> {
+        let encoded_path = encode_project_path(project_path);
+        let url = self.api_url(&format!(
+            "projects/{encoded_path}/merge_requests?state=opened&per_page=1"
+        ));
+
+        let response = self.get(&url).await?;
+        if !response.status().is_success() {
+            debug!(
+                "failed to get open merge requests count for {}: status {}",
+                project_path,
+                response.status()
+            );
+            return Ok(None);
+        }
+
+        Ok(get_total_count(response.headers()))
+    }
+
+    /// [GL::get_project]
+    #[instrument(skip(self), err)]
+    pub async fn get_project(&self, project_path: &str) -> Result<GitLabProject> {
+        let endpoint = Project::builder()
+            .project(project_ref(project_path))
+            .license(self.project_license)
+            .statistics(self.project_statistics)
+            .build()?;
+
+        let project: GitLabProject = self.query(&endpoint).await?;
+        
+        debug!("Project response for {}: description={:?}, license={:?}, topics={:?}", 
+               project_path, 
+               project.description.as_ref().map(|s| &s[..s.len().min(50)]),
+               project.license,
+               project.topics);
+        
+        Ok(project)
+    }
+
+    /// [GL::get_recent_commit_count]
+    #[instrument(skip(self), err)]
+    pub async fn get_recent_commit_count(
+        &self,
+        project_path: &str,
+        ref_: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<usize>> {
+        let url = self.api_url(&format!(
+            "projects/{}/repository/commits?ref_name={}&since={}&per_page=1",
+            encode_project_path(project_path),
+            urlencoding::encode(ref_),
+            urlencoding::encode(&since.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        ));
+        let response = self.get(&url).await?.error_for_status()?;
+        if let Some(total) = get_total_count(response.headers()) {
+            return Ok(Some(total));
+        }
+
+        // The number of commits is not available, so we count them paging
+        // through them (up to a limit)
+        let endpoint = Commits::builder()
+            .project(project_ref(project_path))
+            .ref_name(ref_)
+            .since(since)
+            .build()?;
+        let commits: Vec<GitLabCommit> =
+            self.query(&api::paged(endpoint, Pagination::Limit(MAX_RECENT_COMMITS_COUNT))).await?;
+        Ok(Some(commits.len()))
+    }
+
+    /// [GL::get_weekly_commit_activity]
+    ///
+    /// The commits of the last weeks have to be paged through (up to a limit)
+    /// to bucket them, so this is only done when enabled in the settings.
+    #[instrument(skip(self), err)]
+    pub async fn get_weekly_commit_activity(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<Vec<u32>>> {
+        if !self.commit_activity {
+            return Ok(None);
+        }
+        let now = Utc::now();
+        let weeks = i64::try_from(COMMIT_ACTIVITY_WEEKS)?;
+        let endpoint = Commits::builder()
+            .project(project_ref(project_path))
+            .ref_name(ref_)
+            .since(now - chrono::Duration::weeks(weeks))
+            .build()?;
+        let commits: Vec<GitLabCommit> =
+            self.query(&api::paged(endpoint, Pagination::Limit(MAX_RECENT_COMMITS_COUNT))).await?;
+        Ok(Some(bucket_weekly_commit_activity(
+            commits.iter().map(|commit| commit.committed_date),
+            now,
+        )))
+    }
+}
+
+/// The GL operations are implemented by the GLApi methods of the same name,
+/// whose errors are classified into GitLab errors.
+#[async_trait]
+impl GL for GLApi {
+    /// [GL::get_contributors]
+    async fn get_contributors(&self, project_path: &str) -> Result<GitLabContributors, GitlabError> {
+        Ok(GLApi::get_contributors(self, project_path).await?)
+    }
+
+    /// [GL::get_first_commit]
+    async fn get_first_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError> {
+        Ok(GLApi::get_first_commit(self, project_path, ref_).await?)
+    }
+
+    /// [GL::get_good_first_issues_count]
+    async fn get_good_first_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        Ok(GLApi::get_good_first_issues_count(self, project_path).await?)
+    }
+
+    /// [GL::get_group_projects]
+    async fn get_group_projects(&self, group_path: &str) -> Result<Vec<String>, GitlabError> {
+        Ok(GLApi::get_group_projects(self, group_path).await?)
+    }
+
+    /// [GL::get_help_wanted_count]
+    async fn get_help_wanted_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        Ok(GLApi::get_help_wanted_count(self, project_path).await?)
+    }
+
+    /// [GL::get_languages]
+    async fn get_languages(&self, project_path: &str) -> Result<Option<BTreeMap<String, f64>>, GitlabError> {
+        Ok(GLApi::get_languages(self, project_path).await?)
+    }
+
+    /// [GL::get_latest_commit]
+    async fn get_latest_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError> {
+        Ok(GLApi::get_latest_commit(self, project_path, ref_).await?)
+    }
+
+    /// [GL::get_latest_pipeline_status]
+    async fn get_latest_pipeline_status(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<String>, GitlabError> {
+        Ok(GLApi::get_latest_pipeline_status(self, project_path, ref_).await?)
+    }
+
+    /// [GL::get_latest_release]
+    async fn get_latest_release(
+        &self,
+        project_path: &str,
+    ) -> Result<Option<landscape2_core::data::Release>, GitlabError> {
+        Ok(GLApi::get_latest_release(self, project_path).await?)
+    }
+
+    /// [GL::get_open_issues_count]
+    async fn get_open_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        Ok(GLApi::get_open_issues_count(self, project_path).await?)
+    }
+
+    /// [GL::get_open_mrs_count]
+    async fn get_open_mrs_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        Ok(GLApi::get_open_mrs_count(self, project_path).await?)
+    }
+
+    /// [GL::get_project]
+    async fn get_project(&self, project_path: &str) -> Result<GitLabProject, GitlabError> {
+        Ok(GLApi::get_project(self, project_path).await?)
+    }
+
+    /// [GL::get_recent_commit_count]
+    async fn get_recent_commit_count(
+        &self,
+        project_path: &str,
+        ref_: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<usize>, GitlabError> {
+        Ok(GLApi::get_recent_commit_count(self, project_path, ref_, since).await?)
+    }
+
+    /// [GL::get_weekly_commit_activity]
+    async fn get_weekly_commit_activity(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<Vec<u32>>, GitlabError> {
+        Ok(GLApi::get_weekly_commit_activity(self, project_path, ref_).await?)
+    }
+}
+
+/// Client used to run the gitlab crate queries, which uses one request from the
+/// request budget for each request sent using the underlying client.
+pub(super) struct BudgetedClient<'a> {
+    client: &'a AsyncGitlab,
+    request_budget: &'a RequestBudget,
+}
+
+/// Error returned by the budgeted client.
+#[derive(Debug, thiserror::Error)]
+pub(super) enum BudgetedClientError {
+    #[error(transparent)]
+    Rest(#[from] RestError),
+    #[error(transparent)]
+    BudgetExhausted(#[from] RequestBudgetExhausted),
+}
+
+impl RestClient for BudgetedClient<'_> {
+    type Error = BudgetedClientError;
+
+    fn rest_endpoint(&self, endpoint: &str) -> Result<Url, ApiError<Self::Error>> {
+        self.client
+            .rest_endpoint(endpoint)
+            .map_err(|err| err.map_client(BudgetedClientError::Rest))
+    }
+
+    fn instance_endpoint(&self, endpoint: &str) -> Result<Url, ApiError<Self::Error>> {
+        self.client
+            .instance_endpoint(endpoint)
+            .map_err(|err| err.map_client(BudgetedClientError::Rest))
+    }
+}
+
+#[async_trait]
+impl AsyncClient for BudgetedClient<'_> {
+    async fn rest_async(
+        &self,
+        request: http::request::Builder,
+        body: Vec<u8>,
+    ) -> Result<http::Response<Bytes>, ApiError<Self::Error>> {
+        if !self.request_budget.try_use() {
+            return Err(ApiError::client(RequestBudgetExhausted.into()));
+        }
+        self.client
+            .rest_async(request, body)
+            .await
+            .map_err(|err| err.map_client(BudgetedClientError::Rest))
+    }
+}
+
+/// Error returned by the GL operations, classifying the failure so that it can
+/// be handled accordingly. The original error is kept in all cases.
+#[derive(Debug, thiserror::Error)]
+pub(super) enum GitlabError {
+    /// The token is missing, invalid or lacks the permissions required.
+    #[error("gitlab authentication error: {0:#}")]
+    Auth(anyhow::Error),
+    /// The rate limit has been exceeded.
+    #[error("gitlab rate limit exceeded: {0:#}")]
+    RateLimited(anyhow::Error),
+    /// The project (or the resource requested) does not exist.
+    #[error("gitlab resource not found: {0:#}")]
+    NotFound(anyhow::Error),
+    /// The instance could not be reached or did not reply in time.
+    #[error("gitlab network error: {0:#}")]
+    Network(anyhow::Error),
+    /// The response could not be parsed.
+    #[error("error parsing gitlab response: {0:#}")]
+    Parse(anyhow::Error),
+    /// Any other error.
+    #[error("{0:#}")]
+    Other(anyhow::Error),
+}
+
+impl GitlabError {
+    /// Return the original error.
+    pub fn inner(&self) -> &anyhow::Error {
+        match self {
+            Self::Auth(err)
+            | Self::RateLimited(err)
+            | Self::NotFound(err)
+            | Self::Network(err)
+            | Self::Parse(err)
+            | Self::Other(err) => err,
+        }
+    }
+}
+
+impl From<anyhow::Error> for GitlabError {
+    fn from(err: anyhow::Error) -> Self {
+        // The error may be already a GitLab error (i.e. returned by another
+        // GL implementation)
+        let err = match err.downcast::<GitlabError>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+
+        if retry_after(&err).is_some() {
+            return Self::RateLimited(err);
+        }
+        match error_status(&err) {
+            Some(401 | 403) => return Self::Auth(err),
+            Some(404 | 410) => return Self::NotFound(err),
+            Some(429) => return Self::RateLimited(err),
+            _ => {}
+        }
+        if let Some(api_err) = err.downcast_ref::<ApiError<RestError>>() {
+            match api_err {
+                ApiError::Auth { .. } => return Self::Auth(err),
+                ApiError::Client { .. } => return Self::Network(err),
+                ApiError::Json { .. } | ApiError::DataType { .. } => return Self::Parse(err),
+                _ => {}
+            }
+        }
+        if let Some(reqwest_err) = err.downcast_ref::<reqwest::Error>() {
+            if reqwest_err.is_timeout() || reqwest_err.is_connect() || reqwest_err.is_request() {
+                return Self::Network(err);
+            }
+            if reqwest_err.is_decode() {
+                return Self::Parse(err);
+            }
+        }
+        if err.is::<serde_json::Error>() {
+            return Self::Parse(err);
+        }
+        Self::Other(err)
+    }
+}
+
+/// Return the original error of the one provided when it's a GitLab error.
+pub(super) fn underlying_error(err: &anyhow::Error) -> &anyhow::Error {
+    err.downcast_ref::<GitlabError>().map_or(err, GitlabError::inner)
+}
+
+/// Check if the error provided is a transient one (rate limited, server error
+/// or network issue), so the operation that produced it can be retried.
+pub(super) fn is_retryable(err: &anyhow::Error) -> bool {
+    let err = underlying_error(err);
+    let is_retryable_status = |status: u16| status == 429 || (500..600).contains(&status);
+
+    if let Some(err) = err.downcast_ref::<ApiError<RestError>>() {
+        return match err {
+            ApiError::GitlabRateLimited { .. } | ApiError::Client { .. } => true,
+            ApiError::GitlabService { status, .. }
+            | ApiError::GitlabWithStatus { status, .. }
+            | ApiError::GitlabObjectWithStatus { status, .. }
+            | ApiError::GitlabUnrecognizedWithStatus { status, .. } => is_retryable_status(status.as_u16()),
+            _ => false,
+        };
+    }
+    if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        return err.is_timeout()
+            || err.is_connect()
+            || err.status().is_some_and(|status| is_retryable_status(status.as_u16()));
+    }
+    false
+}
+
+/// Return the status code of the error provided when it is a permanent one
+/// (the project does not exist or has been deleted), so that it's not
+/// fetched again on every build.
+pub(super) fn permanent_error_status(err: &anyhow::Error) -> Option<u16> {
+    match err.downcast_ref::<GitlabError>() {
+        Some(GitlabError::NotFound(err)) => error_status(err),
+        _ => None,
+    }
+}
+
+/// Return the http status code of the error provided, if any.
+pub(super) fn error_status(err: &anyhow::Error) -> Option<u16> {
+    let err = underlying_error(err);
+    if let Some(err) = err.downcast_ref::<ApiError<RestError>>() {
+        match err {
+            ApiError::GitlabService { status, .. }
+            | ApiError::GitlabWithStatus { status, .. }
+            | ApiError::GitlabObjectWithStatus { status, .. }
+            | ApiError::GitlabUnrecognizedWithStatus { status, .. } => Some(status.as_u16()),
+            _ => None,
+        }
+    } else {
+        Some(err.downcast_ref::<reqwest::Error>()?.status()?.as_u16())
+    }
+}
+
+/// Check if the error provided means that the token used is no longer usable
+/// (i.e. it has been revoked). Forbidden errors are not considered, as they may
+/// be specific to the project, and neither are rate limited requests, as the
+/// token will be usable again once the rate limit is reset.
+pub(super) fn is_token_error(err: &anyhow::Error) -> bool {
+    error_status(err) == Some(401)
+}
+
+/// Check if the error provided was produced by a request that was rate
+/// limited, so the token used should not be used for a while.
+pub(super) fn is_rate_limit_error(err: &anyhow::Error) -> bool {
+    retry_after(err).is_some() || error_status(err) == Some(429)
+}
+
+/// Return the kind of the error provided, used to group the failures in the
+/// collection stats.
+pub(super) fn error_kind(err: &anyhow::Error) -> String {
+    let err = underlying_error(err);
+    if retry_after(err).is_some() {
+        return "rate_limited".to_string();
+    }
+    if let Some(status) = error_status(err) {
+        return format!("status_{status}");
+    }
+    if let Some(ApiError::Client { .. }) = err.downcast_ref::<ApiError<RestError>>() {
+        return "network".to_string();
+    }
+    if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        if err.is_timeout() {
+            return "timeout".to_string();
+        }
+        if err.is_connect() {
+            return "network".to_string();
+        }
+    }
+    "other".to_string()
+}
+
+/// Return how long GitLab asked us to wait before retrying, if the error
+/// provided was produced by a rate limited request.
+pub(super) fn retry_after(err: &anyhow::Error) -> Option<Duration> {
+    let err = underlying_error(err);
+    if let Some(ApiError::GitlabRateLimited { retry_after, .. }) = err.downcast_ref::<ApiError<RestError>>() {
+        return Some(*retry_after);
+    }
+    None
+}
+
+/// Return how long to wait before retrying the request that produced the
+/// response provided, when it was rate limited and included a `Retry-After`
+/// header (in seconds or as an HTTP date).
+fn respect_retry_after(response: &reqwest::Response) -> Option<Duration> {
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let retry_after = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let delay = if let Ok(seconds) = retry_after.parse::<u64>() {
+        Duration::from_secs(seconds)
+    } else {
+        let date = DateTime::parse_from_rfc2822(retry_after).ok()?;
+        (date.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default()
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+/// Rate limit information reported by GitLab in the response headers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RateLimit {
+    limit: Option<u64>,
+    remaining: u64,
+    reset: Option<u64>,
+}
+
+impl RateLimit {
+    /// Get the rate limit information from the headers provided, if available.
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let get = |name: &str| headers.get(name)?.to_str().ok()?.parse().ok();
+        Some(Self {
+            limit: get("ratelimit-limit"),
+            remaining: get("ratelimit-remaining")?,
+            reset: get("ratelimit-reset"),
+        })
+    }
+}
+
+/// Return the total number of items available reported in the `X-Total`
+/// header of a paginated response (GitLab omits it for very large results).
+fn get_total_count(headers: &HeaderMap) -> Option<usize> {
+    headers.get("x-total")?.to_str().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use landscape2_core::settings::{Collection, GitLabCollection};
+    use reqwest::StatusCode;
+
+    use std::path::PathBuf;
+
+    use landscape2_core::data::parse_gitlab_url;
+    use landscape2_core::settings::LandscapeSettings;
+
+    use super::*;
+    use crate::build::gitlab::{
+        DEFAULT_HTTP_TIMEOUT, DEFAULT_USER_AGENT, GitlabCollectionOptions, get_prereleases_filter,
+        tests::{api_error, capture_warnings},
+        tokens::GitlabInstanceConfig,
+    };
+
+    #[tokio::test]
+    async fn create_gitlab_pool_http_instance() {
+        let mut server = mockito::Server::new_async().await;
+        let config = GitlabInstanceConfig {
+            base_url: server.url(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        };
+        assert!(config.base_url.starts_with("http://"));
+
+        let user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let languages_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/languages")
+            .with_body(r#"{"Rust": 100.0}"#)
+            .create_async()
+            .await;
+
+        let gl_pool = create_gitlab_pool(&config.base_url, &config.tokens, &ClientOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(gl_pool.status().size, 1);
+        let gl = gl_pool.get().await.unwrap();
+        let languages = gl.get_languages("group/project").await.unwrap();
+
+        user_mock.assert_async().await;
+        languages_mock.assert_async().await;
+        assert_eq!(languages, Some(BTreeMap::from([("Rust".to_string(), 100.0)])));
+    }
+
+    #[tokio::test]
+    async fn create_gitlab_pool_discards_invalid_tokens() {
+        let mut server = mockito::Server::new_async().await;
+        let valid_token_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .match_header("PRIVATE-TOKEN", "valid-token")
+            .with_body("{}")
+            .create_async()
+            .await;
+        let invalid_token_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .match_header("PRIVATE-TOKEN", "invalid-token")
+            .with_status(401)
+            .with_body(r#"{"message": "401 Unauthorized"}"#)
+            .create_async()
+            .await;
+
+        let tokens = vec!["invalid-token".to_string(), "valid-token".to_string()];
+        let gl_pool = create_gitlab_pool(&server.url(), &tokens, &ClientOptions::default()).await.unwrap();
+
+        valid_token_mock.assert_async().await;
+        invalid_token_mock.assert_async().await;
+        assert_eq!(gl_pool.status().size, 1);
+    }
+
+    #[tokio::test]
+    async fn create_gitlab_pool_all_tokens_invalid() {
+        let mut server = mockito::Server::new_async().await;
+        let invalid_token_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_status(401)
+            .with_body(r#"{"message": "401 Unauthorized"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let tokens = vec!["token1".to_string(), "token2".to_string()];
+        let gl_pool = create_gitlab_pool(&server.url(), &tokens, &ClientOptions::default()).await;
+
+        invalid_token_mock.assert_async().await;
+        assert!(gl_pool.is_none());
+    }
+
+    #[tokio::test]
+    async fn new_http_client_uses_timeout() {
+        // The listener accepts connections but never replies to the requests
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let http_client = new_http_client(
+            Some("token"),
+            Duration::from_millis(100),
+            &[],
+            DEFAULT_USER_AGENT,
+            None,
+        )
+        .unwrap();
+        let err = http_client.get(&url).send().await.unwrap_err();
+        assert!(err.is_timeout());
+    }
+
+    #[tokio::test]
+    async fn new_http_client_uses_proxy() {
+        let mut proxy = mockito::Server::new_async().await;
+        let proxy_mock = proxy
+            .mock("GET", "/api/v4/version")
+            .match_header("host", "gitlab.example.com")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let http_client = new_http_client(
+            None,
+            DEFAULT_HTTP_TIMEOUT,
+            &[],
+            DEFAULT_USER_AGENT,
+            Some(&proxy.url()),
+        )
+        .unwrap();
+        let response = http_client.get("http://gitlab.example.com/api/v4/version").send().await.unwrap();
+        assert!(response.status().is_success());
+        proxy_mock.assert_async().await;
+
+        let err = new_http_client(
+            None,
+            DEFAULT_HTTP_TIMEOUT,
+            &[],
+            DEFAULT_USER_AGENT,
+            Some("http://[invalid"),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "invalid GITLAB_PROXY value");
+    }
+
+    #[tokio::test]
+    async fn glapi_sends_user_agent() {
+        let mut server = mockito::Server::new_async().await;
+        let user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .match_header("user-agent", "custom-agent/1.0")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let languages_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/languages")
+            .match_header("user-agent", "custom-agent/1.0")
+            .with_body(r#"{"Rust": 100.0}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let options = ClientOptions {
+            user_agent: "custom-agent/1.0".to_string(),
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        assert!(gl.get_languages("group/project").await.unwrap().is_some());
+
+        user_mock.assert_async().await;
+        languages_mock.assert_async().await;
+        assert!(DEFAULT_USER_AGENT.starts_with("landscape2/"));
+    }
+
+    #[tokio::test]
+    async fn glapi_new_invalid_ca_bundle_path() {
+        let options = ClientOptions {
+            ca_bundle: Some(PathBuf::from("/nonexistent/ca-bundle.pem")),
+            ..Default::default()
+        };
+
+        let Err(err) = GLApi::new("https://gitlab.example.com", "token", &options).await else {
+            panic!("expected error loading CA bundle");
+        };
+        assert_eq!(
+            err.to_string(),
+            "error reading gitlab CA bundle /nonexistent/ca-bundle.pem"
+        );
+    }
+
+    #[tokio::test]
+    async fn glapi_new_ca_bundle_without_certificates() {
+        let ca_bundle = tempfile::NamedTempFile::new().unwrap();
+        fs::write(ca_bundle.path(), "not a certificate").unwrap();
+        let options = ClientOptions {
+            ca_bundle: Some(ca_bundle.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let Err(err) = GLApi::new("https://gitlab.example.com", "token", &options).await else {
+            panic!("expected error loading CA bundle");
+        };
+        assert!(err.to_string().starts_with("no certificates found in gitlab CA bundle"));
+    }
+
+    #[tokio::test]
+    async fn glapi_oauth2_token_sent_as_bearer_token() {
+        let mut server = mockito::Server::new_async().await;
+        let user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .match_header("authorization", "Bearer token")
+            .match_header("private-token", mockito::Matcher::Missing)
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let token_mock = server
+            .mock("GET", "/api/v4/personal_access_tokens/self")
+            .match_header("authorization", "Bearer token")
+            .match_header("private-token", mockito::Matcher::Missing)
+            .with_body(r#"{"scopes": ["read_api"]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "oauth2:token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(
+            gl.get_token_scopes().await.unwrap(),
+            Some(vec!["read_api".to_string()])
+        );
+
+        user_mock.assert_async().await;
+        token_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_private_token_sent_in_private_token_header() {
+        let mut server = mockito::Server::new_async().await;
+        let user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .match_header("private-token", "token")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let token_mock = server
+            .mock("GET", "/api/v4/personal_access_tokens/self")
+            .match_header("private-token", "token")
+            .match_header("authorization", mockito::Matcher::Missing)
+            .with_body(r#"{"scopes": ["read_api"]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        gl.get_token_scopes().await.unwrap();
+
+        user_mock.assert_async().await;
+        token_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_token_scopes() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let token_mock = server
+            .mock("GET", "/api/v4/personal_access_tokens/self")
+            .with_body(r#"{"id": 1, "name": "token", "scopes": ["read_user", "read_api"]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let not_found_mock = server
+            .mock("GET", "/api/v4/personal_access_tokens/self")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(
+            gl.get_token_scopes().await.unwrap(),
+            Some(vec!["read_user".to_string(), "read_api".to_string()])
+        );
+        assert_eq!(gl.get_token_scopes().await.unwrap(), None);
+
+        token_mock.assert_async().await;
+        not_found_mock.assert_async().await;
+    }
+
+    #[test]
+    fn rate_limit_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(RateLimit::from_headers(&headers), None);
+
+        headers.insert("RateLimit-Limit", HeaderValue::from_static("2000"));
+        headers.insert("RateLimit-Remaining", HeaderValue::from_static("5"));
+        headers.insert("RateLimit-Reset", HeaderValue::from_static("1700000000"));
+        assert_eq!(
+            RateLimit::from_headers(&headers),
+            Some(RateLimit {
+                limit: Some(2000),
+                remaining: 5,
+                reset: Some(1_700_000_000),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn glapi_get_languages_respects_retry_after() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let rate_limited_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/languages")
+            .with_status(429)
+            .with_header("Retry-After", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let languages_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/languages")
+            .with_body(r#"{"Rust": 100.0}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        let languages = gl.get_languages("group/project").await.unwrap();
+
+        rate_limited_mock.assert_async().await;
+        languages_mock.assert_async().await;
+        assert_eq!(languages, Some(BTreeMap::from([("Rust".to_string(), 100.0)])));
+    }
+
+    #[tokio::test]
+    async fn respect_retry_after_parses_header() {
+        let mut server = mockito::Server::new_async().await;
+        let _seconds_mock = server
+            .mock("GET", "/seconds")
+            .with_status(429)
+            .with_header("Retry-After", "7")
+            .create_async()
+            .await;
+        let _no_header_mock = server.mock("GET", "/no-header").with_status(429).create_async().await;
+        let _not_limited_mock =
+            server.mock("GET", "/ok").with_header("Retry-After", "7").create_async().await;
+
+        let get = |path: &str| reqwest::get(format!("{}{path}", server.url()));
+        assert_eq!(
+            respect_retry_after(&get("/seconds").await.unwrap()),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(respect_retry_after(&get("/no-header").await.unwrap()), None);
+        assert_eq!(respect_retry_after(&get("/ok").await.unwrap()), None);
+    }
+
+    #[tokio::test]
+    async fn glapi_get_languages_forbidden_warns_once_per_instance() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let languages_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/api/v4/projects/group%2Fproject\d/languages$".to_string()),
+            )
+            .with_status(403)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let (logs, _guard) = capture_warnings();
+
+        // Clients of the same instance share the instances already reported
+        let options = ClientOptions::default();
+        let gl1 = GLApi::new(&server.url(), "token1", &options).await.unwrap();
+        let gl2 = GLApi::new(&server.url(), "token2", &options).await.unwrap();
+        for (gl, project_path) in [
+            (&gl1, "group/project1"),
+            (&gl2, "group/project2"),
+            (&gl1, "group/project3"),
+        ] {
+            assert_eq!(gl.get_languages(project_path).await.unwrap(), None);
+        }
+
+        assert_eq!(logs.logs().matches("languages endpoint not available").count(), 1);
+        languages_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_rejects_oversized_responses() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let large_body = format!(r#"{{"Rust": 100.0, "Padding": "{}"}}"#, "x".repeat(1024));
+        let _languages_mock = server
+            .mock("GET", "/api/v4/projects/group%2Flarge/languages")
+            .with_body(&large_body)
+            .create_async()
+            .await;
+        let _chunked_languages_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fchunked/languages")
+            .with_chunked_body(move |w| w.write_all(large_body.as_bytes()))
+            .create_async()
+            .await;
+        let _small_languages_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fsmall/languages")
+            .with_body(r#"{"Rust": 100.0}"#)
+            .create_async()
+            .await;
+        let _issues_mock = server
+            .mock("GET", "/api/v4/projects/group%2Flarge/issues_statistics")
+            .match_query(mockito::Matcher::Any)
+            .with_body(format!(
+                r#"{{"statistics": {{"counts": {{"opened": 3}}}}, "padding": "{}"}}"#,
+                "x".repeat(1024)
+            ))
+            .create_async()
+            .await;
+
+        let (logs, _guard) = capture_warnings();
+        let options = ClientOptions {
+            max_response_size: 512,
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+
+        assert_eq!(gl.get_languages("group/large").await.unwrap(), None);
+        assert_eq!(gl.get_languages("group/chunked").await.unwrap(), None);
+        assert_eq!(gl.get_good_first_issues_count("group/large").await.unwrap(), None);
+        assert_eq!(
+            gl.get_languages("group/small").await.unwrap(),
+            Some(BTreeMap::from([("Rust".to_string(), 100.0)]))
+        );
+        let logs = logs.logs();
+        assert!(logs.contains("languages response for group/large exceeds the maximum size"));
+        assert!(logs.contains("languages response for group/chunked exceeds the maximum size"));
+        assert!(logs.contains("issues statistics response for group/large exceeds the maximum size"));
+    }
+
+    #[tokio::test]
+    async fn glapi_get_open_issues_and_mrs_count() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let issues_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/issues_statistics")
+            .match_query(mockito::Matcher::UrlEncoded("state".into(), "opened".into()))
+            .with_body(r#"{"statistics": {"counts": {"all": 10, "closed": 7, "opened": 3}}}"#)
+            .create_async()
+            .await;
+        let mrs_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/merge_requests")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("state".into(), "opened".into()),
+                mockito::Matcher::UrlEncoded("per_page".into(), "1".into()),
+            ]))
+            .with_header("X-Total", "5")
+            .with_body("[{}]")
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(gl.get_open_issues_count("group/project").await.unwrap(), Some(3));
+        assert_eq!(gl.get_open_mrs_count("group/project").await.unwrap(), Some(5));
+
+        issues_mock.assert_async().await;
+        mrs_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_relative_url_root() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/gitlab/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let project_mock = server
+            .mock("GET", "/gitlab/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{
+                    "default_branch": "main",
+                    "path_with_namespace": "group/project",
+                    "star_count": 10,
+                    "web_url": "https://example.com/gitlab/group/project"
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let languages_mock = server
+            .mock("GET", "/gitlab/api/v4/projects/group%2Fproject/languages")
+            .with_body(r#"{"Rust": 100.0}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let issues_statistics_mock = server
+            .mock("GET", "/gitlab/api/v4/projects/group%2Fproject/issues_statistics")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"statistics": {"counts": {"all": 2, "closed": 0, "opened": 2}}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        // Both the requests made by the gitlab crate client and the ones made
+        // directly target the api under the relative url root
+        let base_url = format!("{}/gitlab", server.url());
+        let gl = GLApi::new(&base_url, "token", &ClientOptions::default()).await.unwrap();
+        let project = GLApi::get_project(&gl, "group/project").await.unwrap();
+        assert_eq!(project.path_with_namespace, "group/project");
+        assert_eq!(
+            gl.get_languages("group/project").await.unwrap(),
+            Some(BTreeMap::from([("Rust".to_string(), 100.0)]))
+        );
+        assert_eq!(
+            gl.get_good_first_issues_count("group/project").await.unwrap(),
+            Some(2)
+        );
+
+        project_mock.assert_async().await;
+        languages_mock.assert_async().await;
+        issues_statistics_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_good_first_issues_count_custom_labels() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let mut labels_mocks = vec![];
+        for (label, count) in [("help wanted", 2), ("Good First Issue", 3)] {
+            let mock = server
+                .mock("GET", "/api/v4/projects/group%2Fproject/issues_statistics")
+                .match_query(mockito::Matcher::AllOf(vec![
+                    mockito::Matcher::UrlEncoded("labels".into(), label.into()),
+                    mockito::Matcher::UrlEncoded("state".into(), "opened".into()),
+                ]))
+                .with_body(format!(
+                    r#"{{"statistics": {{"counts": {{"all": {count}, "closed": 0, "opened": {count}}}}}}}"#
+                ))
+                .expect(1)
+                .create_async()
+                .await;
+            labels_mocks.push(mock);
+        }
+
+        let options = ClientOptions {
+            good_first_issues_labels: vec!["help wanted".to_string(), "Good First Issue".to_string()],
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        assert_eq!(
+            gl.get_good_first_issues_count("group/project").await.unwrap(),
+            Some(5)
+        );
+
+        for mock in labels_mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn glapi_get_help_wanted_count() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let help_wanted_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/issues_statistics")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("labels".into(), "help wanted".into()),
+                mockito::Matcher::UrlEncoded("state".into(), "opened".into()),
+            ]))
+            .with_body(r#"{"statistics": {"counts": {"all": 7, "closed": 3, "opened": 4}}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let unavailable_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/issues_statistics")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "labels".into(),
+                "contributions welcome".into(),
+            ))
+            .with_status(404)
+            .expect(2)
+            .create_async()
+            .await;
+
+        // Labels whose statistics are not available are not counted
+        let options = ClientOptions {
+            help_wanted_labels: vec!["help wanted".to_string(), "contributions welcome".to_string()],
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        assert_eq!(gl.get_help_wanted_count("group/project").await.unwrap(), Some(4));
+
+        // No count is returned when none of the statistics are available
+        let options = ClientOptions {
+            help_wanted_labels: vec!["contributions welcome".to_string()],
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        assert_eq!(gl.get_help_wanted_count("group/project").await.unwrap(), None);
+
+        help_wanted_mock.assert_async().await;
+        unavailable_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_open_issues_and_mrs_count_unavailable() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let _not_found_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/api/v4/projects/".into()))
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(gl.get_open_issues_count("group/project").await.unwrap(), None);
+        assert_eq!(gl.get_open_mrs_count("group/project").await.unwrap(), None);
+    }
+
+    #[test]
+    fn gitlab_error_from_api_errors() {
+        assert!(matches!(
+            api_error(StatusCode::UNAUTHORIZED),
+            GitlabError::Auth(_)
+        ));
+        assert!(matches!(api_error(StatusCode::FORBIDDEN), GitlabError::Auth(_)));
+        assert!(matches!(
+            api_error(StatusCode::NOT_FOUND),
+            GitlabError::NotFound(_)
+        ));
+        assert!(matches!(api_error(StatusCode::GONE), GitlabError::NotFound(_)));
+        assert!(matches!(
+            api_error(StatusCode::TOO_MANY_REQUESTS),
+            GitlabError::RateLimited(_)
+        ));
+        assert!(matches!(
+            api_error(StatusCode::SERVICE_UNAVAILABLE),
+            GitlabError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn gitlab_error_from_parse_and_other_errors() {
+        let err = serde_json::from_str::<GitLabProject>("{").unwrap_err();
+        assert!(matches!(
+            GitlabError::from(anyhow::Error::from(err)),
+            GitlabError::Parse(_)
+        ));
+        assert!(matches!(
+            GitlabError::from(format_err!("error")),
+            GitlabError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn gitlab_error_from_gitlab_error_is_preserved() {
+        let err = anyhow::Error::from(api_error(StatusCode::NOT_FOUND));
+        assert_eq!(permanent_error_status(&err), Some(404));
+        assert!(matches!(GitlabError::from(err), GitlabError::NotFound(_)));
+
+        let err = anyhow::Error::from(api_error(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(permanent_error_status(&err), None);
+        assert_eq!(error_kind(&err), "status_503");
+    }
+
+    #[tokio::test]
+    async fn gitlab_error_from_network_error() {
+        // Nothing is listening on the address once the listener is dropped
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let err = reqwest::get(&url).await.unwrap_err();
+        assert!(matches!(
+            GitlabError::from(anyhow::Error::from(err)),
+            GitlabError::Network(_)
+        ));
+    }
+
+    /// Return a rate limited error that asks to retry after the delay given.
+    fn rate_limited_error(retry_after: Duration) -> ApiError<RestError> {
+        ApiError::GitlabRateLimited {
+            rl_limit: 10,
+            rl_name: "throttle_authenticated_api".to_string(),
+            rl_observed: 11,
+            rl_remaining: 0,
+            rl_reset: Utc::now(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn is_token_error_only_for_revoked_tokens() {
+        let err = |status| anyhow::Error::from(api_error(status));
+        assert!(is_token_error(&err(StatusCode::UNAUTHORIZED)));
+        assert!(!is_token_error(&err(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(!is_token_error(&err(StatusCode::FORBIDDEN)));
+        assert!(!is_token_error(&err(StatusCode::NOT_FOUND)));
+
+        let rate_limited = anyhow::Error::from(rate_limited_error(Duration::from_secs(60)));
+        assert!(!is_token_error(&rate_limited));
+    }
+
+    #[test]
+    fn is_rate_limit_error_for_rate_limited_requests() {
+        let err = |status| anyhow::Error::from(api_error(status));
+        assert!(is_rate_limit_error(&err(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(!is_rate_limit_error(&err(StatusCode::UNAUTHORIZED)));
+
+        let rate_limited = anyhow::Error::from(rate_limited_error(Duration::from_secs(60)));
+        assert!(is_rate_limit_error(&rate_limited));
+    }
+
+    #[tokio::test]
+    async fn glapi_get_group_projects_includes_subgroups() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let projects_mock = server
+            .mock("GET", "/api/v4/groups/group%2Fsubgroup/projects")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "include_subgroups".to_string(),
+                "true".to_string(),
+            ))
+            .with_body(
+                r#"[
+                    {"id": 1, "path_with_namespace": "group/subgroup/project1"},
+                    {"id": 2, "path_with_namespace": "group/subgroup/nested/project2"}
+                ]"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(
+            gl.get_group_projects("group/subgroup").await.unwrap(),
+            vec![
+                "group/subgroup/project1".to_string(),
+                "group/subgroup/nested/project2".to_string()
+            ]
+        );
+
+        projects_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_latest_release_selects_most_recent_release() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let releases_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/releases")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("order_by".to_string(), "released_at".to_string()),
+                mockito::Matcher::UrlEncoded("sort".to_string(), "desc".to_string()),
+            ]))
+            .with_body(
+                r#"[
+                    {
+                        "tag_name": "v1.1.0",
+                        "created_at": "2024-05-01T10:00:00.000Z",
+                        "released_at": "2024-05-01T10:00:00.000Z",
+                        "_links": {}
+                    },
+                    {
+                        "tag_name": "v1.0.0",
+                        "created_at": "2024-03-01T10:00:00.000Z",
+                        "released_at": "2024-03-01T10:00:00.000Z",
+                        "_links": {}
+                    },
+                    {
+                        "tag_name": "v1.2.0",
+                        "created_at": "2024-06-01T10:00:00.000Z",
+                        "released_at": null,
+                        "_links": {
+                            "self": "https://gitlab.com/group/project/-/releases/v1.2.0"
+                        }
+                    },
+                    {
+                        "tag_name": "v0.9.0",
+                        "created_at": "2024-01-01T10:00:00.000Z",
+                        "_links": {}
+                    }
+                ]"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        let release = GLApi::get_latest_release(&gl, "group/project").await.unwrap().unwrap();
+        assert_eq!(release.tag_name, Some("v1.2.0".to_string()));
+        assert_eq!(release.ts, Some("2024-06-01T10:00:00Z".parse().unwrap()));
+        assert_eq!(release.url, "https://gitlab.com/group/project/-/releases/v1.2.0");
+        releases_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_latest_release_skips_prereleases() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let _releases_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/releases")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"[
+                    {
+                        "tag_name": "v2.0.0",
+                        "released_at": "2024-07-01T10:00:00.000Z",
+                        "upcoming_release": true,
+                        "_links": {}
+                    },
+                    {
+                        "tag_name": "v1.1.0-rc.1",
+                        "released_at": "2024-06-01T10:00:00.000Z",
+                        "upcoming_release": false,
+                        "_links": {}
+                    },
+                    {
+                        "tag_name": "v1.0.0",
+                        "released_at": "2024-05-01T10:00:00.000Z",
+                        "upcoming_release": false,
+                        "_links": {}
+                    }
+                ]"#,
+            )
+            .create_async()
+            .await;
+
+        // Pre-releases are considered by default
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        let release = GLApi::get_latest_release(&gl, "group/project").await.unwrap().unwrap();
+        assert_eq!(release.tag_name, Some("v2.0.0".to_string()));
+
+        // Skipping them, the stable release is selected
+        let settings = LandscapeSettings {
+            collection: Some(Collection {
+                gitlab: Some(GitLabCollection {
+                    skip_prereleases: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let options = ClientOptions {
+            prereleases_filter: get_prereleases_filter(&settings).unwrap(),
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        let release = GLApi::get_latest_release(&gl, "group/project").await.unwrap().unwrap();
+        assert_eq!(release.tag_name, Some("v1.0.0".to_string()));
+    }
+
+    #[tokio::test]
+    async fn glapi_get_latest_commit_uses_ref() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let commits_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/commits")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "ref_name".to_string(),
+                "release".to_string(),
+            ))
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(
+            gl.get_latest_commit("group/project", "release").await.unwrap(),
+            None
+        );
+
+        commits_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_uses_project_ids_in_endpoints() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let commits_mock = server
+            .mock("GET", "/api/v4/projects/12345/repository/commits")
+            .match_query(mockito::Matcher::Any)
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+        let issues_statistics_mock = server
+            .mock("GET", "/api/v4/projects/12345/issues_statistics")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"statistics": {"counts": {"all": 4, "closed": 2, "opened": 2}}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let repo_url = format!("{}/-/project/12345", server.url());
+        let (_, project_path) = parse_gitlab_url(&repo_url).unwrap();
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(gl.get_latest_commit(&project_path, "main").await.unwrap(), None);
+        assert_eq!(
+            gl.get_good_first_issues_count(&project_path).await.unwrap(),
+            Some(2)
+        );
+
+        commits_mock.assert_async().await;
+        issues_statistics_mock.assert_async().await;
+    }
+
+    #[test]
+    fn project_ref_and_encoding() {
+        assert_eq!(project_ref("-/project/12345"), NameOrId::Id(12345));
+        assert_eq!(
+            project_ref("group/project"),
+            NameOrId::Name(Cow::Borrowed("group/project"))
+        );
+        assert_eq!(encode_project_path("-/project/12345"), "12345");
+        assert_eq!(encode_project_path("group/project"), "group%2Fproject");
+        assert!(!is_project_id(""));
+        assert!(!is_project_id("12345"));
+        assert!(!is_project_id("-/project/"));
+        assert!(!is_project_id("group/12345"));
+    }
+
+    #[test]
+    fn decode_project_path_valid_and_invalid() {
+        assert_eq!(
+            decode_project_path("Group/SubGroup/My-Project").unwrap(),
+            "Group/SubGroup/My-Project"
+        );
+        assert_eq!(
+            decode_project_path("Group%2FSubGroup/My%2DProject").unwrap(),
+            "Group/SubGroup/My-Project"
+        );
+        for path in [
+            "",
+            "group//project",
+            "group/my project",
+            "group/my%20project",
+            "group/%FF",
+        ] {
+            assert!(decode_project_path(path).is_err(), "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn glapi_get_project_follows_redirects() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let redirect_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fold-project")
+            .match_query(mockito::Matcher::Any)
+            .with_status(301)
+            .with_header(
+                "location",
+                &format!("{}/api/v4/projects/group%2Fnew-project", server.url()),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fnew-project")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{
+                    "default_branch": "main",
+                    "path_with_namespace": "group/new-project",
+                    "star_count": 10,
+                    "web_url": "https://gitlab.com/group/new-project"
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        let project = GLApi::get_project(&gl, "group/old-project").await.unwrap();
+        assert_eq!(project.path_with_namespace, "group/new-project");
+        assert_eq!(project.web_url, "https://gitlab.com/group/new-project");
+        redirect_mock.assert_async().await;
+        project_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_encodes_namespace_path_as_single_segment() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let project_path = "Group/SubGroup/My-Project";
+        let encoded_path = encode_project_path(project_path);
+        assert_eq!(encoded_path, "Group%2FSubGroup%2FMy-Project");
+        assert_eq!(urlencoding::decode(&encoded_path).unwrap(), project_path);
+        let languages_mock = server
+            .mock("GET", "/api/v4/projects/Group%2FSubGroup%2FMy-Project/languages")
+            .with_body(r#"{"Rust": 100.0}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let issues_statistics_mock = server
+            .mock(
+                "GET",
+                "/api/v4/projects/Group%2FSubGroup%2FMy-Project/issues_statistics",
+            )
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"statistics": {"counts": {"all": 2, "closed": 0, "opened": 2}}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(
+            gl.get_languages(project_path).await.unwrap(),
+            Some(BTreeMap::from([("Rust".to_string(), 100.0)]))
+        );
+        assert_eq!(
+            gl.get_good_first_issues_count(project_path).await.unwrap(),
+            Some(2)
+        );
+
+        languages_mock.assert_async().await;
+        issues_statistics_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_first_commit_requests_last_page_only() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let commit = |id: &str| {
+            format!(
+                r#"[{{"web_url": "https://gitlab.com/group/project/-/commit/{id}", "committed_date": "2024-01-01T00:00:00Z"}}]"#
+            )
+        };
+        let commits_path = "/api/v4/projects/group%2Fproject/repository/commits";
+        let page_query = |page: &str| {
+            mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("ref_name".to_string(), "main".to_string()),
+                mockito::Matcher::UrlEncoded("per_page".to_string(), "1".to_string()),
+                mockito::Matcher::UrlEncoded("page".to_string(), page.to_string()),
+            ])
+        };
+        let first_page_mock = server
+            .mock("GET", commits_path)
+            .match_query(page_query("1"))
+            .with_header("X-Total", "3")
+            .with_body(commit("3"))
+            .expect(1)
+            .create_async()
+            .await;
+        let last_page_mock = server
+            .mock("GET", commits_path)
+            .match_query(page_query("3"))
+            .with_header("X-Total", "3")
+            .with_body(commit("1"))
+            .expect(1)
+            .create_async()
+            .await;
+        let other_pages_mock = server
+            .mock("GET", commits_path)
+            .match_query(page_query("2"))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        let first_commit = gl.get_first_commit("group/project", "main").await.unwrap().unwrap();
+        assert_eq!(first_commit.url, "https://gitlab.com/group/project/-/commit/1");
+
+        first_page_mock.assert_async().await;
+        last_page_mock.assert_async().await;
+        other_pages_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_first_commit_probes_last_page_without_total_header() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+
+        // The repository has 5 commits, one per page (the newest first), and
+        // the X-Total header is not returned
+        let commits_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/commits")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_request(|request| {
+                let page: usize = request
+                    .path_and_query()
+                    .split(['?', '&'])
+                    .find_map(|param| param.strip_prefix("page="))
+                    .and_then(|page| page.parse().ok())
+                    .unwrap_or(1);
+                if page > 5 {
+                    return b"[]".to_vec();
+                }
+                format!(
+                    r#"[{{"web_url": "https://gitlab.com/group/project/-/commit/{}", "committed_date": "2024-01-01T00:00:00Z"}}]"#,
+                    6 - page
+                )
+                .into_bytes()
+            })
+            .expect(6)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        let first_commit = gl.get_first_commit("group/project", "main").await.unwrap().unwrap();
+        assert_eq!(first_commit.url, "https://gitlab.com/group/project/-/commit/1");
+
+        // Pages 1, 2, 4 and 8 are requested while probing, and then 6 and 5
+        commits_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_contributors_uses_total_header() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let contributors_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/contributors")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("order_by".to_string(), "commits".to_string()),
+                mockito::Matcher::UrlEncoded("sort".to_string(), "desc".to_string()),
+                mockito::Matcher::UrlEncoded("per_page".to_string(), CONTRIBUTORS_PAGE_SIZE.to_string()),
+            ]))
+            .with_header("X-Total", "25000")
+            .with_body(r#"[{"name": "user2", "commits": 20}, {"name": "user1", "commits": 5}]"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let other_pages_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/contributors")
+            .match_query(mockito::Matcher::UrlEncoded("page".to_string(), "2".to_string()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        let contributors = gl.get_contributors("group/project").await.unwrap();
+        assert_eq!(contributors.count, 25000);
+        assert_eq!(
+            contributors.top.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            vec!["user2", "user1"]
+        );
+
+        contributors_mock.assert_async().await;
+        other_pages_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_latest_pipeline_status() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let pipelines_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/pipelines")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("ref".to_string(), "main".to_string()),
+                mockito::Matcher::UrlEncoded("per_page".to_string(), "1".to_string()),
+            ]))
+            .with_body(r#"[{"id": 1, "status": "success", "ref": "main"}]"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let not_found_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fno-ci/pipelines")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(
+            gl.get_latest_pipeline_status("group/project", "main").await.unwrap(),
+            Some("success".to_string())
+        );
+        assert_eq!(
+            gl.get_latest_pipeline_status("group/no-ci", "main").await.unwrap(),
+            None
+        );
+
+        pipelines_mock.assert_async().await;
+        not_found_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_recent_commit_count() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let commits_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/commits")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("ref_name".to_string(), "main".to_string()),
+                mockito::Matcher::UrlEncoded("since".to_string(), "2024-01-01T00:00:00Z".to_string()),
+                mockito::Matcher::UrlEncoded("per_page".to_string(), "1".to_string()),
+            ]))
+            .with_header("X-Total", "42")
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        let since = "2024-01-01T00:00:00Z".parse().unwrap();
+        assert_eq!(
+            gl.get_recent_commit_count("group/project", "main", since).await.unwrap(),
+            Some(42)
+        );
+
+        commits_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_weekly_commit_activity() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let committed_date = (Utc::now() - chrono::Duration::days(10)).to_rfc3339();
+        let commits_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/commits")
+            .match_query(mockito::Matcher::UrlEncoded("ref_name".to_string(), "main".to_string()))
+            .with_body(format!(
+                r#"[{{"web_url": "https://gitlab.com/group/project/-/commit/1", "committed_date": "{committed_date}"}}]"#
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+
+        // The commit activity is only collected when enabled
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(
+            gl.get_weekly_commit_activity("group/project", "main").await.unwrap(),
+            None
+        );
+
+        let options = ClientOptions {
+            commit_activity: true,
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        let activity = gl.get_weekly_commit_activity("group/project", "main").await.unwrap().unwrap();
+        assert_eq!(activity[COMMIT_ACTIVITY_WEEKS - 2], 1);
+        assert_eq!(activity.iter().sum::<u32>(), 1);
+
+        commits_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_project_without_statistics() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "statistics".to_string(),
+                "false".to_string(),
+            ))
+            .with_body(
+                r#"{
+                    "default_branch": "main",
+                    "path_with_namespace": "group/project",
+                    "star_count": 10,
+                    "web_url": "https://gitlab.com/group/project"
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let options = ClientOptions {
+            collection: GitlabCollectionOptions {
+                project_statistics: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        assert!(GLApi::get_project(&gl, "group/project").await.is_ok());
+        project_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glapi_get_commits_empty_repository() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let commits_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/commits")
+            .match_query(mockito::Matcher::Any)
+            .with_header("X-Total", "0")
+            .with_body("[]")
+            .expect(2)
+            .create_async()
+            .await;
+
+        let gl = GLApi::new(&server.url(), "token", &ClientOptions::default()).await.unwrap();
+        assert_eq!(gl.get_latest_commit("group/project", "main").await.unwrap(), None);
+        assert_eq!(gl.get_first_commit("group/project", "main").await.unwrap(), None);
+
+        commits_mock.assert_async().await;
+    }
+}
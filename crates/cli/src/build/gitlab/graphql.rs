@@ -0,0 +1,563 @@
+//! This module defines the GitLab client that gets the projects metadata
+//! using the GraphQL API.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use gitlab::RestError;
+use gitlab::api::ApiError;
+use landscape2_core::data::Commit;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tracing::instrument;
+
+use super::{
+    GitlabCollectionOptions,
+    client::{GL, GLApi, GitlabError, is_project_id},
+    dto::{GitLabContributors, GitLabProject},
+};
+
+/// GraphQL query used to fetch the project information.
+const PROJECT_GRAPHQL_QUERY: &str = r"
+query($fullPath: ID!) {
+    project(fullPath: $fullPath) {
+        archived
+        avatarUrl
+        createdAt
+        description
+        forkedFrom {
+            fullPath
+        }
+        fullPath
+        languages {
+            name
+            share
+        }
+        lastActivityAt
+        openIssuesCount
+        openMergeRequestsCount
+        repository {
+            rootRef
+        }
+        starCount
+        topics
+        visibility
+        webUrl
+    }
+}
+";
+
+/// Maximum number of projects whose GraphQL query results are kept to be
+/// reused by the operations that follow getting the project.
+const MAX_CACHED_PROJECTS: usize = 1_000;
+
+/// GL implementation backed by the GitLab GraphQL API. The project details,
+/// languages and open issues and merge requests counts are fetched with a
+/// single query when getting the project, and reused by the corresponding
+/// operations later. The REST API is used for the remaining operations, as
+/// well as to get the project license, statistics and mirror details (not
+/// available in the GraphQL API) when their collection is enabled.
+pub(super) struct GLGraphQL {
+    rest: GLApi,
+    rest_project_fallback: bool,
+    projects: Mutex<HashMap<String, GitLabGraphQLProject>>,
+}
+
+impl GLGraphQL {
+    /// Create a new GLGraphQL instance.
+    pub fn new(rest: GLApi, collection: &GitlabCollectionOptions) -> Self {
+        Self {
+            rest,
+            rest_project_fallback: collection.license
+                || collection.project_statistics
+                || collection.github_mirrors,
+            projects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the project information fetched previously from the GraphQL API.
+    fn cached_project(&self, project_path: &str) -> Option<GitLabGraphQLProject> {
+        self.projects.lock().expect("projects lock not to be poisoned").get(project_path).cloned()
+    }
+
+    /// Get the project information from the GraphQL API, caching it so that
+    /// it can be reused by other operations later.
+    #[instrument(skip(self), err)]
+    async fn query_project(&self, project_path: &str) -> Result<GitLabProject> {
+        // Projects can only be queried by their full path in the GraphQL API
+        if is_project_id(project_path) {
+            return self.rest.get_project(project_path).await;
+        }
+
+        let body = serde_json::json!({
+            "query": PROJECT_GRAPHQL_QUERY,
+            "variables": { "fullPath": project_path },
+        });
+        self.rest.use_request_budget()?;
+        let response = self.rest.http_client.post(self.rest.graphql_url()).json(&body).send().await?;
+        if !response.status().is_success() {
+            return Err(ApiError::<RestError>::GitlabWithStatus {
+                status: response.status(),
+                msg: "error querying graphql api".to_string(),
+            }
+            .into());
+        }
+
+        let response: GitLabGraphQLResponse = response.json().await?;
+        let Some(project) = response.data.and_then(|data| data.project) else {
+            if let Some(errors) = response.errors {
+                bail!("error querying graphql api: {errors:?}");
+            }
+            return Err(ApiError::<RestError>::GitlabWithStatus {
+                status: StatusCode::NOT_FOUND,
+                msg: "project not found".to_string(),
+            }
+            .into());
+        };
+        {
+            // Cached projects are only needed until the project's operations
+            // complete, so the cache is cleared when it grows too large
+            let mut projects = self.projects.lock().expect("projects lock not to be poisoned");
+            if projects.len() >= MAX_CACHED_PROJECTS {
+                projects.clear();
+            }
+            projects.insert(project_path.to_string(), project.clone());
+        }
+
+        // The license, the statistics and the mirror details are not available
+        // in the GraphQL API, so they are taken from the REST project when any
+        // of them is collected
+        let rest_project = if self.rest_project_fallback {
+            Some(self.rest.get_project(project_path).await?)
+        } else {
+            None
+        };
+        let (import_url, license, mirror, statistics) = match rest_project {
+            Some(rest_project) => (
+                rest_project.import_url,
+                rest_project.license,
+                rest_project.mirror,
+                rest_project.statistics,
+            ),
+            None => (None, None, false, None),
+        };
+
+        Ok(GitLabProject {
+            archived: project.archived,
+            avatar_url: project.avatar_url,
+            created_at: project.created_at,
+            description: project.description,
+            default_branch: project.repository.and_then(|r| r.root_ref).unwrap_or_default(),
+            forked: project.forked_from.is_some(),
+            import_url,
+            last_activity_at: project.last_activity_at,
+            mirror,
+            path_with_namespace: project.full_path,
+            star_count: project.star_count,
+            statistics,
+            topics: project.topics,
+            visibility: project.visibility,
+            web_url: project.web_url,
+            license,
+        })
+    }
+}
+
+#[async_trait]
+impl GL for GLGraphQL {
+    /// [GL::get_contributors]
+    async fn get_contributors(&self, project_path: &str) -> Result<GitLabContributors, GitlabError> {
+        Ok(self.rest.get_contributors(project_path).await?)
+    }
+
+    /// [GL::get_first_commit]
+    async fn get_first_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError> {
+        Ok(self.rest.get_first_commit(project_path, ref_).await?)
+    }
+
+    /// [GL::get_good_first_issues_count]
+    async fn get_good_first_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        Ok(self.rest.get_good_first_issues_count(project_path).await?)
+    }
+
+    /// [GL::get_group_projects]
+    async fn get_group_projects(&self, group_path: &str) -> Result<Vec<String>, GitlabError> {
+        Ok(self.rest.get_group_projects(group_path).await?)
+    }
+
+    /// [GL::get_help_wanted_count]
+    async fn get_help_wanted_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        Ok(self.rest.get_help_wanted_count(project_path).await?)
+    }
+
+    /// [GL::get_languages]
+    async fn get_languages(&self, project_path: &str) -> Result<Option<BTreeMap<String, f64>>, GitlabError> {
+        let Some(project) = self.cached_project(project_path) else {
+            return Ok(self.rest.get_languages(project_path).await?);
+        };
+        let languages: BTreeMap<String, f64> =
+            project.languages.into_iter().map(|language| (language.name, language.share)).collect();
+        if languages.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(languages))
+    }
+
+    /// [GL::get_latest_commit]
+    async fn get_latest_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError> {
+        Ok(self.rest.get_latest_commit(project_path, ref_).await?)
+    }
+
+    /// [GL::get_latest_pipeline_status]
+    async fn get_latest_pipeline_status(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<String>, GitlabError> {
+        Ok(self.rest.get_latest_pipeline_status(project_path, ref_).await?)
+    }
+
+    /// [GL::get_latest_release]
+    async fn get_latest_release(
+        &self,
+        project_path: &str,
+    ) -> Result<Option<landscape2_core::data::Release>, GitlabError> {
+        Ok(self.rest.get_latest_release(project_path).await?)
+    }
+
+    /// [GL::get_open_issues_count]
+    async fn get_open_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        match self.cached_project(project_path) {
+            Some(project) => Ok(project.open_issues_count),
+            None => Ok(self.rest.get_open_issues_count(project_path).await?),
+        }
+    }
+
+    /// [GL::get_open_mrs_count]
+    async fn get_open_mrs_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        match self.cached_project(project_path) {
+            Some(project) => Ok(project.open_merge_requests_count),
+            None => Ok(self.rest.get_open_mrs_count(project_path).await?),
+        }
+    }
+
+    /// [GL::get_project]
+    async fn get_project(&self, project_path: &str) -> Result<GitLabProject, GitlabError> {
+        Ok(self.query_project(project_path).await?)
+    }
+
+    /// [GL::get_recent_commit_count]
+    async fn get_recent_commit_count(
+        &self,
+        project_path: &str,
+        ref_: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<usize>, GitlabError> {
+        Ok(self.rest.get_recent_commit_count(project_path, ref_, since).await?)
+    }
+
+    /// [GL::get_weekly_commit_activity]
+    async fn get_weekly_commit_activity(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<Vec<u32>>, GitlabError> {
+        Ok(self.rest.get_weekly_commit_activity(project_path, ref_).await?)
+    }
+}
+
+/// GitLab GraphQL API response.
+#[derive(Debug, Clone, Deserialize)]
+struct GitLabGraphQLResponse {
+    data: Option<GitLabGraphQLData>,
+    errors: Option<Vec<serde_json::Value>>,
+}
+
+/// GitLab GraphQL API response data.
+#[derive(Debug, Clone, Deserialize)]
+struct GitLabGraphQLData {
+    /// Null when the project does not exist or is not visible.
+    project: Option<GitLabGraphQLProject>,
+}
+
+/// GitLab project information returned by the GraphQL API.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitLabGraphQLProject {
+    #[serde(default)]
+    archived: bool,
+    /// Null for projects without an avatar.
+    avatar_url: Option<String>,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    description: Option<String>,
+    /// Only present when the project is a fork.
+    #[serde(default)]
+    forked_from: Option<serde::de::IgnoredAny>,
+    full_path: String,
+    #[serde(default)]
+    languages: Vec<GitLabGraphQLLanguage>,
+    #[serde(default)]
+    last_activity_at: Option<DateTime<Utc>>,
+    open_issues_count: Option<usize>,
+    open_merge_requests_count: Option<usize>,
+    repository: Option<GitLabGraphQLRepository>,
+    star_count: i64,
+    #[serde(default)]
+    topics: Vec<String>,
+    /// Visibility level of the project (public, internal or private).
+    #[serde(default)]
+    visibility: Option<String>,
+    web_url: String,
+}
+
+/// GitLab GraphQL project language information.
+#[derive(Debug, Clone, Deserialize)]
+struct GitLabGraphQLLanguage {
+    name: String,
+    share: f64,
+}
+
+/// GitLab GraphQL project repository information.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitLabGraphQLRepository {
+    root_ref: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::build::gitlab::{ClientOptions, GitlabCollectionOptions, client::permanent_error_status};
+
+    #[tokio::test]
+    async fn glgraphql_get_project_reuses_query_results() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let graphql_mock = server
+            .mock("POST", "/api/graphql")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "variables": { "fullPath": "group/project" }
+            })))
+            .with_body(
+                r#"{
+                    "data": {
+                        "project": {
+                            "archived": true,
+                            "avatarUrl": "https://gitlab.com/uploads/-/system/project/avatar/1/logo.png",
+                            "createdAt": "2020-01-01T00:00:00Z",
+                            "description": "Project description",
+                            "forkedFrom": { "fullPath": "upstream/project" },
+                            "fullPath": "group/project",
+                            "languages": [{ "name": "Rust", "share": 75.5 }, { "name": "Go", "share": 24.5 }],
+                            "lastActivityAt": "2024-01-01T00:00:00Z",
+                            "openIssuesCount": 3,
+                            "openMergeRequestsCount": 2,
+                            "repository": { "rootRef": "main" },
+                            "starCount": 10,
+                            "topics": ["topic1"],
+                            "visibility": "public",
+                            "webUrl": "https://gitlab.com/group/project"
+                        }
+                    }
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{
+                    "default_branch": "main",
+                    "path_with_namespace": "group/project",
+                    "star_count": 10,
+                    "statistics": { "repository_size": 1024 },
+                    "web_url": "https://gitlab.com/group/project",
+                    "license": { "name": "Apache License 2.0" }
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let rest_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/api/v4/projects/.+/".into()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let options = ClientOptions::default();
+        let gl = GLGraphQL::new(
+            GLApi::new(&server.url(), "token", &options).await.unwrap(),
+            &options.collection,
+        );
+        let project = gl.get_project("group/project").await.unwrap();
+        assert!(project.archived);
+        assert_eq!(
+            project.avatar_url,
+            Some("https://gitlab.com/uploads/-/system/project/avatar/1/logo.png".to_string())
+        );
+        assert_eq!(project.default_branch, "main");
+        assert_eq!(project.created_at, Some("2020-01-01T00:00:00Z".parse().unwrap()));
+        assert_eq!(project.description, Some("Project description".to_string()));
+        assert!(project.forked);
+        assert_eq!(
+            project.last_activity_at,
+            Some("2024-01-01T00:00:00Z".parse().unwrap())
+        );
+        assert_eq!(
+            project.license.map(|l| l.name),
+            Some("Apache License 2.0".to_string())
+        );
+        assert_eq!(project.star_count, 10);
+        assert_eq!(project.statistics.and_then(|s| s.repository_size), Some(1024));
+        assert_eq!(project.topics, vec!["topic1".to_string()]);
+        assert_eq!(project.visibility, Some("public".to_string()));
+        assert_eq!(
+            gl.get_languages("group/project").await.unwrap(),
+            Some(BTreeMap::from([
+                ("Go".to_string(), 24.5),
+                ("Rust".to_string(), 75.5)
+            ]))
+        );
+        assert_eq!(gl.get_open_issues_count("group/project").await.unwrap(), Some(3));
+        assert_eq!(gl.get_open_mrs_count("group/project").await.unwrap(), Some(2));
+
+        graphql_mock.assert_async().await;
+        project_mock.assert_async().await;
+        rest_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glgraphql_get_project_skips_rest_when_not_needed() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let graphql_mock = server
+            .mock("POST", "/api/graphql")
+            .with_body(
+                r#"{
+                    "data": {
+                        "project": {
+                            "archived": false,
+                            "fullPath": "group/project",
+                            "repository": { "rootRef": "main" },
+                            "starCount": 10,
+                            "webUrl": "https://gitlab.com/group/project"
+                        }
+                    }
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let rest_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/api/v4/projects/".into()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        // The license, statistics and mirror details are not collected
+        let options = ClientOptions {
+            collection: GitlabCollectionOptions {
+                license: false,
+                project_statistics: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let gl = GLGraphQL::new(
+            GLApi::new(&server.url(), "token", &options).await.unwrap(),
+            &options.collection,
+        );
+        let project = gl.get_project("group/project").await.unwrap();
+        assert!(!project.forked);
+        assert_eq!(project.license.map(|l| l.name), None);
+        assert!(project.statistics.is_none());
+
+        graphql_mock.assert_async().await;
+        rest_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn glgraphql_projects_cache_is_bounded() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let _graphql_mock = server
+            .mock("POST", "/api/graphql")
+            .with_body(
+                r#"{
+                    "data": {
+                        "project": {
+                            "fullPath": "group/project",
+                            "starCount": 10,
+                            "webUrl": "https://gitlab.com/group/project"
+                        }
+                    }
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let options = ClientOptions {
+            collection: GitlabCollectionOptions {
+                license: false,
+                project_statistics: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let gl = GLGraphQL::new(
+            GLApi::new(&server.url(), "token", &options).await.unwrap(),
+            &options.collection,
+        );
+        for i in 0..=MAX_CACHED_PROJECTS {
+            gl.get_project(&format!("group/project{i}")).await.unwrap();
+        }
+        assert_eq!(gl.projects.lock().unwrap().len(), 1);
+        assert!(gl.cached_project(&format!("group/project{MAX_CACHED_PROJECTS}")).is_some());
+    }
+
+    #[tokio::test]
+    async fn glgraphql_get_project_not_found() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let _graphql_mock = server
+            .mock("POST", "/api/graphql")
+            .with_body(r#"{"data": {"project": null}}"#)
+            .create_async()
+            .await;
+
+        let options = ClientOptions::default();
+        let gl = GLGraphQL::new(
+            GLApi::new(&server.url(), "token", &options).await.unwrap(),
+            &options.collection,
+        );
+        let err = gl.get_project("group/project").await.unwrap_err();
+        assert_eq!(permanent_error_status(&err.into()), Some(404));
+    }
+}
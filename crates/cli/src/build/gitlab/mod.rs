@@ -0,0 +1,6452 @@
+//! This module defines some types used to represent the information collected
+//! from GitLab for each of the landscape items repositories (when applicable),
+//! as well as the functionality used to collect that information.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail, format_err};
+use chrono::{DateTime, Utc};
+use deadpool::unmanaged::{Object, Pool};
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use landscape2_core::data::{
+    Commit, Contributors as DataContributors, GitData, RepositoryGitData, TopContributor,
+    normalize_gitlab_url, parse_gitlab_group_url, parse_gitlab_url,
+};
+use landscape2_core::settings::{GitLabCollectionProfile, LandscapeSettings};
+use rand::Rng;
+use regex::Regex;
+use serde::Serialize;
+use tracing::{Instrument, debug, info, info_span, instrument, warn};
+use url::Url;
+
+use self::{
+    cache::{
+        FailedFetch, FailedFetches, PartialCacheWriter, from_cache, get_offline_data, keep_cached_data,
+        read_cache,
+    },
+    client::{
+        DynGL, GitlabError, MAX_RETRY_AFTER, UnavailableEndpointInstances, create_gitlab_pool,
+        create_unauthenticated_gitlab_pool, decode_project_path, error_kind, is_project_id,
+        is_rate_limit_error, is_retryable, is_token_error, permanent_error_status, retry_after,
+        underlying_error,
+    },
+    dto::{GitLabContributor, GitLabLicense, GitLabProject, PrereleasesFilter},
+    retry::{CircuitBreaker, CircuitBreakerConfig, RequestBudget, RequestBudgetExhausted, RetryConfig},
+    tokens::{
+        CommandTokenProvider, DynTokenProvider, GitlabInstanceConfig, find_config_for_instance,
+        get_instance_cache_ttl, parse_gitlab_base_url_aliases_env, parse_gitlab_tokens_env, resolve_api_url,
+    },
+};
+use super::{
+    LandscapeData,
+    cache::Cache,
+    github::{DynGitHubCollector, GitHubApiCollector, GitHubArgs},
+};
+
+mod cache;
+mod client;
+mod dto;
+mod graphql;
+mod retry;
+mod tokens;
+
+/// File used to cache data collected from GitLab (compressed, as it can get
+/// quite big for landscapes with many repositories).
+const GITLAB_CACHE_FILE: &str = "gitlab.json.gz";
+
+/// File used to cache the GitLab repositories that failed permanently.
+const GITLAB_ERRORS_CACHE_FILE: &str = "gitlab-errors.json";
+
+/// File the GitLab data collection report is written to (when requested).
+const GITLAB_REPORT_FILE: &str = "gitlab-report.json";
+
+/// Default number of days the GitLab data in the cache is valid for.
+const DEFAULT_GITLAB_CACHE_TTL: u32 = 7;
+
+/// Default GitLab instance URL.
+const DEFAULT_GITLAB_URL: &str = "https://gitlab.com";
+
+/// Environment variable used to enable or disable checking the scopes of the
+/// GitLab tokens provided (enabled by default).
+const GITLAB_CHECK_TOKEN_SCOPES: &str = "GITLAB_CHECK_TOKEN_SCOPES";
+
+/// Environment variable containing the number of remaining requests allowed
+/// by the GitLab rate limit below which a warning will be emitted.
+const GITLAB_RATE_LIMIT_WARNING_THRESHOLD: &str = "GITLAB_RATE_LIMIT_WARNING_THRESHOLD";
+
+/// Environment variable containing the timeout (in seconds) applied to each
+/// request made to the GitLab API.
+const GITLAB_HTTP_TIMEOUT_SECS: &str = "GITLAB_HTTP_TIMEOUT_SECS";
+
+/// Environment variable containing the path of a PEM bundle with additional
+/// root CA certificates to trust when connecting to GitLab instances.
+const GITLAB_CA_BUNDLE: &str = "GITLAB_CA_BUNDLE";
+
+/// Environment variable containing the maximum random delay (in milliseconds)
+/// applied before the first request of each of the concurrent collection
+/// tasks of an instance, so that they don't hit the instance all at once.
+const GITLAB_STARTUP_JITTER_MS: &str = "GITLAB_STARTUP_JITTER_MS";
+
+/// Environment variable containing the url of the proxy used for the requests
+/// made directly to the GitLab API. When not set, the standard proxy variables
+/// (`HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`) are honored.
+const GITLAB_PROXY: &str = "GITLAB_PROXY";
+
+/// Environment variable containing the maximum size (in bytes) of the bodies
+/// of the responses read using the raw HTTP client (languages and issues
+/// statistics), so that misbehaving instances cannot exhaust the memory.
+const GITLAB_MAX_RESPONSE_SIZE: &str = "GITLAB_MAX_RESPONSE_SIZE";
+
+/// Environment variable containing the User-Agent sent in the requests made
+/// to the GitLab API (some self-hosted instances block unknown agents).
+const GITLAB_USER_AGENT: &str = "GITLAB_USER_AGENT";
+
+/// Environment variable containing the path of the file the GitLab
+/// repositories that could not be collected are written to (in json format),
+/// so that CI pipelines can act on them.
+const GITLAB_FAILURES_FILE: &str = "GITLAB_FAILURES_FILE";
+
+/// Environment variable containing the number of repositories collected
+/// between the partial writes of the GitLab cache during the build, so that
+/// the progress made is not lost if the build is interrupted (0 disables them).
+const GITLAB_CACHE_FLUSH_INTERVAL: &str = "GITLAB_CACHE_FLUSH_INTERVAL";
+
+/// Environment variable containing the command used to get fresh tokens to
+/// replace the GitLab tokens that expire during the build (i.e. short-lived
+/// tokens issued by a secrets manager). The command is split into the program
+/// and its arguments following the shell quoting rules. The url of the
+/// instance is appended to its arguments, and the fresh token is read from its
+/// output.
+const GITLAB_TOKEN_COMMAND: &str = "GITLAB_TOKEN_COMMAND";
+
+/// Default User-Agent sent in the requests made to the GitLab API.
+const DEFAULT_USER_AGENT: &str = concat!("landscape2/", env!("CARGO_PKG_VERSION"));
+
+/// Default maximum size of the bodies of the responses read using the raw HTTP
+/// client (5MB).
+const DEFAULT_MAX_RESPONSE_SIZE: usize = 5 * 1024 * 1024;
+
+/// Default timeout applied to each request made to the GitLab API.
+const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default maximum random delay applied before the first request of each of
+/// the concurrent collection tasks of an instance.
+const DEFAULT_STARTUP_JITTER: Duration = Duration::from_millis(200);
+
+/// Default number of repositories collected between the partial writes of the
+/// GitLab cache.
+const DEFAULT_CACHE_FLUSH_INTERVAL: usize = 100;
+
+/// Default rate limit remaining requests warning threshold.
+const DEFAULT_RATE_LIMIT_WARNING_THRESHOLD: u64 = 10;
+
+/// Number of projects of a GitLab group collected concurrently.
+const GROUP_PROJECTS_CONCURRENCY: usize = 4;
+
+/// Maximum number of recent commits counted when GitLab does not report the
+/// total number of commits and they have to be paged through.
+const MAX_RECENT_COMMITS_COUNT: usize = 1000;
+
+/// Number of weeks covered by the weekly commit activity.
+const COMMIT_ACTIVITY_WEEKS: usize = 52;
+
+/// Number of top contributors (by number of commits) collected for each
+/// repository.
+const TOP_CONTRIBUTORS_COUNT: usize = 10;
+
+/// Default labels used to count the good first issues.
+const DEFAULT_GOOD_FIRST_ISSUES_LABELS: &[&str] = &["good first issue"];
+
+/// Default labels used to count the help wanted issues.
+const DEFAULT_HELP_WANTED_LABELS: &[&str] = &["help wanted"];
+
+/// Default pattern of the tags of the releases considered pre-releases.
+const DEFAULT_PRERELEASE_TAGS_PATTERN: &str = r"(?i)(^|[^a-z])(rc|alpha|beta)([^a-z]|$)";
+
+/// Default total number of bytes the languages percentages are scaled to when
+/// the repository size is not available.
+const DEFAULT_LANGUAGES_TOTAL_BYTES: f64 = 100_000.0;
+
+/// GitLab data collection arguments.
+#[derive(clap::Args, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct GitLabArgs {
+    /// GitLab API used to collect the repositories data.
+    #[arg(long = "gitlab-api", value_enum, default_value_t = GitLabApi::Rest)]
+    pub api: GitLabApi,
+
+    /// Number of days the data collected from GitLab is cached for.
+    #[arg(long = "gitlab-cache-ttl", default_value_t = DEFAULT_GITLAB_CACHE_TTL)]
+    pub cache_ttl: u32,
+
+    /// Refresh the GitLab data cached before the instant provided (RFC 3339),
+    /// even if it hasn't expired yet. This can be used to refresh some of the
+    /// repositories of large landscapes in a controlled way.
+    #[arg(long = "gitlab-refresh-before")]
+    pub refresh_before: Option<DateTime<Utc>>,
+
+    /// Write a report of the GitLab data collection to the cache directory.
+    #[arg(long = "gitlab-report")]
+    pub report: bool,
+
+    /// Write the GitLab data collected as newline-delimited JSON (one
+    /// repository per line) to the file provided (use `-` for stdout), in
+    /// addition to the cache. Useful to ingest it into other systems.
+    #[arg(long = "gitlab-ndjson-output")]
+    pub ndjson_output: Option<PathBuf>,
+
+    /// Fail when no valid token is available for any of the GitLab instances
+    /// with repositories in the landscape.
+    #[arg(long = "gitlab-require-tokens")]
+    pub require_tokens: bool,
+
+    /// Fail when the GitLab tokens environment variable is malformed, instead
+    /// of ignoring the segments that cannot be used.
+    #[arg(long = "gitlab-strict-tokens")]
+    pub strict_tokens: bool,
+
+    /// Collect the public repositories data of the GitLab instances without a
+    /// valid token using unauthenticated requests (best effort, as they are
+    /// subject to stricter rate limits).
+    #[arg(long = "gitlab-allow-unauthenticated")]
+    pub allow_unauthenticated: bool,
+
+    /// List the GitLab repositories that would be fetched from each instance
+    /// (and the ones that would be skipped) without collecting any data.
+    #[arg(long = "gitlab-dry-run")]
+    pub dry_run: bool,
+
+    /// Maximum number of requests made to the GitLab API during the build.
+    /// Once exhausted, cached data is used (even if it has expired).
+    #[arg(long = "gitlab-max-requests")]
+    pub max_requests: Option<u64>,
+
+    /// Maximum number of GitLab repositories collected (the first ones, in
+    /// alphabetical order), useful to run quick builds when testing. The
+    /// repositories skipped keep their cached data.
+    #[arg(long = "gitlab-max-repos")]
+    pub max_repos: Option<usize>,
+
+    /// Ignore the GitLab data in the cache, fetching all repositories again
+    /// (fresh results are still written to the cache).
+    #[arg(long = "gitlab-ignore-cache")]
+    pub ignore_cache: bool,
+
+    /// Refresh only the metadata of the GitLab repositories whose cached data
+    /// has expired (using just the project endpoint), keeping the rest of the
+    /// data from the cache. Repositories not cached are fully collected.
+    #[arg(long = "gitlab-metadata-only-refresh")]
+    pub metadata_only_refresh: bool,
+
+    /// Use only the GitLab data in the cache (regardless of its age), without
+    /// making any requests to GitLab. Repositories not cached are skipped.
+    #[arg(long = "gitlab-offline", conflicts_with = "ignore_cache")]
+    pub offline: bool,
+
+    /// Display a progress bar while collecting the GitLab data (only when
+    /// running in an interactive terminal).
+    #[arg(long = "gitlab-progress")]
+    pub progress: bool,
+
+    /// Filter used to collect only the data of some of the items.
+    #[command(flatten)]
+    pub filter: GitLabItemsFilter,
+}
+
+/// GitLab API used to collect the repositories data.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum GitLabApi {
+    /// Use the GraphQL API when possible, falling back to the REST API for the
+    /// information not available on it.
+    Graphql,
+    /// Use the REST API.
+    Rest,
+}
+
+/// Filter used to collect from GitLab only the data of the repositories of
+/// some of the landscape items. The cached data is used for the rest of them.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct GitLabItemsFilter {
+    /// Only collect from GitLab the repositories of the items in the category
+    /// provided (can be repeated).
+    #[arg(long = "gitlab-only-category")]
+    pub categories: Vec<String>,
+
+    /// Only collect from GitLab the repositories of the items in the
+    /// subcategory provided (can be repeated).
+    #[arg(long = "gitlab-only-subcategory")]
+    pub subcategories: Vec<String>,
+
+    /// Only collect from GitLab the repositories of the items whose name
+    /// matches the glob pattern provided (can be repeated).
+    #[arg(long = "gitlab-only-item")]
+    pub items: Vec<String>,
+}
+
+impl GitLabItemsFilter {
+    /// Check if the filter is empty (all items match it).
+    fn is_empty(&self) -> bool {
+        self.categories.is_empty() && self.subcategories.is_empty() && self.items.is_empty()
+    }
+}
+
+/// Options used to setup the GitLab API clients.
+#[derive(Debug, Clone)]
+struct ClientOptions {
+    allow_unauthenticated: bool,
+    api: GitLabApi,
+    ca_bundle: Option<PathBuf>,
+    /// Number of repositories collected between the partial writes of the
+    /// cache (0 disables them).
+    cache_flush_interval: usize,
+    check_token_scopes: bool,
+    circuit_breaker: CircuitBreakerConfig,
+    collection: GitlabCollectionOptions,
+    commit_activity: bool,
+    good_first_issues_labels: Vec<String>,
+    help_wanted_labels: Vec<String>,
+    http_timeout: Duration,
+    languages_unavailable: Arc<UnavailableEndpointInstances>,
+    max_response_size: usize,
+    /// Filter used to skip the pre-releases when selecting the latest release
+    /// (when not set, all releases are considered).
+    prereleases_filter: Option<PrereleasesFilter>,
+    proxy: Option<String>,
+    rate_limit_warning_threshold: u64,
+    request_budget: Arc<RequestBudget>,
+    retry_config: RetryConfig,
+    startup_jitter: Duration,
+    /// Provider used to replace the tokens that expire during the build (when
+    /// not set, clients whose token expires are removed from the pool).
+    token_provider: Option<DynTokenProvider>,
+    user_agent: String,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            allow_unauthenticated: false,
+            api: GitLabApi::Rest,
+            ca_bundle: None,
+            cache_flush_interval: DEFAULT_CACHE_FLUSH_INTERVAL,
+            check_token_scopes: true,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            collection: GitlabCollectionOptions::default(),
+            commit_activity: false,
+            good_first_issues_labels: DEFAULT_GOOD_FIRST_ISSUES_LABELS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            help_wanted_labels: DEFAULT_HELP_WANTED_LABELS.iter().map(ToString::to_string).collect(),
+            http_timeout: DEFAULT_HTTP_TIMEOUT,
+            languages_unavailable: Arc::default(),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            prereleases_filter: None,
+            proxy: None,
+            rate_limit_warning_threshold: DEFAULT_RATE_LIMIT_WARNING_THRESHOLD,
+            request_budget: Arc::new(RequestBudget::default()),
+            retry_config: RetryConfig::default(),
+            startup_jitter: DEFAULT_STARTUP_JITTER,
+            token_provider: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+        }
+    }
+}
+
+impl ClientOptions {
+    /// Create a new clients options instance from the landscape settings and
+    /// arguments provided, as well as from the environment.
+    fn new(settings: &LandscapeSettings, args: &GitLabArgs) -> Result<Self> {
+        let mut options = Self {
+            allow_unauthenticated: args.allow_unauthenticated,
+            api: args.api,
+            ca_bundle: env::var_os(GITLAB_CA_BUNDLE).map(PathBuf::from),
+            circuit_breaker: CircuitBreakerConfig::from_env()?,
+            collection: GitlabCollectionOptions {
+                metadata_only_refresh: args.metadata_only_refresh,
+                ..GitlabCollectionOptions::new(settings)
+            },
+            commit_activity: collect_commit_activity(settings),
+            good_first_issues_labels: get_good_first_issues_labels(settings),
+            help_wanted_labels: get_help_wanted_labels(settings),
+            prereleases_filter: get_prereleases_filter(settings)?,
+            request_budget: Arc::new(RequestBudget::new(args.max_requests)),
+            retry_config: RetryConfig::from_env()?,
+            ..Default::default()
+        };
+        if let Ok(cache_flush_interval) = env::var(GITLAB_CACHE_FLUSH_INTERVAL) {
+            options.cache_flush_interval = cache_flush_interval
+                .parse()
+                .with_context(|| format!("invalid {GITLAB_CACHE_FLUSH_INTERVAL} value"))?;
+        }
+        if let Ok(check_token_scopes) = env::var(GITLAB_CHECK_TOKEN_SCOPES) {
+            options.check_token_scopes = check_token_scopes
+                .parse()
+                .with_context(|| format!("invalid {GITLAB_CHECK_TOKEN_SCOPES} value"))?;
+        }
+        if let Ok(timeout) = env::var(GITLAB_HTTP_TIMEOUT_SECS) {
+            options.http_timeout = Duration::from_secs(
+                timeout.parse().with_context(|| format!("invalid {GITLAB_HTTP_TIMEOUT_SECS} value"))?,
+            );
+        }
+        if let Ok(threshold) = env::var(GITLAB_RATE_LIMIT_WARNING_THRESHOLD) {
+            options.rate_limit_warning_threshold = threshold
+                .parse()
+                .with_context(|| format!("invalid {GITLAB_RATE_LIMIT_WARNING_THRESHOLD} value"))?;
+        }
+        if let Ok(max_response_size) = env::var(GITLAB_MAX_RESPONSE_SIZE) {
+            options.max_response_size = max_response_size
+                .parse()
+                .with_context(|| format!("invalid {GITLAB_MAX_RESPONSE_SIZE} value"))?;
+        }
+        if let Ok(startup_jitter) = env::var(GITLAB_STARTUP_JITTER_MS) {
+            options.startup_jitter = Duration::from_millis(
+                startup_jitter
+                    .parse()
+                    .with_context(|| format!("invalid {GITLAB_STARTUP_JITTER_MS} value"))?,
+            );
+        }
+        if let Ok(proxy) = env::var(GITLAB_PROXY)
+            && !proxy.trim().is_empty()
+        {
+            options.proxy = Some(proxy.trim().to_string());
+        }
+        if let Ok(user_agent) = env::var(GITLAB_USER_AGENT)
+            && !user_agent.trim().is_empty()
+        {
+            options.user_agent = user_agent.trim().to_string();
+        }
+        if let Ok(command) = env::var(GITLAB_TOKEN_COMMAND)
+            && let Some(token_provider) = CommandTokenProvider::from_command(&command)?
+        {
+            options.token_provider = Some(Arc::new(token_provider));
+        }
+        Ok(options)
+    }
+}
+
+/// Collect GitLab data for each of the items repositories in the landscape,
+/// reusing cached data whenever possible. Cached entries are refreshed once
+/// they are older than the cache TTL of their instance (when configured in the
+/// tokens file) or the one provided in the arguments (in days).
+#[instrument(skip_all, err)]
+pub(crate) async fn collect_gitlab_data(
+    cache: &Cache,
+    landscape_data: &LandscapeData,
+    settings: &LandscapeSettings,
+    args: &GitLabArgs,
+    github_args: &GitHubArgs,
+) -> Result<GitData> {
+    debug!("collecting repositories information from gitlab (this may take a while)");
+
+    let ctx = GitLabCollectionContext::new(cache, landscape_data, settings, args, github_args, None).await?;
+    let failures_file = env::var_os(GITLAB_FAILURES_FILE).map(PathBuf::from);
+    collect_gitlab_data_with_context(&ctx, failures_file.as_deref()).await
+}
+
+/// Context of the collection of the GitLab data of the repositories in the
+/// landscape, grouped by instance, along with the clients, cached data and
+/// stats used to collect them.
+pub(crate) struct GitLabCollectionContext<'a> {
+    args: &'a GitLabArgs,
+    cache: &'a Cache,
+    landscape_data: &'a LandscapeData,
+    instance_configs: Vec<GitlabInstanceConfig>,
+    base_url_aliases: BTreeMap<String, String>,
+    client_options: ClientOptions,
+    /// Collector used to get the data of the mirrors of GitHub repositories.
+    github: Option<DynGitHubCollector>,
+    repos_by_instance: BTreeMap<String, Vec<String>>,
+    /// Repositories not collected, whose cached data is kept as is.
+    filtered_out: Vec<String>,
+    repos_options: HashMap<String, RepositoryOptions>,
+    cached_data: Option<GitData>,
+    cached_failed_fetches: FailedFetches,
+    instance_pools: BTreeMap<String, Pool<DynGL>>,
+    stats: Mutex<GitlabCollectionStats>,
+    latencies: Mutex<FetchLatencies>,
+    progress: ProgressBar,
+}
+
+impl<'a> GitLabCollectionContext<'a> {
+    /// Create a new collection context for the landscape provided, using the
+    /// tokens and clients configuration from the environment and settings.
+    /// The token provider given (if any) is used to replace the tokens that
+    /// expire during the build, instead of the token command configured in
+    /// the environment.
+    pub(crate) async fn new(
+        cache: &'a Cache,
+        landscape_data: &'a LandscapeData,
+        settings: &LandscapeSettings,
+        args: &'a GitLabArgs,
+        github_args: &GitHubArgs,
+        token_provider: Option<DynTokenProvider>,
+    ) -> Result<Self> {
+        // Parse GitLab tokens and clients configuration
+        let instance_configs = parse_gitlab_tokens_env(args.strict_tokens)?;
+        let base_url_aliases = parse_gitlab_base_url_aliases_env()?;
+        let mut client_options = ClientOptions::new(settings, args)?;
+        if token_provider.is_some() {
+            client_options.token_provider = token_provider;
+        }
+        let exclude_patterns = get_exclude_patterns(settings)?;
+
+        // Setup the collector used to get the data of the mirrors of GitHub
+        // repositories from GitHub (when enabled in the settings)
+        let github: Option<DynGitHubCollector> = if follow_github_mirrors(settings) {
+            let github = GitHubApiCollector::new(github_args)?;
+            if !github.has_tokens() {
+                warn!(
+                    "github tokens not provided: mirrors of github repositories will be collected from gitlab"
+                );
+            }
+            Some(Box::new(github))
+        } else {
+            None
+        };
+
+        Self::with_config(
+            cache,
+            landscape_data,
+            args,
+            instance_configs,
+            base_url_aliases,
+            client_options,
+            &exclude_patterns,
+            github,
+        )
+        .await
+    }
+
+    /// Create a new collection context for the landscape provided using the
+    /// tokens and clients configuration provided. Repositories matching any of
+    /// the exclude patterns provided are not collected, and the ones of the
+    /// items not matching the filter in the arguments use their cached data.
+    /// No clients are created in dry run and offline modes.
+    #[allow(clippy::too_many_arguments)]
+    async fn with_config(
+        cache: &'a Cache,
+        landscape_data: &'a LandscapeData,
+        args: &'a GitLabArgs,
+        instance_configs: Vec<GitlabInstanceConfig>,
+        base_url_aliases: BTreeMap<String, String>,
+        client_options: ClientOptions,
+        exclude_patterns: &[Regex],
+        github: Option<DynGitHubCollector>,
+    ) -> Result<Self> {
+        // Report the mismatches between the instances configured and the ones
+        // the repositories in the landscape belong to (i.e. typos in their urls)
+        reconcile_instance_configs(landscape_data, &instance_configs).log();
+
+        // Collect GitLab repository URLs and group them by instance
+        let mut repos_by_instance = group_repositories_by_instance(landscape_data, &instance_configs);
+        exclude_repositories(&mut repos_by_instance, exclude_patterns);
+        let mut filtered_out = filter_repositories(&mut repos_by_instance, landscape_data, &args.filter)?;
+        if let Some(max_repos) = args.max_repos {
+            filtered_out.extend(limit_repositories(&mut repos_by_instance, max_repos));
+        }
+        let repos_options = collect_repositories_options(landscape_data);
+
+        debug!(
+            "found {} GitLab instances with repositories: {:?}",
+            repos_by_instance.len(),
+            repos_by_instance.keys().collect::<Vec<_>>()
+        );
+
+        // Read cached data and failed fetches (unless nothing will be fetched
+        // or the cache must be ignored)
+        let (cached_data, cached_failed_fetches) = if args.dry_run {
+            (None, FailedFetches::new())
+        } else if args.ignore_cache {
+            debug!("ignoring gitlab cache: all repositories will be fetched again");
+            (None, FailedFetches::new())
+        } else {
+            read_cache(cache)
+        };
+
+        // Create client pools for each instance that has repositories (the
+        // cached data is used as is in offline mode)
+        let instance_pools = if args.dry_run || args.offline {
+            BTreeMap::new()
+        } else {
+            create_instances_pools(
+                &repos_by_instance,
+                &instance_configs,
+                &base_url_aliases,
+                &client_options,
+                args.require_tokens,
+            )
+            .await?
+        };
+
+        let repos_count: usize = repos_by_instance.values().map(Vec::len).sum();
+        let show_progress = args.progress && !args.dry_run && !args.offline && io::stderr().is_terminal();
+
+        Ok(Self {
+            args,
+            cache,
+            landscape_data,
+            instance_configs,
+            base_url_aliases,
+            client_options,
+            github,
+            repos_by_instance,
+            filtered_out,
+            repos_options,
+            cached_data,
+            cached_failed_fetches,
+            instance_pools,
+            stats: Mutex::new(GitlabCollectionStats::default()),
+            latencies: Mutex::new(FetchLatencies::default()),
+            progress: new_progress_bar(show_progress, repos_count),
+        })
+    }
+}
+
+/// Return a stream that collects the GitLab data of the repositories in the
+/// context provided, yielding the result of each of them (keyed by its url) as
+/// soon as it's available. Instances are processed concurrently, each of them
+/// with its own concurrency limit based on the number of tokens available for
+/// it. Nothing is yielded in dry run mode, and only the cached data is yielded
+/// in offline mode.
+pub(crate) fn stream_gitlab_data<'b>(
+    ctx: &'b GitLabCollectionContext<'_>,
+) -> impl Stream<Item = (String, Result<RepositoryGitData>)> + 'b {
+    if ctx.args.dry_run {
+        return stream::empty().boxed();
+    }
+    if ctx.args.offline {
+        let urls: Vec<String> =
+            ctx.repos_by_instance.values().flatten().chain(&ctx.filtered_out).cloned().collect();
+        let offline_data = get_offline_data(&urls, ctx.cached_data.as_ref());
+        return stream::iter(offline_data.into_iter().map(|(url, repo)| (url, Ok(repo)))).boxed();
+    }
+
+    stream::select_all(ctx.repos_by_instance.iter().map(|(base_url, urls)| {
+        let instance_ctx = InstanceCollectionContext {
+            gl_pool: ctx.instance_pools.get(base_url),
+            base_url,
+            repos_options: &ctx.repos_options,
+            cached_data: ctx.cached_data.as_ref(),
+            cache_ttl: get_instance_cache_ttl(base_url, &ctx.instance_configs, ctx.args.cache_ttl),
+            refresh_before: ctx.args.refresh_before,
+            failed_fetches: &ctx.cached_failed_fetches,
+            stats: &ctx.stats,
+            latencies: &ctx.latencies,
+            progress: &ctx.progress,
+            startup_jitter: ctx.client_options.startup_jitter,
+            github: ctx.github.as_ref(),
+            collection_options: &ctx.client_options.collection,
+            circuit_breaker: ctx.client_options.circuit_breaker,
+        };
+        let api_url = resolve_api_url(base_url, &ctx.base_url_aliases);
+        stream_instance_data(instance_ctx, urls)
+            .map(move |(url, mut result)| {
+                // Urls returned by the api of aliased instances must point to
+                // the instance used in the landscape data
+                if api_url != base_url
+                    && let Ok(repo) = result.as_mut()
+                {
+                    replace_base_url(repo, api_url, base_url);
+                }
+                (url, result)
+            })
+            .boxed()
+    }))
+    .boxed()
+}
+
+/// Collect the GitLab data of the repositories in the context provided,
+/// waiting for all of them to be processed, and write it to the cache. When a
+/// failures file is provided, the repositories that could not be collected are
+/// written to it.
+#[allow(clippy::too_many_lines)]
+async fn collect_gitlab_data_with_context(
+    ctx: &GitLabCollectionContext<'_>,
+    failures_file: Option<&Path>,
+) -> Result<GitData> {
+    let GitLabCollectionContext {
+        args,
+        cache,
+        landscape_data,
+        repos_by_instance,
+        filtered_out,
+        cached_data,
+        cached_failed_fetches,
+        ..
+    } = ctx;
+
+    // Early return if no GitLab repositories found
+    if repos_by_instance.is_empty() && filtered_out.is_empty() {
+        debug!("no gitlab repositories found");
+        return Ok(BTreeMap::new());
+    }
+
+    // In dry run mode we only report the repositories that would be fetched
+    if args.dry_run {
+        log_dry_run(repos_by_instance, &ctx.instance_configs, &ctx.base_url_aliases);
+        return Ok(BTreeMap::new());
+    }
+
+    // In offline mode the cached data is used as is, without creating any
+    // clients for the instances
+    if args.offline {
+        let gitlab_data = stream_gitlab_data(ctx)
+            .filter_map(|(url, result)| future::ready(result.ok().map(|repo| (url, repo))))
+            .collect()
+            .await;
+        return Ok(gitlab_data);
+    }
+
+    if ctx.instance_pools.is_empty() && !repos_by_instance.is_empty() {
+        warn!("gitlab tokens not provided: no information will be collected from gitlab");
+        return Ok(BTreeMap::new());
+    }
+
+    // Collect repositories information from GitLab, reusing cached data when
+    // available. The data collected is flushed to the cache periodically, so
+    // that the progress made is not lost if the build is interrupted
+    let repos_count: usize = repos_by_instance.values().map(Vec::len).sum();
+    debug!("collecting data for {repos_count} gitlab repositories");
+
+    let cache_flush_interval = ctx.client_options.cache_flush_interval;
+    let partial_cache = (cache_flush_interval > 0)
+        .then(|| PartialCacheWriter::new(cache, cached_data.as_ref(), cache_flush_interval));
+    let results: Vec<_> = stream_gitlab_data(ctx)
+        .map(|(url, result)| {
+            if let Some(partial_cache) = &partial_cache {
+                partial_cache.record(&url, &result);
+            }
+            (url, result)
+        })
+        .collect::<BTreeMap<_, _>>()
+        .await
+        .into_iter()
+        .collect();
+    ctx.progress.finish_and_clear();
+    let failures = get_collection_failures(&results);
+    let (mut gitlab_data, mut failed_fetches) = process_results(results, cached_failed_fetches);
+    keep_cached_data(
+        filtered_out,
+        cached_data.as_ref(),
+        cached_failed_fetches,
+        &mut gitlab_data,
+        &mut failed_fetches,
+    );
+
+    // Write data and failed fetches (in json format) to cache, once all the
+    // partial writes have completed so that they cannot overwrite this one
+    if let Some(partial_cache) = partial_cache {
+        partial_cache.finish().await;
+    }
+    cache.write(GITLAB_CACHE_FILE, &serde_json::to_vec_pretty(&gitlab_data)?)?;
+    cache.write(
+        GITLAB_ERRORS_CACHE_FILE,
+        &serde_json::to_vec_pretty(&failed_fetches)?,
+    )?;
+
+    // Log collection summary and write report (when requested)
+    let mut stats = mem::take(&mut *ctx.stats.lock().expect("stats lock not to be poisoned"));
+    let collected_urls: HashSet<&str> =
+        repos_by_instance.values().flatten().chain(filtered_out).map(String::as_str).collect();
+    stats.items_without_data = get_items_without_data(landscape_data, &collected_urls, &gitlab_data);
+    stats.log_summary();
+    ctx.latencies.lock().expect("latencies lock not to be poisoned").log_summary();
+    if args.report {
+        cache.write(GITLAB_REPORT_FILE, &serde_json::to_vec_pretty(&stats)?)?;
+    }
+
+    // Write the data collected in NDJSON format (when requested)
+    if let Some(ndjson_output) = &args.ndjson_output {
+        write_ndjson_output(ndjson_output, &gitlab_data)?;
+    }
+
+    // Write the repositories that could not be collected (when requested)
+    if let Some(failures_file) = failures_file {
+        fs::write(failures_file, serde_json::to_vec_pretty(&failures)?)
+            .with_context(|| format!("error writing gitlab failures file {}", failures_file.display()))?;
+    }
+
+    debug!(
+        "collected data for {} gitlab repositories ({} from cache)",
+        gitlab_data.len(),
+        gitlab_data.values().filter(|repo| repo.from_cache).count()
+    );
+    debug!("done!");
+
+    Ok(gitlab_data)
+}
+
+/// Create a new progress bar to report the repositories processed out of the
+/// total provided. When disabled, a hidden progress bar that draws nothing is
+/// returned.
+fn new_progress_bar(enabled: bool, total: usize) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    let progress = ProgressBar::new(total as u64);
+    if let Ok(style) =
+        ProgressStyle::with_template("{spinner} gitlab [{bar:40}] {pos}/{len} repositories ({eta})")
+    {
+        progress.set_style(style.progress_chars("=> "));
+    }
+    progress
+}
+
+/// Log the repositories that would be fetched from each GitLab instance, as
+/// well as the ones that would be skipped, without making any api calls.
+fn log_dry_run(
+    repos_by_instance: &BTreeMap<String, Vec<String>>,
+    instance_configs: &[GitlabInstanceConfig],
+    base_url_aliases: &BTreeMap<String, String>,
+) {
+    for (base_url, repo_urls) in repos_by_instance {
+        let api_url = resolve_api_url(base_url, base_url_aliases);
+        if let Some(config) = find_config_for_instance(base_url, instance_configs) {
+            info!(
+                "gitlab dry run: {} repositories would be fetched from {base_url} using {api_url} ({} tokens)",
+                repo_urls.len(),
+                config.tokens.len()
+            );
+        } else {
+            info!(
+                "gitlab dry run: {} repositories would be skipped for {base_url} (no token configured)",
+                repo_urls.len()
+            );
+        }
+        for repo_url in repo_urls {
+            info!("gitlab dry run: - {repo_url}");
+        }
+    }
+}
+
+/// Create a pool of GitLab API clients for each of the instances with
+/// repositories that have valid tokens configured. Instances without tokens
+/// are skipped (or use an unauthenticated client, when allowed), unless tokens
+/// are required, in which case an error will be returned. The pools of the
+/// different instances are created concurrently.
+async fn create_instances_pools(
+    repos_by_instance: &BTreeMap<String, Vec<String>>,
+    instance_configs: &[GitlabInstanceConfig],
+    base_url_aliases: &BTreeMap<String, String>,
+    client_options: &ClientOptions,
+    require_tokens: bool,
+) -> Result<BTreeMap<String, Pool<DynGL>>> {
+    let action = if client_options.allow_unauthenticated {
+        "collected without authentication"
+    } else {
+        "skipped"
+    };
+    let results = future::join_all(repos_by_instance.iter().map(|(base_url, repo_urls)| async move {
+        let api_url = resolve_api_url(base_url, base_url_aliases);
+        if api_url != base_url {
+            debug!("using {api_url} to access the api of gitlab instance {base_url}");
+        }
+        if let Some(config) = find_config_for_instance(base_url, instance_configs) {
+            if let Some(gl_pool) = create_gitlab_pool(api_url, &config.tokens, client_options).await {
+                return (base_url, Some(gl_pool), true);
+            }
+            warn!(
+                "no valid gitlab token for instance: {base_url} ({} repositories will be {action})",
+                repo_urls.len()
+            );
+        } else {
+            warn!(
+                "no gitlab token configured for instance: {base_url} ({} repositories will be {action})",
+                repo_urls.len()
+            );
+        }
+
+        // Public repositories data can still be collected without a token
+        if client_options.allow_unauthenticated
+            && let Some(gl_pool) = create_unauthenticated_gitlab_pool(api_url, client_options).await
+        {
+            warn!(
+                "using unauthenticated requests for gitlab instance: {base_url} (only public \
+                repositories data will be collected, subject to stricter rate limits)"
+            );
+            return (base_url, Some(gl_pool), false);
+        }
+        (base_url, None, false)
+    }))
+    .await;
+
+    let mut instance_pools: BTreeMap<String, Pool<DynGL>> = BTreeMap::new();
+    let mut instances_without_tokens = vec![];
+    for (base_url, gl_pool, has_valid_tokens) in results {
+        if !has_valid_tokens {
+            instances_without_tokens.push(base_url.as_str());
+        }
+        if let Some(gl_pool) = gl_pool {
+            instance_pools.insert(base_url.clone(), gl_pool);
+        }
+    }
+
+    if require_tokens && !instances_without_tokens.is_empty() {
+        bail!(
+            "no valid gitlab token available for instances: {}",
+            instances_without_tokens.join(", ")
+        );
+    }
+
+    Ok(instance_pools)
+}
+
+/// Collect the normalized urls of the GitLab repositories in the landscape,
+/// grouping them by instance (base url). Each repository appears only once,
+/// even if it's listed in multiple items or using different spellings. Only
+/// repositories hosted on gitlab.com or on one of the instances configured
+/// are considered GitLab repositories.
+fn group_repositories_by_instance(
+    landscape_data: &LandscapeData,
+    instance_configs: &[GitlabInstanceConfig],
+) -> BTreeMap<String, Vec<String>> {
+    let is_gitlab_instance = |base_url: &str| {
+        base_url.eq_ignore_ascii_case(DEFAULT_GITLAB_URL)
+            || find_config_for_instance(base_url, instance_configs).is_some()
+    };
+
+    let mut repos_by_instance: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for item in &landscape_data.items {
+        if let Some(repositories) = &item.repositories {
+            for repo in repositories {
+                if let Some(url) = normalize_gitlab_url(&repo.url)
+                    && let Some(base_url) = get_instance_url(&url, instance_configs)
+                {
+                    if !is_gitlab_instance(&base_url) {
+                        debug!(
+                            "skipping repository not hosted on a known gitlab instance: {}",
+                            repo.url
+                        );
+                        continue;
+                    }
+                    repos_by_instance.entry(base_url).or_default().push(url);
+                }
+            }
+        }
+    }
+
+    // Remove duplicates
+    for urls in repos_by_instance.values_mut() {
+        urls.sort_unstable();
+        urls.dedup();
+    }
+
+    repos_by_instance
+}
+
+/// Mismatches between the GitLab instances configured and the instances the
+/// repositories in the landscape belong to.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct InstancesReconciliation {
+    /// Instances configured that have no repositories in the landscape.
+    unused_configs: Vec<String>,
+    /// Instances with repositories in the landscape that are not configured,
+    /// along with the number of repositories (no token will be used for them).
+    unconfigured_instances: BTreeMap<String, usize>,
+}
+
+impl InstancesReconciliation {
+    /// Log a warning for each of the mismatches found.
+    fn log(&self) {
+        for base_url in &self.unused_configs {
+            warn!("gitlab instance {base_url} is configured but has no repositories in the landscape");
+        }
+        for (base_url, repos_count) in &self.unconfigured_instances {
+            warn!(
+                "gitlab instance {base_url} has {repos_count} repositories in the landscape but is not \
+                 configured (no token will be used for them)"
+            );
+        }
+    }
+}
+
+/// Reconcile the GitLab instances configured with the instances the
+/// repositories in the landscape belong to, so that mismatches in their urls
+/// can be reported. As any repository url could belong to a GitLab instance,
+/// only the ones hosted on gitlab.com or on hosts that include `gitlab` in
+/// their name are expected to have a configuration.
+fn reconcile_instance_configs(
+    landscape_data: &LandscapeData,
+    instance_configs: &[GitlabInstanceConfig],
+) -> InstancesReconciliation {
+    let mut repos_by_instance: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+    for repo in landscape_data.items.iter().flat_map(|item| item.repositories.iter().flatten()) {
+        if let Some(url) = normalize_gitlab_url(&repo.url)
+            && let Some(base_url) = get_instance_url(&url, instance_configs)
+        {
+            repos_by_instance.entry(base_url).or_default().insert(url);
+        }
+    }
+
+    let looks_like_gitlab = |base_url: &str| {
+        base_url.eq_ignore_ascii_case(DEFAULT_GITLAB_URL)
+            || Url::parse(base_url)
+                .is_ok_and(|url| url.host_str().is_some_and(|host| host.to_lowercase().contains("gitlab")))
+    };
+    InstancesReconciliation {
+        unused_configs: instance_configs
+            .iter()
+            .filter(|config| {
+                !repos_by_instance.keys().any(|base_url| {
+                    find_config_for_instance(base_url, std::slice::from_ref(*config)).is_some()
+                })
+            })
+            .map(|config| config.base_url.clone())
+            .collect(),
+        unconfigured_instances: repos_by_instance
+            .iter()
+            .filter(|(base_url, _)| {
+                find_config_for_instance(base_url, instance_configs).is_none() && looks_like_gitlab(base_url)
+            })
+            .map(|(base_url, urls)| (base_url.clone(), urls.len()))
+            .collect(),
+    }
+}
+
+/// Return the url of the GitLab instance the repository url provided belongs
+/// to. Instances served under a relative url root (i.e.
+/// `https://example.com/gitlab`) are only detected when they are configured
+/// using their url including it. Their repositories must be referenced by
+/// their path, as the urls referencing projects by id are not told apart from
+/// the instance url once normalized.
+fn get_instance_url(url: &str, instance_configs: &[GitlabInstanceConfig]) -> Option<String> {
+    instance_configs
+        .iter()
+        .map(|config| config.base_url.trim_end_matches('/'))
+        .filter(|base_url| {
+            has_relative_url_root(base_url) && parse_instance_url(url, base_url, parse_gitlab_url).is_some()
+        })
+        .max_by_key(|base_url| base_url.len())
+        .map(ToString::to_string)
+        .or_else(|| parse_gitlab_url(url).map(|(base_url, _)| base_url))
+}
+
+/// Check if the GitLab instance url provided includes a relative url root.
+fn has_relative_url_root(base_url: &str) -> bool {
+    Url::parse(base_url).is_ok_and(|url| !url.path().trim_matches('/').is_empty())
+}
+
+/// Parse the GitLab url provided, belonging to the instance given, using the
+/// parser provided. The relative url root of the instance (if any) is not part
+/// of the paths of its projects and groups, so it's removed before parsing the
+/// url. The instance url is returned as the base url.
+fn parse_instance_url(
+    url: &str,
+    base_url: &str,
+    parse: fn(&str) -> Option<(String, String)>,
+) -> Option<(String, String)> {
+    let base_url = base_url.trim_end_matches('/');
+    let Some(instance_url) = Url::parse(base_url).ok().filter(|_| has_relative_url_root(base_url)) else {
+        return parse(url);
+    };
+    if !url.get(..base_url.len())?.eq_ignore_ascii_case(base_url) {
+        return None;
+    }
+    let path = url[base_url.len()..].strip_prefix('/')?;
+    let (_, path) = parse(&format!("{}/{path}", &instance_url[..url::Position::BeforePath]))?;
+    Some((base_url.to_string(), path))
+}
+
+/// Remove the repositories matching any of the exclude patterns provided,
+/// dropping the instances left without repositories.
+fn exclude_repositories(repos_by_instance: &mut BTreeMap<String, Vec<String>>, exclude_patterns: &[Regex]) {
+    if exclude_patterns.is_empty() {
+        return;
+    }
+    for urls in repos_by_instance.values_mut() {
+        urls.retain(|url| {
+            let excluded = exclude_patterns.iter().any(|pattern| pattern.is_match(url));
+            if excluded {
+                debug!("excluding repository from collection: {url}");
+            }
+            !excluded
+        });
+    }
+    repos_by_instance.retain(|_, urls| !urls.is_empty());
+}
+
+/// Remove the repositories of the items that don't match the filter provided,
+/// dropping the instances left without repositories. Repositories shared with
+/// any of the items matching the filter are kept. The urls of the repositories
+/// removed are returned.
+fn filter_repositories(
+    repos_by_instance: &mut BTreeMap<String, Vec<String>>,
+    landscape_data: &LandscapeData,
+    filter: &GitLabItemsFilter,
+) -> Result<Vec<String>> {
+    if filter.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Collect the repositories of the items matching the filter
+    let items_patterns: Vec<Regex> = filter
+        .items
+        .iter()
+        .map(|pattern| glob_to_regex(pattern).with_context(|| format!("invalid item pattern: {pattern}")))
+        .collect::<Result<_>>()?;
+    let matches = |values: &[String], value: &str| {
+        values.is_empty() || values.iter().any(|v| v.trim().eq_ignore_ascii_case(value))
+    };
+    let selected_urls: HashSet<String> = landscape_data
+        .items
+        .iter()
+        .filter(|item| {
+            matches(&filter.categories, &item.category)
+                && matches(&filter.subcategories, &item.subcategory)
+                && (items_patterns.is_empty() || items_patterns.iter().any(|p| p.is_match(&item.name)))
+        })
+        .flat_map(|item| item.repositories.iter().flatten())
+        .filter_map(|repo| normalize_gitlab_url(&repo.url))
+        .collect();
+
+    // Remove the rest of the repositories
+    let mut filtered_out = vec![];
+    for urls in repos_by_instance.values_mut() {
+        urls.retain(|url| {
+            if selected_urls.contains(url) {
+                return true;
+            }
+            filtered_out.push(url.clone());
+            false
+        });
+    }
+    repos_by_instance.retain(|_, urls| !urls.is_empty());
+    debug!("{} repositories filtered out from collection", filtered_out.len());
+
+    Ok(filtered_out)
+}
+
+/// Options set explicitly for a GitLab repository in the landscape.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct RepositoryOptions {
+    /// Branch used to collect the commits information.
+    branch: Option<String>,
+    /// Whether the repository url points to a group, whose projects data
+    /// should be aggregated.
+    group: bool,
+}
+
+/// Collect the options set explicitly for the GitLab repositories in the
+/// landscape, indexed by the repository normalized url. When a repository
+/// appears multiple times with different branches, the first one is used.
+fn collect_repositories_options(landscape_data: &LandscapeData) -> HashMap<String, RepositoryOptions> {
+    let mut repos_options: HashMap<String, RepositoryOptions> = HashMap::new();
+    for item in &landscape_data.items {
+        for repo in item.repositories.iter().flatten() {
+            let group = repo.gitlab_group.unwrap_or_default();
+            if (repo.branch.is_some() || group)
+                && let Some(url) = normalize_gitlab_url(&repo.url)
+            {
+                let options = repos_options.entry(url).or_default();
+                if options.branch.is_none() {
+                    options.branch.clone_from(&repo.branch);
+                }
+                options.group |= group;
+            }
+        }
+    }
+    repos_options
+}
+
+/// Context shared by the tasks collecting the data of the repositories of a
+/// GitLab instance.
+#[derive(Clone, Copy)]
+struct InstanceCollectionContext<'a> {
+    /// Pool of clients of the instance (none when no tokens are available).
+    gl_pool: Option<&'a Pool<DynGL>>,
+    base_url: &'a str,
+    repos_options: &'a HashMap<String, RepositoryOptions>,
+    cached_data: Option<&'a GitData>,
+    cache_ttl: chrono::Duration,
+    /// Cached data generated before this instant is refreshed.
+    refresh_before: Option<DateTime<Utc>>,
+    /// Repositories that failed permanently recently are skipped.
+    failed_fetches: &'a FailedFetches,
+    stats: &'a Mutex<GitlabCollectionStats>,
+    latencies: &'a Mutex<FetchLatencies>,
+    progress: &'a ProgressBar,
+    /// Maximum random delay before the first request of each concurrent task.
+    startup_jitter: Duration,
+    /// Collector used to get the data of the mirrors of GitHub repositories.
+    github: Option<&'a DynGitHubCollector>,
+    collection_options: &'a GitlabCollectionOptions,
+    circuit_breaker: CircuitBreakerConfig,
+}
+
+/// Return a stream that collects data for the repositories provided, all of
+/// them belonging to the instance in the context provided, yielding the result
+/// of each repository as soon as it's available (in no particular order). Up to
+/// one repository per client in the instance pool is processed concurrently.
+#[allow(clippy::too_many_lines)]
+fn stream_instance_data<'a>(
+    ctx: InstanceCollectionContext<'a>,
+    urls: &'a [String],
+) -> impl Stream<Item = (String, Result<RepositoryGitData>)> + 'a {
+    let InstanceCollectionContext {
+        gl_pool,
+        base_url,
+        repos_options,
+        cached_data,
+        cache_ttl,
+        refresh_before,
+        failed_fetches,
+        stats,
+        latencies,
+        progress,
+        startup_jitter,
+        github,
+        collection_options,
+        circuit_breaker,
+    } = ctx;
+    let concurrency = gl_pool.map_or(1, |gl_pool| gl_pool.status().size.max(1));
+    let circuit_breaker = Arc::new(CircuitBreaker::new(circuit_breaker));
+    let update_stats = move |f: &dyn Fn(&mut GitlabCollectionStats)| {
+        f(&mut stats.lock().expect("stats lock not to be poisoned"));
+    };
+    update_stats(&|stats| stats.total += urls.len());
+
+    stream::iter(urls.iter().enumerate())
+        .map(move |(i, url)| {
+            let circuit_breaker = Arc::clone(&circuit_breaker);
+            // The events logged while processing the repository are recorded
+            // in its own span, so that they can be filtered by repository
+            let span = info_span!("gitlab_repository", url = %url, instance = %base_url);
+            async move {
+                let url = url.clone();
+                let options = repos_options.get(&url);
+                let branch = options.and_then(|options| options.branch.as_deref());
+
+                // Cached data can only be used if it was collected from the
+                // branch currently configured for the repository
+                let get_cached_repo = || {
+                    cached_data
+                        .and_then(|cache| cache.get(&url))
+                        .filter(|repo| repo.branch.as_deref() == branch)
+                };
+
+                // Use cached data when available if it hasn't expired yet
+                // (and it wasn't generated before the refresh instant)
+                if let Some(cached_repo) = get_cached_repo().filter(|repo| {
+                    repo.generated_at + cache_ttl > Utc::now()
+                        && refresh_before.is_none_or(|refresh_before| repo.generated_at >= refresh_before)
+                }) {
+                    debug!("using cached data for {}", url);
+                    update_stats(&|stats| stats.cache_hits += 1);
+                    (url, Ok(from_cache(cached_repo)))
+                }
+                // Skip repositories that failed permanently recently
+                else if let Some(failed_fetch) = failed_fetches.get(&url).filter(|f| !f.has_expired()) {
+                    debug!("skipping {} (failed with status {})", url, failed_fetch.status);
+                    update_stats(&|stats| stats.skipped_failed_recently += 1);
+                    (
+                        url,
+                        Err(format_err!(
+                            "skipped: failed recently with status {}",
+                            failed_fetch.status
+                        )),
+                    )
+                }
+                // Skip the remaining repositories of instances that keep failing
+                else if gl_pool.is_some() && !circuit_breaker.allow_request() {
+                    debug!(
+                        "skipping {} (circuit breaker open for instance {})",
+                        url, base_url
+                    );
+                    update_stats(&|stats| stats.skipped_circuit_open += 1);
+                    let err = format_err!("skipped: too many consecutive failures on instance {base_url}");
+                    (url, Err(err))
+                }
+                // Otherwise we pull it from GitLab if a pool exists for this instance
+                else if let Some(gl_pool) = gl_pool {
+                    debug!("fetching fresh data for {}", url);
+                    if i < concurrency {
+                        tokio::time::sleep(random_delay(startup_jitter)).await;
+                    }
+                    let mut gl = match get_client(gl_pool).await {
+                        Ok(gl) => gl,
+                        Err(err) => {
+                            warn!("error collecting {url} from instance {base_url}: {err}");
+                            update_stats(&|stats| stats.record_failure(&err));
+                            return (url, Err(err));
+                        }
+                    };
+                    let start = Instant::now();
+                    let mut rate_limited_retries = 0;
+                    let result = loop {
+                        let result = if options.is_some_and(|options| options.group) {
+                            collect_group_data(&gl, base_url, &url, collection_options).await
+                        } else if collection_options.metadata_only_refresh
+                            && let Some(cached_repo) = get_cached_repo()
+                        {
+                            collect_repository_metadata(&gl, base_url, &url, cached_repo).await
+                        } else {
+                            collect_repository_data(&gl, base_url, &url, branch, github, collection_options)
+                                .await
+                        };
+
+                        // When the token of the client is no longer usable, the
+                        // client is removed from the pool and we try again with a
+                        // different one. The last client is never removed, as the
+                        // tasks waiting for a client would never get one (no await
+                        // point between checking the size and taking the client)
+                        match result {
+                            Err(err) if is_token_error(&err) && gl_pool.status().size > 1 => {
+                                warn!("gitlab token no longer usable, removing it from the pool: {err}");
+                                drop(Object::take(gl));
+                                gl = match get_client(gl_pool).await {
+                                    Ok(gl) => gl,
+                                    Err(err) => break Err(err),
+                                };
+                            }
+                            // When the token of the client has been rate limited, we
+                            // try again with a different one (once per client). The
+                            // rate limited client is returned to the pool after the
+                            // delay GitLab asked for, so the token can be used again
+                            Err(err)
+                                if is_rate_limit_error(&err)
+                                    && rate_limited_retries + 1 < gl_pool.status().size =>
+                            {
+                                warn!("gitlab token rate limited, trying again with a different one: {err}");
+                                rate_limited_retries += 1;
+                                let next_gl = match get_client(gl_pool).await {
+                                    Ok(gl) => gl,
+                                    Err(err) => break Err(err),
+                                };
+                                let rate_limited_gl = std::mem::replace(&mut gl, next_gl);
+                                let delay = retry_after(&err).unwrap_or_default().min(MAX_RETRY_AFTER);
+                                tokio::spawn(async move {
+                                    tokio::time::sleep(delay).await;
+                                    drop(rate_limited_gl);
+                                });
+                            }
+                            result => break result,
+                        }
+                    };
+                    // Fetches not sent as the request budget was exhausted
+                    // would skew the latency figures, so they are ignored
+                    if !matches!(&result, Err(err) if underlying_error(err).is::<RequestBudgetExhausted>()) {
+                        let mut latencies = latencies.lock().expect("latencies lock not to be poisoned");
+                        latencies.record(base_url, start.elapsed());
+                    }
+                    match &result {
+                        Err(err) if underlying_error(err).is::<RequestBudgetExhausted>() => {}
+                        Err(err) if is_retryable(err) => {
+                            if circuit_breaker.record_failure() {
+                                warn!(
+                                    "too many consecutive failures collecting from instance {base_url}, \
+                                     skipping its remaining repositories"
+                                );
+                            }
+                        }
+                        _ => circuit_breaker.record_success(),
+                    }
+                    match &result {
+                        Ok(_) => update_stats(&|stats| stats.fresh_fetches += 1),
+                        Err(err) if underlying_error(err).is::<RequestBudgetExhausted>() => {
+                            // Fall back to the cached data, even if it has expired
+                            if let Some(cached_repo) = get_cached_repo() {
+                                debug!("request budget exhausted, using expired cached data for {}", url);
+                                update_stats(&|stats| stats.expired_cache_hits += 1);
+                                return (url, Ok(from_cache(cached_repo)));
+                            }
+                            update_stats(&|stats| stats.skipped_budget_exhausted += 1);
+                        }
+                        Err(err) => {
+                            log_failure(&url, err);
+                            update_stats(&|stats| stats.record_failure(err));
+                        }
+                    }
+                    (url, result)
+                } else {
+                    update_stats(&|stats| stats.skipped_no_token += 1);
+                    let err = GitlabError::Auth(format_err!("no token configured for instance"));
+                    (url, Err(err.into()))
+                }
+            }
+            .instrument(span)
+        })
+        .buffer_unordered(concurrency)
+        .inspect(move |_| progress.inc(1))
+}
+
+/// Get a client from the GitLab clients pool provided. An error is returned
+/// when the pool has been closed or has no clients left (i.e. they have been
+/// taken out of it), as waiting for one to be available would never finish.
+async fn get_client(gl_pool: &Pool<DynGL>) -> Result<Object<DynGL>> {
+    if gl_pool.status().size == 0 {
+        bail!("no gitlab clients available for instance");
+    }
+    gl_pool.get().await.map_err(|err| format_err!("error getting gitlab client: {err}"))
+}
+
+/// Return a random delay of up to the maximum provided.
+fn random_delay(max: Duration) -> Duration {
+    let millis = rand::thread_rng().gen_range(0..=max.as_millis());
+    Duration::from_millis(u64::try_from(millis).unwrap_or_default())
+}
+
+/// Log the failure collecting the data of the repository provided, warning
+/// about the ones that may require some action (i.e. updating the tokens).
+fn log_failure(url: &str, err: &anyhow::Error) {
+    match err.downcast_ref::<GitlabError>() {
+        Some(GitlabError::Auth(_)) => warn!("error collecting {url} (check the gitlab tokens): {err}"),
+        Some(GitlabError::RateLimited(_)) => warn!("error collecting {url} (rate limited): {err}"),
+        _ => debug!("error collecting {url}: {err}"),
+    }
+}
+
+/// Summary of the outcome of collecting the GitLab repositories data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+struct GitlabCollectionStats {
+    /// Number of repositories processed.
+    total: usize,
+    /// Repositories whose data was taken from the cache.
+    cache_hits: usize,
+    /// Repositories whose expired cached data was used as the request budget
+    /// was exhausted.
+    expired_cache_hits: usize,
+    /// Repositories whose data was fetched from GitLab successfully.
+    fresh_fetches: usize,
+    /// Repositories skipped as no valid token was available for the instance.
+    skipped_no_token: usize,
+    /// Repositories skipped as they failed permanently recently.
+    skipped_failed_recently: usize,
+    /// Repositories skipped as the request budget was exhausted (and no cached
+    /// data was available).
+    skipped_budget_exhausted: usize,
+    /// Repositories skipped as the circuit breaker of their instance was open.
+    skipped_circuit_open: usize,
+    /// Repositories that could not be fetched, grouped by error kind.
+    failures: BTreeMap<String, usize>,
+    /// Names of the items with GitLab repositories for which no data was
+    /// collected at all (i.e. all of them failed or were skipped).
+    items_without_data: Vec<String>,
+}
+
+impl GitlabCollectionStats {
+    /// Record a failure fetching a repository's data.
+    fn record_failure(&mut self, err: &anyhow::Error) {
+        *self.failures.entry(error_kind(err)).or_default() += 1;
+    }
+
+    /// Log a summary of the collection, warning when the request budget was
+    /// exhausted or when some items were left without data.
+    fn log_summary(&self) {
+        info!("gitlab data collection summary: {self}");
+        if self.expired_cache_hits > 0 || self.skipped_budget_exhausted > 0 {
+            warn!(
+                "gitlab request budget exhausted: {} repositories used expired cached data and {} were \
+                 skipped",
+                self.expired_cache_hits, self.skipped_budget_exhausted
+            );
+        }
+        if !self.items_without_data.is_empty() {
+            warn!(
+                "no gitlab data collected for {} items: {}",
+                self.items_without_data.len(),
+                self.items_without_data.join(", ")
+            );
+        }
+    }
+}
+
+impl std::fmt::Display for GitlabCollectionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} repositories ({} cached, {} expired cached, {} fetched, {} skipped without token, {} \
+             skipped after failing recently, {} skipped after exhausting budget, {} skipped after \
+             instance kept failing, {} failed {:?})",
+            self.total,
+            self.cache_hits,
+            self.expired_cache_hits,
+            self.fresh_fetches,
+            self.skipped_no_token,
+            self.skipped_failed_recently,
+            self.skipped_budget_exhausted,
+            self.skipped_circuit_open,
+            self.failures.values().sum::<usize>(),
+            self.failures,
+        )
+    }
+}
+
+/// Durations of the repositories data fetches, grouped by GitLab instance.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FetchLatencies(BTreeMap<String, Vec<Duration>>);
+
+impl FetchLatencies {
+    /// Record the duration of a fetch from the instance provided.
+    fn record(&mut self, base_url: &str, duration: Duration) {
+        self.0.entry(base_url.to_string()).or_default().push(duration);
+    }
+
+    /// Return the latency summary of each of the instances.
+    fn summary(&self) -> BTreeMap<String, LatencySummary> {
+        self.0
+            .iter()
+            .filter_map(|(base_url, durations)| Some((base_url.clone(), LatencySummary::new(durations)?)))
+            .collect()
+    }
+
+    /// Log the latency summary of each of the instances.
+    fn log_summary(&self) {
+        for (base_url, summary) in self.summary() {
+            info!("gitlab fetch latency for instance {base_url}: {summary}");
+        }
+    }
+}
+
+/// Summary of the latency of the fetches from a GitLab instance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LatencySummary {
+    count: usize,
+    average: Duration,
+    p95: Duration,
+}
+
+impl LatencySummary {
+    /// Create a new latency summary from the durations provided (if any). The
+    /// p95 is calculated using the nearest-rank method.
+    fn new(durations: &[Duration]) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        let mut durations = durations.to_vec();
+        durations.sort();
+
+        let count = durations.len();
+        let total: Duration = durations.iter().sum();
+        Some(Self {
+            count,
+            average: total / u32::try_from(count).unwrap_or(u32::MAX),
+            p95: durations[(count * 95).div_ceil(100) - 1],
+        })
+    }
+}
+
+impl std::fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} fetches (average {}ms, p95 {}ms)",
+            self.count,
+            self.average.as_millis(),
+            self.p95.as_millis()
+        )
+    }
+}
+
+/// Limit the repositories to collect to the maximum provided, keeping the first
+/// ones in alphabetical order and dropping the instances left without
+/// repositories. The urls of the repositories removed are returned.
+fn limit_repositories(
+    repos_by_instance: &mut BTreeMap<String, Vec<String>>,
+    max_repos: usize,
+) -> Vec<String> {
+    let mut all_urls: Vec<&String> = repos_by_instance.values().flatten().collect();
+    if all_urls.len() <= max_repos {
+        return vec![];
+    }
+    all_urls.sort();
+    let selected_urls: HashSet<String> = all_urls.into_iter().take(max_repos).cloned().collect();
+
+    let mut skipped = vec![];
+    for urls in repos_by_instance.values_mut() {
+        urls.retain(|url| {
+            if selected_urls.contains(url) {
+                return true;
+            }
+            skipped.push(url.clone());
+            false
+        });
+    }
+    repos_by_instance.retain(|_, urls| !urls.is_empty());
+    info!(
+        "gitlab collection limited to {max_repos} repositories ({} skipped)",
+        skipped.len()
+    );
+
+    skipped
+}
+
+/// Split the results of collecting the repositories data into the data
+/// collected successfully and the failed fetches that should be remembered.
+/// Repositories that failed permanently are recorded with the current time,
+/// whereas the ones that were skipped keep their previous failed fetch entry.
+fn process_results(
+    results: Vec<(String, Result<RepositoryGitData>)>,
+    cached_failed_fetches: &FailedFetches,
+) -> (GitData, FailedFetches) {
+    let mut gitlab_data = GitData::new();
+    let mut failed_fetches = FailedFetches::new();
+
+    for (url, result) in results {
+        match result {
+            Ok(repo_data) => {
+                gitlab_data.insert(url, repo_data);
+            }
+            Err(err) => {
+                if let Some(status) = permanent_error_status(&err) {
+                    let failed_fetch = FailedFetch {
+                        failed_at: Utc::now(),
+                        status,
+                    };
+                    failed_fetches.insert(url, failed_fetch);
+                } else if let Some(failed_fetch) =
+                    cached_failed_fetches.get(&url).filter(|f| !f.has_expired())
+                {
+                    failed_fetches.insert(url, failed_fetch.clone());
+                }
+            }
+        }
+    }
+
+    (gitlab_data, failed_fetches)
+}
+
+/// Return the names of the items that have some of the GitLab repositories
+/// collected, but for which no data is available for any of them (sorted and
+/// without duplicates).
+fn get_items_without_data(
+    landscape_data: &LandscapeData,
+    collected_urls: &HashSet<&str>,
+    gitlab_data: &GitData,
+) -> Vec<String> {
+    let mut items_without_data: Vec<String> = landscape_data
+        .items
+        .iter()
+        .filter_map(|item| {
+            let urls: Vec<String> = item
+                .repositories
+                .iter()
+                .flatten()
+                .filter_map(|repo| normalize_gitlab_url(&repo.url))
+                .filter(|url| collected_urls.contains(url.as_str()))
+                .collect();
+            if urls.is_empty() || urls.iter().any(|url| gitlab_data.contains_key(url)) {
+                return None;
+            }
+            Some(item.name.clone())
+        })
+        .collect();
+    items_without_data.sort();
+    items_without_data.dedup();
+    items_without_data
+}
+
+/// Repository data record written to the NDJSON output.
+#[derive(Debug, Serialize)]
+struct NdjsonRecord<'a> {
+    repository_url: &'a str,
+    #[serde(flatten)]
+    data: &'a RepositoryGitData,
+}
+
+/// Write the GitLab data provided in NDJSON format to the output file
+/// provided, or to stdout when it is `-`.
+fn write_ndjson_output(output: &Path, gitlab_data: &GitData) -> Result<()> {
+    if output == Path::new("-") {
+        return write_ndjson(&mut io::stdout().lock(), gitlab_data);
+    }
+    let file = fs::File::create(output)
+        .with_context(|| format!("error creating gitlab ndjson output file {}", output.display()))?;
+    let mut writer = BufWriter::new(file);
+    write_ndjson(&mut writer, gitlab_data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the GitLab data provided to the writer given in NDJSON format, one
+/// repository per line.
+fn write_ndjson(writer: &mut impl Write, gitlab_data: &GitData) -> Result<()> {
+    for (repository_url, data) in gitlab_data {
+        serde_json::to_writer(&mut *writer, &NdjsonRecord { repository_url, data })?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Details about a repository whose data could not be collected from GitLab.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct CollectionFailure {
+    url: String,
+    kind: String,
+    message: String,
+}
+
+/// Return the repositories that could not be collected from the results
+/// provided, along with the kind of error and its message. Failures are
+/// sorted by url, as the results order depends on when each repository
+/// finished processing.
+fn get_collection_failures(results: &[(String, Result<RepositoryGitData>)]) -> Vec<CollectionFailure> {
+    let mut failures: Vec<CollectionFailure> = results
+        .iter()
+        .filter_map(|(url, result)| {
+            let err = result.as_ref().err()?;
+            Some(CollectionFailure {
+                url: url.clone(),
+                kind: error_kind(err),
+                message: format!("{err:#}"),
+            })
+        })
+        .collect();
+    failures.sort_by(|a, b| a.url.cmp(&b.url));
+    failures
+}
+
+/// Get the labels used to count the good first issues from the landscape
+/// settings, falling back to the default ones when not provided.
+fn get_good_first_issues_labels(settings: &LandscapeSettings) -> Vec<String> {
+    settings
+        .collection
+        .as_ref()
+        .and_then(|collection| collection.gitlab.as_ref())
+        .and_then(|gitlab| gitlab.good_first_issues_labels.clone())
+        .unwrap_or_else(|| ClientOptions::default().good_first_issues_labels)
+}
+
+/// Get the labels used to count the help wanted issues from the landscape
+/// settings, falling back to the default ones when not provided.
+fn get_help_wanted_labels(settings: &LandscapeSettings) -> Vec<String> {
+    settings
+        .collection
+        .as_ref()
+        .and_then(|collection| collection.gitlab.as_ref())
+        .and_then(|gitlab| gitlab.help_wanted_labels.clone())
+        .unwrap_or_else(|| ClientOptions::default().help_wanted_labels)
+}
+
+/// Get the filter used to skip the pre-releases when selecting the latest
+/// release from the landscape settings (if enabled). The default tags pattern
+/// is used when none is provided, and an empty one disables it.
+fn get_prereleases_filter(settings: &LandscapeSettings) -> Result<Option<PrereleasesFilter>> {
+    let Some(gitlab) = settings
+        .collection
+        .as_ref()
+        .and_then(|collection| collection.gitlab.as_ref())
+        .filter(|gitlab| gitlab.skip_prereleases)
+    else {
+        return Ok(None);
+    };
+    let pattern = gitlab.prerelease_tags_pattern.as_deref().unwrap_or(DEFAULT_PRERELEASE_TAGS_PATTERN);
+    let tags_pattern = if pattern.trim().is_empty() {
+        None
+    } else {
+        Some(Regex::new(pattern).with_context(|| format!("invalid prerelease tags pattern: {pattern}"))?)
+    };
+    Ok(Some(PrereleasesFilter { tags_pattern }))
+}
+
+/// Get the patterns of the repositories urls to exclude from the collection
+/// configured in the landscape settings. Patterns are globs where `*` matches
+/// any sequence of characters and `?` matches any single character. They are
+/// matched against the whole repository url, ignoring case.
+fn get_exclude_patterns(settings: &LandscapeSettings) -> Result<Vec<Regex>> {
+    let Some(patterns) = settings.collection.as_ref().and_then(|collection| collection.exclude.as_ref())
+    else {
+        return Ok(vec![]);
+    };
+    patterns
+        .iter()
+        .map(|pattern| {
+            glob_to_regex(pattern).with_context(|| format!("invalid collection exclude pattern: {pattern}"))
+        })
+        .collect()
+}
+
+/// Convert the glob pattern provided into a regular expression that matches
+/// whole values, ignoring case. In globs, `*` matches any sequence of
+/// characters and `?` matches any single character.
+fn glob_to_regex(pattern: &str) -> Result<Regex> {
+    let regex = regex::escape(pattern.trim()).replace(r"\*", ".*").replace(r"\?", ".");
+    Ok(Regex::new(&format!("(?i)^{regex}$"))?)
+}
+
+/// Check if the data of the mirrors of GitHub repositories should be collected
+/// from GitHub, as configured in the landscape settings.
+fn follow_github_mirrors(settings: &LandscapeSettings) -> bool {
+    settings
+        .collection
+        .as_ref()
+        .and_then(|collection| collection.gitlab.as_ref())
+        .is_some_and(|gitlab| gitlab.follow_github_mirrors)
+}
+
+/// Options used to select the data collected from each GitLab project. The
+/// requests needed to collect the data disabled are not made, leaving the
+/// corresponding fields empty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::struct_excessive_bools)]
+struct GitlabCollectionOptions {
+    commits_last_year: bool,
+    /// Collect the data of the mirrors of GitHub repositories from GitHub,
+    /// which requires the project import url.
+    github_mirrors: bool,
+    good_first_issues: bool,
+    help_wanted_issues: bool,
+    languages: bool,
+    latest_commit: bool,
+    latest_pipeline_status: bool,
+    latest_release: bool,
+    license: bool,
+    /// Refresh only the metadata of the repositories with expired cached data.
+    metadata_only_refresh: bool,
+    open_issues: bool,
+    open_merge_requests: bool,
+    project_statistics: bool,
+}
+
+impl Default for GitlabCollectionOptions {
+    fn default() -> Self {
+        Self {
+            commits_last_year: true,
+            github_mirrors: false,
+            good_first_issues: true,
+            help_wanted_issues: true,
+            languages: true,
+            latest_commit: true,
+            latest_pipeline_status: true,
+            latest_release: true,
+            license: true,
+            metadata_only_refresh: false,
+            open_issues: true,
+            open_merge_requests: true,
+            project_statistics: true,
+        }
+    }
+}
+
+impl GitlabCollectionOptions {
+    /// Create a new collection options instance from the landscape settings,
+    /// enabling the data not configured explicitly. The lightweight profile
+    /// disables all the data based on statistics.
+    fn new(settings: &LandscapeSettings) -> Self {
+        let Some(gitlab) = settings.collection.as_ref().and_then(|collection| collection.gitlab.as_ref())
+        else {
+            return Self::default();
+        };
+        let options = Self {
+            commits_last_year: gitlab.commits_last_year.unwrap_or(true),
+            github_mirrors: gitlab.follow_github_mirrors,
+            good_first_issues: gitlab.good_first_issues.unwrap_or(true),
+            help_wanted_issues: gitlab.help_wanted_issues.unwrap_or(true),
+            languages: gitlab.languages.unwrap_or(true),
+            latest_commit: gitlab.latest_commit.unwrap_or(true),
+            latest_pipeline_status: gitlab.latest_pipeline_status.unwrap_or(true),
+            latest_release: gitlab.latest_release.unwrap_or(true),
+            license: gitlab.license.unwrap_or(true),
+            metadata_only_refresh: false,
+            open_issues: gitlab.open_issues.unwrap_or(true),
+            open_merge_requests: gitlab.open_merge_requests.unwrap_or(true),
+            project_statistics: true,
+        };
+        if gitlab.profile == Some(GitLabCollectionProfile::Lightweight) {
+            return Self {
+                good_first_issues: false,
+                help_wanted_issues: false,
+                open_issues: false,
+                project_statistics: false,
+                ..options
+            };
+        }
+        options
+    }
+}
+
+/// Check if the weekly commit activity of the repositories should be
+/// collected, as configured in the landscape settings.
+fn collect_commit_activity(settings: &LandscapeSettings) -> bool {
+    settings
+        .collection
+        .as_ref()
+        .and_then(|collection| collection.gitlab.as_ref())
+        .is_some_and(|gitlab| gitlab.commit_activity)
+}
+
+/// Replace the api base url in the urls of the repository data provided with
+/// the base url used in the landscape data.
+fn replace_base_url(repo: &mut RepositoryGitData, api_url: &str, base_url: &str) {
+    let replace = |url: &mut String| {
+        if url.get(..api_url.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(api_url)) {
+            *url = format!("{base_url}{}", &url[api_url.len()..]);
+        }
+    };
+
+    replace(&mut repo.url);
+    replace(&mut repo.contributors.url);
+    replace(&mut repo.latest_commit.url);
+    if let Some(avatar_url) = repo.avatar_url.as_mut() {
+        replace(avatar_url);
+    }
+    if let Some(first_commit) = repo.first_commit.as_mut() {
+        replace(&mut first_commit.url);
+    }
+    if let Some(latest_release) = repo.latest_release.as_mut() {
+        replace(&mut latest_release.url);
+    }
+}
+
+/// Collect the data of a single GitLab repository, without going through the
+/// whole landscape collection. This is useful to debug the collection of a
+/// problematic repository. The clients are set up as in the build, using the
+/// tokens and options in the environment, the settings and the arguments.
+pub(crate) async fn collect_single_repository_data(
+    repo_url: &str,
+    branch: Option<&str>,
+    settings: &LandscapeSettings,
+    args: &GitLabArgs,
+) -> Result<RepositoryGitData> {
+    let instance_configs = parse_gitlab_tokens_env(args.strict_tokens)?;
+    let base_url_aliases = parse_gitlab_base_url_aliases_env()?;
+    let client_options = ClientOptions::new(settings, args)?;
+
+    collect_single_repository_data_with_config(
+        repo_url,
+        branch,
+        &instance_configs,
+        &base_url_aliases,
+        &client_options,
+    )
+    .await
+}
+
+/// Collect the data of a single GitLab repository using the tokens and
+/// clients configuration provided.
+async fn collect_single_repository_data_with_config(
+    repo_url: &str,
+    branch: Option<&str>,
+    instance_configs: &[GitlabInstanceConfig],
+    base_url_aliases: &BTreeMap<String, String>,
+    client_options: &ClientOptions,
+) -> Result<RepositoryGitData> {
+    let invalid_url = || format_err!("invalid gitlab repository url");
+    let repo_url = normalize_gitlab_url(repo_url).ok_or_else(invalid_url)?;
+    let base_url = get_instance_url(&repo_url, instance_configs).ok_or_else(invalid_url)?;
+
+    // Setup the clients of the instance the repository belongs to
+    let repos_by_instance = BTreeMap::from([(base_url.clone(), vec![repo_url.clone()])]);
+    let instance_pools = create_instances_pools(
+        &repos_by_instance,
+        instance_configs,
+        base_url_aliases,
+        client_options,
+        false,
+    )
+    .await?;
+    let gl_pool = instance_pools
+        .get(&base_url)
+        .ok_or_else(|| format_err!("no valid gitlab token for instance {base_url}"))?;
+
+    // Collect the repository data, pointing its urls to the instance used in
+    // the repository url when the instance is aliased
+    let mut repo =
+        collect_repository_data_from_pool(gl_pool, &base_url, &repo_url, branch, &client_options.collection)
+            .await?;
+    let api_url = resolve_api_url(&base_url, base_url_aliases);
+    if api_url != base_url {
+        replace_base_url(&mut repo, api_url, &base_url);
+    }
+    Ok(repo)
+}
+
+/// Collect the data of the repository provided, belonging to the instance
+/// given, using one of the clients in the pool given.
+async fn collect_repository_data_from_pool(
+    gl_pool: &Pool<DynGL>,
+    base_url: &str,
+    repo_url: &str,
+    branch: Option<&str>,
+    collection_options: &GitlabCollectionOptions,
+) -> Result<RepositoryGitData> {
+    let gl = get_client(gl_pool).await?;
+    collect_repository_data(&gl, base_url, repo_url, branch, None, collection_options).await
+}
+
+/// Collect repository data from the GitLab instance provided. When a branch is
+/// provided, it'll be used instead of the project's default branch (and it'll
+/// be recorded in the data collected). Projects that have been moved are
+/// collected using their new path, which is recorded in the data collected as
+/// the canonical path. The data is still keyed by the url provided in the
+/// GitLab data returned, as that's the url `add_gitlab_data` uses to look up
+/// the data of each landscape item repository.
+#[instrument(skip_all, err)]
+async fn collect_repository_data(
+    gl: &Object<DynGL>,
+    base_url: &str,
+    repo_url: &str,
+    branch: Option<&str>,
+    github: Option<&DynGitHubCollector>,
+    collection_options: &GitlabCollectionOptions,
+) -> Result<RepositoryGitData> {
+    let (base_url, path) = parse_instance_url(repo_url, base_url, parse_gitlab_url)
+        .ok_or_else(|| format_err!("invalid gitlab repository url"))?;
+    let mut path = decode_project_path(&path)?;
+    let mut canonical_path = None;
+
+    // GitLab redirects the requests for projects that have been renamed or
+    // moved to a different namespace to their new path, which we'll use from
+    // now on to avoid further redirects
+    let gl_project = gl.get_project(&path).await?;
+    if !is_project_id(&path)
+        && !gl_project.path_with_namespace.is_empty()
+        && !gl_project.path_with_namespace.eq_ignore_ascii_case(&path)
+    {
+        info!(
+            "gitlab project {repo_url} has moved to {base_url}/{}, please update its url",
+            gl_project.path_with_namespace
+        );
+        path.clone_from(&gl_project.path_with_namespace);
+        canonical_path = Some(path.clone());
+    }
+    let repo = collect_project_data(
+        gl,
+        &base_url,
+        &path,
+        gl_project,
+        branch,
+        github,
+        collection_options,
+    )
+    .await?;
+    Ok(RepositoryGitData {
+        branch: branch.map(ToString::to_string),
+        canonical_path,
+        ..repo
+    })
+}
+
+/// Refresh the metadata of the repository provided from the GitLab instance
+/// given, using only the project endpoint. The rest of the data is taken from
+/// the cached data of the repository provided (even if it has expired), so the
+/// data returned is only approximately up to date.
+#[instrument(skip_all, err)]
+async fn collect_repository_metadata(
+    gl: &Object<DynGL>,
+    base_url: &str,
+    repo_url: &str,
+    cached_repo: &RepositoryGitData,
+) -> Result<RepositoryGitData> {
+    let (_, path) = parse_instance_url(repo_url, base_url, parse_gitlab_url)
+        .ok_or_else(|| format_err!("invalid gitlab repository url"))?;
+    let path = decode_project_path(&path)?;
+
+    let gl_project = gl.get_project(&path).await?;
+    let repository_size_bytes = gl_project.statistics.as_ref().and_then(|s| s.repository_size);
+    Ok(RepositoryGitData {
+        generated_at: Utc::now(),
+        archived: gl_project.archived,
+        avatar_url: gl_project.avatar_url,
+        description: gl_project.description.unwrap_or_default(),
+        forked: gl_project.forked,
+        from_cache: false,
+        last_activity_at: gl_project.last_activity_at,
+        license_spdx: gl_project.license.as_ref().and_then(GitLabLicense::spdx_id),
+        license: gl_project.license.map(|l| l.name),
+        project_created_at: gl_project.created_at,
+        repository_size_bytes: repository_size_bytes.or(cached_repo.repository_size_bytes),
+        stars: gl_project.star_count,
+        topics: gl_project.topics,
+        url: gl_project.web_url,
+        visibility: gl_project.visibility,
+        ..cached_repo.clone()
+    })
+}
+
+/// Collect the data of all the projects in a GitLab group (including the ones
+/// in its subgroups) from the instance provided, aggregating it into a single
+/// repository data instance. Projects that cannot be collected are skipped,
+/// unless the error affects the client used (the token is no longer usable,
+/// it has been rate limited or the request budget has been exhausted).
+#[instrument(skip_all, err)]
+async fn collect_group_data(
+    gl: &Object<DynGL>,
+    base_url: &str,
+    group_url: &str,
+    collection_options: &GitlabCollectionOptions,
+) -> Result<RepositoryGitData> {
+    let (base_url, group_path) = parse_instance_url(group_url, base_url, parse_gitlab_group_url)
+        .ok_or_else(|| format_err!("invalid gitlab group url"))?;
+
+    let projects_paths = gl.get_group_projects(&group_path).await?;
+    let projects_count = projects_paths.len();
+    debug!("collecting data for {projects_count} projects in group {group_url}");
+    let base_url = base_url.as_str();
+    let results: Vec<(String, Result<RepositoryGitData>)> = stream::iter(projects_paths)
+        .map(|project_path| async move {
+            let result = async {
+                let gl_project = gl.get_project(&project_path).await?;
+                collect_project_data(
+                    gl,
+                    base_url,
+                    &project_path,
+                    gl_project,
+                    None,
+                    None,
+                    collection_options,
+                )
+                .await
+            }
+            .await;
+            (project_path, result)
+        })
+        .buffer_unordered(GROUP_PROJECTS_CONCURRENCY)
+        .collect()
+        .await;
+    let mut projects = vec![];
+    for (project_path, result) in results {
+        match result {
+            Ok(project) => projects.push(project),
+            Err(err)
+                if is_token_error(&err)
+                    || is_rate_limit_error(&err)
+                    || underlying_error(&err).is::<RequestBudgetExhausted>() =>
+            {
+                return Err(err);
+            }
+            Err(err) => {
+                warn!("error collecting project {project_path} of group {group_url}, skipping it: {err}");
+            }
+        }
+    }
+    if projects.is_empty() && projects_count > 0 {
+        bail!("error collecting the projects of group {group_url}");
+    }
+
+    Ok(aggregate_projects_data(
+        &format!("{base_url}/{group_path}"),
+        &projects,
+    ))
+}
+
+/// Aggregate the data of the projects of the group provided: stars, open
+/// issues and merge requests and contributors are added up, topics are
+/// merged, and the oldest first commit and the most recent commit are used.
+/// The contributors count is an upper bound, as the contributors of several
+/// projects are counted once per project.
+fn aggregate_projects_data(group_url: &str, projects: &[RepositoryGitData]) -> RepositoryGitData {
+    let sum = |values: Vec<Option<usize>>| values.into_iter().flatten().reduce(|a, b| a + b);
+
+    let mut topics: Vec<String> = projects.iter().flat_map(|p| p.topics.clone()).collect();
+    topics.sort();
+    topics.dedup();
+
+    RepositoryGitData {
+        generated_at: Utc::now(),
+        contributors: DataContributors {
+            count: projects.iter().map(|p| p.contributors.count).sum(),
+            url: group_url.to_string(),
+        },
+        first_commit: projects
+            .iter()
+            .filter_map(|p| p.first_commit.clone())
+            .filter(|c| c.ts.is_some())
+            .min_by_key(|c| c.ts),
+        latest_commit: projects
+            .iter()
+            .map(|p| p.latest_commit.clone())
+            .max_by_key(|c| c.ts)
+            .unwrap_or_default(),
+        last_activity_at: projects.iter().filter_map(|p| p.last_activity_at).max(),
+        open_issues: sum(projects.iter().map(|p| p.open_issues).collect()),
+        open_merge_requests: sum(projects.iter().map(|p| p.open_merge_requests).collect()),
+        stars: projects.iter().map(|p| p.stars).sum(),
+        topics,
+        url: group_url.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Collect data for a GitLab project. The commits information is collected
+/// from the branch provided, or from the project's default branch if none.
+/// When a GitHub collector is provided and the project is a mirror of a
+/// GitHub repository, the data is collected from the upstream repository
+/// instead (falling back to GitLab if that fails).
+///
+/// The project information is requested concurrently, so each repository
+/// being processed may issue multiple simultaneous requests using the same
+/// token (the outer stream only limits how many repositories are processed
+/// at once per instance). Only the data enabled in the collection options
+/// provided is requested.
+#[allow(clippy::too_many_lines)]
+async fn collect_project_data(
+    gl: &Object<DynGL>,
+    base_url: &str,
+    project_path: &str,
+    gl_project: GitLabProject,
+    branch: Option<&str>,
+    github: Option<&DynGitHubCollector>,
+    collection_options: &GitlabCollectionOptions,
+) -> Result<RepositoryGitData> {
+    if let Some(github) = github
+        && let Some(upstream_url) = gl_project.github_upstream_url()
+    {
+        debug!("{project_path} is a mirror of {upstream_url}, collecting data from github");
+        match github.collect_repository_data(&upstream_url).await {
+            Ok(repo) => return Ok(repo),
+            Err(err) => debug!("error collecting {upstream_url} data, using gitlab data instead: {err}"),
+        }
+    }
+
+    let ref_ = branch.unwrap_or(&gl_project.default_branch);
+    let one_year_ago = Utc::now() - chrono::Duration::days(365);
+    let (
+        (commits_last_year, commit_activity_weekly),
+        contributors,
+        first_commit,
+        languages_pct,
+        good_first_issues,
+        help_wanted_issues,
+        latest_commit,
+        latest_pipeline_status,
+        latest_release,
+        open_issues,
+        open_merge_requests,
+    ) = tokio::try_join!(
+        get_commits_activity(
+            gl,
+            project_path,
+            ref_,
+            one_year_ago,
+            collection_options.commits_last_year
+        ),
+        gl.get_contributors(project_path),
+        gl.get_first_commit(project_path, ref_),
+        collect_if(collection_options.languages, gl.get_languages(project_path)),
+        collect_if(
+            collection_options.good_first_issues,
+            gl.get_good_first_issues_count(project_path)
+        ),
+        collect_if(
+            collection_options.help_wanted_issues,
+            gl.get_help_wanted_count(project_path)
+        ),
+        collect_if(
+            collection_options.latest_commit,
+            gl.get_latest_commit(project_path, ref_)
+        ),
+        collect_if(
+            collection_options.latest_pipeline_status,
+            gl.get_latest_pipeline_status(project_path, ref_)
+        ),
+        collect_if(
+            collection_options.latest_release,
+            gl.get_latest_release(project_path)
+        ),
+        collect_if(
+            collection_options.open_issues,
+            gl.get_open_issues_count(project_path)
+        ),
+        collect_if(
+            collection_options.open_merge_requests,
+            gl.get_open_mrs_count(project_path)
+        ),
+    )?;
+    debug!("languages result for {}: {:?}", project_path, languages_pct);
+
+    let repository_size_bytes = gl_project.statistics.as_ref().and_then(|s| s.repository_size);
+
+    // Repositories in the landscape are expected to be public
+    if let Some(visibility) = &gl_project.visibility
+        && visibility != "public"
+    {
+        warn!("{base_url}/{project_path} is not public (visibility: {visibility})");
+    }
+
+    // Use the project creation date as the first commit date when no commits
+    // are found (i.e. empty repositories or rewritten history)
+    let first_commit = first_commit.or_else(|| {
+        gl_project.created_at.map(|ts| Commit {
+            ts: Some(ts),
+            url: gl_project.web_url.clone(),
+            ..Default::default()
+        })
+    });
+
+    // When the latest commit details are not required, the date of the last
+    // activity in the project is used instead
+    let latest_commit = if collection_options.latest_commit {
+        latest_commit.unwrap_or_default()
+    } else {
+        Commit {
+            ts: gl_project.last_activity_at,
+            url: format!("{base_url}/{project_path}/-/commits/{ref_}"),
+            ..Default::default()
+        }
+    };
+
+    // Prepare repository instance using the information collected
+    Ok(RepositoryGitData {
+        generated_at: Utc::now(),
+        contributors: DataContributors {
+            count: contributors.count,
+            url: format!("{base_url}/{project_path}/-/graphs/{ref_}?ref_type=heads"),
+        },
+        archived: gl_project.archived,
+        avatar_url: gl_project.avatar_url,
+        commit_activity_weekly,
+        commits_last_year,
+        description: gl_project.description.unwrap_or_default(),
+        first_commit,
+        forked: gl_project.forked,
+        good_first_issues,
+        help_wanted_issues,
+        languages: languages_pct
+            .as_ref()
+            .map(|languages_pct| estimate_languages_bytes(languages_pct, repository_size_bytes)),
+        languages_are_approximate: languages_pct.as_ref().map(|_| true),
+        languages_pct,
+        last_activity_at: gl_project.last_activity_at,
+        latest_commit,
+        latest_pipeline_status,
+        latest_release,
+        license_spdx: gl_project.license.as_ref().and_then(GitLabLicense::spdx_id),
+        license: gl_project.license.map(|l| l.name),
+        open_issues,
+        open_merge_requests,
+        project_created_at: gl_project.created_at,
+        repository_size_bytes,
+        stars: gl_project.star_count,
+        top_contributors: Some(get_top_contributors(&contributors.top, TOP_CONTRIBUTORS_COUNT))
+            .filter(|top_contributors| !top_contributors.is_empty()),
+        topics: gl_project.topics,
+        url: gl_project.web_url,
+        visibility: gl_project.visibility,
+        ..Default::default()
+    })
+}
+
+/// Await the operation provided only when enabled, returning no data
+/// otherwise (so that its requests are not made).
+async fn collect_if<T>(
+    enabled: bool,
+    operation: impl Future<Output = Result<Option<T>, GitlabError>>,
+) -> Result<Option<T>, GitlabError> {
+    if enabled { operation.await } else { Ok(None) }
+}
+
+/// Get the number of commits since the date provided (when requested) and the
+/// weekly commit activity (when enabled). As the activity requires paging
+/// through the commits of the last weeks, the number of commits is computed
+/// from it when available, unless the commits paged through reached the limit.
+async fn get_commits_activity(
+    gl: &Object<DynGL>,
+    project_path: &str,
+    ref_: &str,
+    since: DateTime<Utc>,
+    count_commits: bool,
+) -> Result<(Option<usize>, Option<Vec<u32>>), GitlabError> {
+    let activity = gl.get_weekly_commit_activity(project_path, ref_).await?;
+    if !count_commits {
+        return Ok((None, activity));
+    }
+    if let Some(activity) = &activity {
+        let count = activity.iter().map(|count| *count as usize).sum();
+        if count < MAX_RECENT_COMMITS_COUNT {
+            return Ok((Some(count), Some(activity.clone())));
+        }
+    }
+    let count = gl.get_recent_commit_count(project_path, ref_, since).await?;
+    Ok((count, activity))
+}
+
+/// Bucket the commits dates provided into the number of commits of each of
+/// the last weeks before the date given, from the oldest week to the most
+/// recent one. Older commits are ignored.
+fn bucket_weekly_commit_activity(
+    dates: impl IntoIterator<Item = DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Vec<u32> {
+    let mut activity = vec![0; COMMIT_ACTIVITY_WEEKS];
+    for date in dates {
+        // Commits dated in the future (i.e. clock skew) belong to the last week
+        let weeks_ago = usize::try_from((now - date).num_weeks()).unwrap_or_default();
+        if weeks_ago < COMMIT_ACTIVITY_WEEKS {
+            activity[COMMIT_ACTIVITY_WEEKS - 1 - weeks_ago] += 1;
+        }
+    }
+    activity
+}
+
+/// Return the top contributors (up to the number provided) ordered by their
+/// number of commits.
+fn get_top_contributors(contributors: &[GitLabContributor], n: usize) -> Vec<TopContributor> {
+    let mut contributors: Vec<&GitLabContributor> = contributors.iter().collect();
+    contributors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.name.cmp(&b.name)));
+    contributors
+        .into_iter()
+        .take(n)
+        .map(|contributor| TopContributor {
+            name: contributor.name.clone(),
+            commits: contributor.commits,
+            avatar_url: None,
+        })
+        .collect()
+}
+
+/// Estimate the number of bytes of each language from the percentages
+/// provided by GitLab, so that they can be aggregated with the languages
+/// information collected from GitHub. As GitLab does not provide the actual
+/// number of bytes, percentages are scaled to the repository size when it is
+/// available, or normalized to a total of 100000 bytes otherwise.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn estimate_languages_bytes(
+    languages_pct: &BTreeMap<String, f64>,
+    repository_size: Option<u64>,
+) -> BTreeMap<String, i64> {
+    let total_bytes = repository_size
+        .filter(|size| *size > 0)
+        .map_or(DEFAULT_LANGUAGES_TOTAL_BYTES, |size| size as f64);
+    languages_pct
+        .iter()
+        .map(|(language, percentage)| {
+            let bytes = (percentage / 100.0 * total_bytes).round() as i64;
+            (language.clone(), bytes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use landscape2_core::data::{Item, Repository};
+    use landscape2_core::settings::{Collection, GitLabCollection, GitLabCollectionProfile};
+    use reqwest::StatusCode;
+
+    use std::sync::atomic::Ordering;
+
+    use gitlab::RestError;
+    use gitlab::api::ApiError;
+
+    use super::cache::GITLAB_ERRORS_CACHE_TTL;
+    use super::client::{GLApi, MockGL, error_status};
+    use super::dto::{GitLabContributors, GitLabProjectStatistics, GitLabRelease, GitLabReleaseLinks};
+    use super::retry::GLRetry;
+    use super::*;
+    use crate::build::github::MockGitHubCollector;
+
+    /// Collect GitLab data for each of the items repositories in the landscape
+    /// using the tokens and clients configuration provided.
+    #[allow(clippy::too_many_arguments)]
+    async fn collect_gitlab_data_with_config(
+        cache: &Cache,
+        landscape_data: &LandscapeData,
+        args: &GitLabArgs,
+        instance_configs: &[GitlabInstanceConfig],
+        base_url_aliases: &BTreeMap<String, String>,
+        client_options: &ClientOptions,
+        exclude_patterns: &[Regex],
+        failures_file: Option<&Path>,
+        github: Option<DynGitHubCollector>,
+    ) -> Result<GitData> {
+        let ctx = GitLabCollectionContext::with_config(
+            cache,
+            landscape_data,
+            args,
+            instance_configs.to_vec(),
+            base_url_aliases.clone(),
+            client_options.clone(),
+            exclude_patterns,
+            github,
+        )
+        .await?;
+        collect_gitlab_data_with_context(&ctx, failures_file).await
+    }
+
+    /// Collect data for the repositories provided, all of them belonging to the
+    /// instance in the context provided, waiting for all of them to be processed.
+    /// See [`stream_instance_data`] for more details.
+    async fn collect_instance_data(
+        ctx: InstanceCollectionContext<'_>,
+        urls: &[String],
+    ) -> BTreeMap<String, Result<RepositoryGitData>> {
+        stream_instance_data(ctx, urls).collect().await
+    }
+
+    /// Create a new GitLab API error with the status code provided.
+    pub(super) fn api_error(status: StatusCode) -> GitlabError {
+        anyhow::Error::from(ApiError::<RestError>::GitlabWithStatus {
+            status,
+            msg: String::new(),
+        })
+        .into()
+    }
+
+    /// Writer used to capture the logs emitted during a test.
+    #[derive(Clone, Default)]
+    pub(super) struct LogsWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl LogsWriter {
+        /// Return the logs captured so far.
+        pub fn logs(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl io::Write for LogsWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Capture the warnings emitted in the current thread while the guard
+    /// returned is alive.
+    pub(super) fn capture_warnings() -> (LogsWriter, tracing::subscriber::DefaultGuard) {
+        capture_logs(tracing::Level::WARN)
+    }
+
+    /// Capture the logs of the level provided (or higher) emitted by the
+    /// current thread until the guard returned is dropped.
+    fn capture_logs(level: tracing::Level) -> (LogsWriter, tracing::subscriber::DefaultGuard) {
+        let logs = LogsWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .with_ansi(false)
+            .with_max_level(level)
+            .finish();
+        (logs, tracing::subscriber::set_default(subscriber))
+    }
+
+    /// Add some default expectations to the GL mock provided for all the
+    /// operations. As expectations are evaluated in FIFO order, the ones set
+    /// before calling this function take precedence.
+    fn add_default_expectations(mut gl: MockGL) -> MockGL {
+        gl.expect_get_contributors().returning(|_| {
+            Box::pin(future::ready(Ok(GitLabContributors {
+                count: 1,
+                top: vec![GitLabContributor::default()],
+            })))
+        });
+        gl.expect_get_first_commit().returning(|_, _| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_good_first_issues_count().returning(|_| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_help_wanted_count().returning(|_| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_languages().returning(|_| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_latest_commit()
+            .returning(|_, _| Box::pin(future::ready(Ok(Some(Commit::default())))));
+        gl.expect_get_latest_pipeline_status().returning(|_, _| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_latest_release().returning(|_| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_open_issues_count().returning(|_| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_open_mrs_count().returning(|_| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_recent_commit_count().returning(|_, _, _| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_weekly_commit_activity().returning(|_, _| Box::pin(future::ready(Ok(None))));
+        gl
+    }
+
+    /// Get a pool object wrapping the GL implementation provided.
+    async fn gl_object(gl: MockGL) -> Object<DynGL> {
+        let gl: DynGL = Box::new(gl);
+        Pool::from(vec![gl]).get().await.unwrap()
+    }
+
+    /// Create a retry configuration suitable for tests (no waiting).
+    pub(super) fn test_retry_config(max_retries: u32) -> RetryConfig {
+        RetryConfig {
+            max_retries,
+            base_delay: Duration::ZERO,
+        }
+    }
+
+    /// Start an HTTP server that replies to all requests with an empty json
+    /// object. Requests are held until the number of requests expected by the
+    /// gate provided have been received (by any of the servers sharing it), so
+    /// they only succeed when they are made concurrently (requests that can't
+    /// proceed in time get an error response).
+    fn start_gated_server(
+        gate: Arc<(std::sync::Mutex<usize>, std::sync::Condvar)>,
+        expected: usize,
+    ) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let gate = gate.clone();
+                std::thread::spawn(move || {
+                    let mut request = vec![];
+                    let mut buf = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match io::Read::read(&mut stream, &mut buf) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+
+                    let (received, all_received) = &*gate;
+                    let mut received = received.lock().unwrap();
+                    *received += 1;
+                    all_received.notify_all();
+                    let (received, _) = all_received
+                        .wait_timeout_while(received, Duration::from_secs(5), |received| *received < expected)
+                        .unwrap();
+                    let status = if *received >= expected {
+                        "200 OK"
+                    } else {
+                        "503 Service Unavailable"
+                    };
+                    drop(received);
+
+                    let response = format!(
+                        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: 2\r\n\
+                         connection: close\r\n\r\n{{}}"
+                    );
+                    _ = stream.write_all(response.as_bytes());
+                });
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn replace_base_url_uses_landscape_data_host() {
+        let mut repo = RepositoryGitData {
+            url: "https://gitlab-internal.example.com/group/project".to_string(),
+            avatar_url: Some("https://gitlab-internal.example.com/uploads/avatar.png".to_string()),
+            first_commit: Some(Commit {
+                url: "https://gitlab-internal.example.com/group/project/-/commit/1".to_string(),
+                ..Default::default()
+            }),
+            latest_commit: Commit {
+                url: "https://GITLAB-INTERNAL.example.com/group/project/-/commit/2".to_string(),
+                ..Default::default()
+            },
+            contributors: DataContributors {
+                url: "https://git.example.com/group/project/-/graphs/main?ref_type=heads".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        replace_base_url(
+            &mut repo,
+            "https://gitlab-internal.example.com",
+            "https://git.example.com",
+        );
+        assert_eq!(repo.url, "https://git.example.com/group/project");
+        assert_eq!(
+            repo.avatar_url,
+            Some("https://git.example.com/uploads/avatar.png".to_string())
+        );
+        assert_eq!(
+            repo.first_commit.unwrap().url,
+            "https://git.example.com/group/project/-/commit/1"
+        );
+        assert_eq!(
+            repo.latest_commit.url,
+            "https://git.example.com/group/project/-/commit/2"
+        );
+        assert_eq!(
+            repo.contributors.url,
+            "https://git.example.com/group/project/-/graphs/main?ref_type=heads"
+        );
+    }
+
+    #[test]
+    fn get_good_first_issues_labels_from_settings() {
+        let mut settings = LandscapeSettings::default();
+        assert_eq!(
+            get_good_first_issues_labels(&settings),
+            vec!["good first issue".to_string()]
+        );
+
+        settings.collection = Some(Collection {
+            gitlab: Some(GitLabCollection {
+                good_first_issues_labels: Some(vec!["help wanted".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            get_good_first_issues_labels(&settings),
+            vec!["help wanted".to_string()]
+        );
+    }
+
+    #[test]
+    fn get_help_wanted_labels_from_settings() {
+        let mut settings = LandscapeSettings::default();
+        assert_eq!(get_help_wanted_labels(&settings), vec!["help wanted".to_string()]);
+
+        settings.collection = Some(Collection {
+            gitlab: Some(GitLabCollection {
+                help_wanted_labels: Some(vec!["contributions welcome".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            get_help_wanted_labels(&settings),
+            vec!["contributions welcome".to_string()]
+        );
+    }
+
+    #[test]
+    fn get_top_contributors_ordered_by_commits() {
+        let contributor = |name: &str, commits| GitLabContributor {
+            name: name.to_string(),
+            commits,
+        };
+        let contributors = vec![
+            contributor("user1", 3),
+            contributor("user2", 10),
+            contributor("user3", 7),
+            contributor("user4", 10),
+        ];
+
+        assert_eq!(
+            get_top_contributors(&contributors, 3),
+            vec![
+                TopContributor {
+                    name: "user2".to_string(),
+                    commits: 10,
+                    avatar_url: None,
+                },
+                TopContributor {
+                    name: "user4".to_string(),
+                    commits: 10,
+                    avatar_url: None,
+                },
+                TopContributor {
+                    name: "user3".to_string(),
+                    commits: 7,
+                    avatar_url: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn group_repositories_by_instance_deduplicates_urls() {
+        let repository = |url: &str| Repository {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        let landscape_data = LandscapeData {
+            items: vec![
+                Item {
+                    repositories: Some(vec![
+                        repository("https://gitlab.com/group/project"),
+                        repository("https://github.com/org/repo"),
+                    ]),
+                    ..Default::default()
+                },
+                Item {
+                    repositories: Some(vec![repository("https://GitLab.com/group/project/")]),
+                    ..Default::default()
+                },
+                Item {
+                    repositories: Some(vec![repository("https://gitlab.com/group/project.git")]),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            group_repositories_by_instance(&landscape_data, &[]),
+            BTreeMap::from([(
+                "https://gitlab.com".to_string(),
+                vec!["https://gitlab.com/group/project".to_string()]
+            )])
+        );
+    }
+
+    #[test]
+    fn group_repositories_by_instance_known_instances_only() {
+        let repository = |url: &str| Repository {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(vec![
+                    repository("https://gitlab.com/group/project"),
+                    repository("https://gitea.com/org/repo"),
+                    repository("https://bitbucket.org/org/repo"),
+                    repository("https://git.sr.ht/~user/repo"),
+                    repository("https://GitLab.Example.com/group/project"),
+                ]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: "https://gitlab.example.com".to_string(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+
+        assert_eq!(
+            group_repositories_by_instance(&landscape_data, &instance_configs),
+            BTreeMap::from([
+                (
+                    "https://gitlab.com".to_string(),
+                    vec!["https://gitlab.com/group/project".to_string()]
+                ),
+                (
+                    "https://gitlab.example.com".to_string(),
+                    vec!["https://gitlab.example.com/group/project".to_string()]
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn group_repositories_by_instance_relative_url_root() {
+        let repository = |url: &str| Repository {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(vec![
+                    repository("https://example.com/gitlab/group/project"),
+                    repository("https://example.com/gitlab/group/subgroup/project/"),
+                    repository("https://example.com/other/project"),
+                ]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: "https://example.com/gitlab/".to_string(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+
+        assert_eq!(
+            group_repositories_by_instance(&landscape_data, &instance_configs),
+            BTreeMap::from([(
+                "https://example.com/gitlab".to_string(),
+                vec![
+                    "https://example.com/gitlab/group/project".to_string(),
+                    "https://example.com/gitlab/group/subgroup/project".to_string()
+                ]
+            )])
+        );
+    }
+
+    #[test]
+    fn write_ndjson_writes_one_record_per_line() {
+        let gitlab_data = GitData::from([
+            (
+                "https://gitlab.com/group/project1".to_string(),
+                RepositoryGitData {
+                    description: "Multi-line\ndescription".to_string(),
+                    stars: 10,
+                    url: "https://gitlab.com/group/project1".to_string(),
+                    ..Default::default()
+                },
+            ),
+            (
+                "https://gitlab.com/group/project2".to_string(),
+                RepositoryGitData {
+                    stars: 20,
+                    topics: vec!["topic".to_string()],
+                    url: "https://gitlab.com/group/new-project2".to_string(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let mut output = vec![];
+        write_ndjson(&mut output, &gitlab_data).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.ends_with('\n'));
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for (line, (repository_url, data)) in lines.iter().zip(&gitlab_data) {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(record["repository_url"], repository_url.as_str());
+            assert_eq!(record["stars"], data.stars);
+            let parsed_data: RepositoryGitData = serde_json::from_value(record).unwrap();
+            assert_eq!(&parsed_data, data);
+        }
+    }
+
+    #[test]
+    fn write_ndjson_output_to_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let output = tmp_dir.path().join("gitlab.ndjson");
+        let gitlab_data = GitData::from([(
+            "https://gitlab.com/group/project".to_string(),
+            RepositoryGitData::default(),
+        )]);
+
+        write_ndjson_output(&output, &gitlab_data).unwrap();
+        let content = fs::read_to_string(&output).unwrap();
+        assert_eq!(content.lines().count(), 1);
+        assert!(serde_json::from_str::<serde_json::Value>(content.trim_end()).is_ok());
+    }
+
+    #[test]
+    fn reconcile_instance_configs_reports_unused_configs() {
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(vec![Repository {
+                    url: "https://gitlab.example.com/group/project".to_string(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let config = |base_url: &str| GitlabInstanceConfig {
+            base_url: base_url.to_string(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        };
+        let instance_configs = [
+            config("https://GitLab.example.com/"),
+            config("https://gitlab.exmaple.com"),
+            config("https://example.com/gitlab"),
+        ];
+
+        assert_eq!(
+            reconcile_instance_configs(&landscape_data, &instance_configs),
+            InstancesReconciliation {
+                unused_configs: vec![
+                    "https://gitlab.exmaple.com".to_string(),
+                    "https://example.com/gitlab".to_string()
+                ],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn reconcile_instance_configs_reports_unconfigured_instances() {
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(
+                    [
+                        "https://gitlab.com/group/project1",
+                        "https://gitlab.com/group/project2",
+                        "https://gitlab.com/group/project2/",
+                        "https://gitlab.example.com/group/project",
+                        "https://code.example.com/group/project",
+                        "https://github.com/org/repo",
+                        "https://gitlab.configured.com/group/project",
+                    ]
+                    .iter()
+                    .map(|url| Repository {
+                        url: (*url).to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: "https://gitlab.configured.com".to_string(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+
+        assert_eq!(
+            reconcile_instance_configs(&landscape_data, &instance_configs),
+            InstancesReconciliation {
+                unconfigured_instances: BTreeMap::from([
+                    ("https://gitlab.com".to_string(), 2),
+                    ("https://gitlab.example.com".to_string(), 1),
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn get_items_without_data_reports_items_with_no_repositories_collected() {
+        let item = |name: &str, urls: &[&str]| Item {
+            name: name.to_string(),
+            repositories: Some(
+                urls.iter()
+                    .map(|url| Repository {
+                        url: (*url).to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let landscape_data = LandscapeData {
+            items: vec![
+                item("Collected", &["https://gitlab.com/group/collected"]),
+                item("Failed", &["https://gitlab.com/group/failed"]),
+                item(
+                    "Partially failed",
+                    &[
+                        "https://gitlab.com/group/failed",
+                        "https://gitlab.com/group/collected",
+                    ],
+                ),
+                item("Not on gitlab", &["https://github.com/org/repo"]),
+            ],
+            ..Default::default()
+        };
+        let collected_urls = HashSet::from([
+            "https://gitlab.com/group/collected",
+            "https://gitlab.com/group/failed",
+        ]);
+        let gitlab_data = GitData::from([(
+            "https://gitlab.com/group/collected".to_string(),
+            RepositoryGitData::default(),
+        )]);
+
+        assert_eq!(
+            get_items_without_data(&landscape_data, &collected_urls, &gitlab_data),
+            vec!["Failed".to_string()]
+        );
+    }
+
+    #[test]
+    fn parse_instance_url_removes_relative_url_root() {
+        let base_url = "https://example.com/gitlab";
+        assert_eq!(
+            parse_instance_url(
+                "https://example.com/gitlab/group/project",
+                base_url,
+                parse_gitlab_url
+            ),
+            Some((base_url.to_string(), "group/project".to_string()))
+        );
+        assert_eq!(
+            parse_instance_url(
+                "https://example.com/gitlab/-/project/12345",
+                base_url,
+                parse_gitlab_url
+            ),
+            Some((base_url.to_string(), "-/project/12345".to_string()))
+        );
+        assert_eq!(
+            parse_instance_url(
+                "https://example.com/gitlab/groups/group",
+                base_url,
+                parse_gitlab_group_url
+            ),
+            Some((base_url.to_string(), "group".to_string()))
+        );
+        assert_eq!(
+            parse_instance_url(
+                "https://example.com/gitlabx/group/project",
+                base_url,
+                parse_gitlab_url
+            ),
+            None
+        );
+
+        // Instances without a relative url root are parsed as usual
+        assert_eq!(
+            parse_instance_url(
+                "https://gitlab.com/group/project",
+                "https://gitlab.com",
+                parse_gitlab_url
+            ),
+            Some(("https://gitlab.com".to_string(), "group/project".to_string()))
+        );
+    }
+
+    #[test]
+    fn exclude_repositories_matching_patterns() {
+        let mut repos_by_instance = BTreeMap::from([
+            (
+                "https://gitlab.com".to_string(),
+                vec![
+                    "https://gitlab.com/big-group/monorepo".to_string(),
+                    "https://gitlab.com/group/project".to_string(),
+                ],
+            ),
+            (
+                "https://gitlab.example.com".to_string(),
+                vec!["https://gitlab.example.com/group/project".to_string()],
+            ),
+        ]);
+        let settings = LandscapeSettings {
+            collection: Some(Collection {
+                exclude: Some(vec!["https://GitLab.com/big-group/*".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        exclude_repositories(&mut repos_by_instance, &get_exclude_patterns(&settings).unwrap());
+        assert_eq!(
+            repos_by_instance,
+            BTreeMap::from([
+                (
+                    "https://gitlab.com".to_string(),
+                    vec!["https://gitlab.com/group/project".to_string()]
+                ),
+                (
+                    "https://gitlab.example.com".to_string(),
+                    vec!["https://gitlab.example.com/group/project".to_string()]
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn limit_repositories_keeps_first_urls() {
+        let mut repos_by_instance = BTreeMap::from([
+            (
+                "https://gitlab.com".to_string(),
+                vec![
+                    "https://gitlab.com/group/c".to_string(),
+                    "https://gitlab.com/group/a".to_string(),
+                ],
+            ),
+            (
+                "https://gitlab.example.com".to_string(),
+                vec!["https://gitlab.example.com/group/b".to_string()],
+            ),
+        ]);
+
+        assert!(limit_repositories(&mut repos_by_instance.clone(), 3).is_empty());
+        let skipped = limit_repositories(&mut repos_by_instance, 1);
+        assert_eq!(
+            repos_by_instance,
+            BTreeMap::from([(
+                "https://gitlab.com".to_string(),
+                vec!["https://gitlab.com/group/a".to_string()],
+            )])
+        );
+        assert_eq!(
+            skipped,
+            vec![
+                "https://gitlab.com/group/c".to_string(),
+                "https://gitlab.example.com/group/b".to_string(),
+            ]
+        );
+        assert_eq!(limit_repositories(&mut repos_by_instance, 0).len(), 1);
+        assert!(repos_by_instance.is_empty());
+    }
+
+    #[test]
+    fn filter_repositories_narrows_repos_by_instance() {
+        let item = |category: &str, subcategory: &str, name: &str, urls: &[&str]| Item {
+            category: category.to_string(),
+            subcategory: subcategory.to_string(),
+            name: name.to_string(),
+            repositories: Some(
+                urls.iter()
+                    .map(|url| Repository {
+                        url: (*url).to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let landscape_data = LandscapeData {
+            items: vec![
+                item(
+                    "Runtime",
+                    "Container",
+                    "Project A",
+                    &["https://gitlab.com/group/a"],
+                ),
+                item(
+                    "Runtime",
+                    "Storage",
+                    "Project B",
+                    &["https://gitlab.com/group/b.git"],
+                ),
+                item(
+                    "Observability",
+                    "Tracing",
+                    "Project C",
+                    &["https://gitlab.com/group/c", "https://gitlab.example.com/group/c"],
+                ),
+            ],
+            ..Default::default()
+        };
+        let repos_by_instance = BTreeMap::from([
+            (
+                "https://gitlab.com".to_string(),
+                vec![
+                    "https://gitlab.com/group/a".to_string(),
+                    "https://gitlab.com/group/b".to_string(),
+                    "https://gitlab.com/group/c".to_string(),
+                ],
+            ),
+            (
+                "https://gitlab.example.com".to_string(),
+                vec!["https://gitlab.example.com/group/c".to_string()],
+            ),
+        ]);
+
+        // No filter: all repositories are kept
+        let mut unfiltered = repos_by_instance.clone();
+        let filtered_out =
+            filter_repositories(&mut unfiltered, &landscape_data, &GitLabItemsFilter::default()).unwrap();
+        assert!(filtered_out.is_empty());
+        assert_eq!(unfiltered, repos_by_instance);
+
+        // Filter by category
+        let mut by_category = repos_by_instance.clone();
+        let filter = GitLabItemsFilter {
+            categories: vec!["runtime".to_string()],
+            ..Default::default()
+        };
+        let filtered_out = filter_repositories(&mut by_category, &landscape_data, &filter).unwrap();
+        assert_eq!(
+            by_category,
+            BTreeMap::from([(
+                "https://gitlab.com".to_string(),
+                vec![
+                    "https://gitlab.com/group/a".to_string(),
+                    "https://gitlab.com/group/b".to_string()
+                ],
+            )])
+        );
+        assert_eq!(
+            filtered_out,
+            vec![
+                "https://gitlab.com/group/c".to_string(),
+                "https://gitlab.example.com/group/c".to_string()
+            ]
+        );
+
+        // Filter by category and item name pattern
+        let mut by_item = repos_by_instance.clone();
+        let filter = GitLabItemsFilter {
+            categories: vec!["Runtime".to_string()],
+            items: vec!["* b".to_string()],
+            ..Default::default()
+        };
+        filter_repositories(&mut by_item, &landscape_data, &filter).unwrap();
+        assert_eq!(
+            by_item,
+            BTreeMap::from([(
+                "https://gitlab.com".to_string(),
+                vec!["https://gitlab.com/group/b".to_string()],
+            )])
+        );
+    }
+
+    #[test]
+    fn get_exclude_patterns_globs() {
+        let mut settings = LandscapeSettings::default();
+        assert!(get_exclude_patterns(&settings).unwrap().is_empty());
+
+        settings.collection = Some(Collection {
+            exclude: Some(vec!["https://gitlab.com/group/project-?".to_string()]),
+            ..Default::default()
+        });
+        let patterns = get_exclude_patterns(&settings).unwrap();
+        assert!(patterns[0].is_match("https://gitlab.com/group/project-1"));
+        assert!(!patterns[0].is_match("https://gitlab.com/group/project-10"));
+        assert!(!patterns[0].is_match("https://gitlab.com/group/project.1"));
+    }
+
+    #[tokio::test]
+    async fn collect_gitlab_data_dry_run() {
+        let mut server = mockito::Server::new_async().await;
+        let user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .expect(0)
+            .create_async()
+            .await;
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(vec![Repository {
+                    url: format!("{}/group/project", server.url()),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let args = GitLabArgs {
+            api: GitLabApi::Rest,
+            cache_ttl: DEFAULT_GITLAB_CACHE_TTL,
+            refresh_before: None,
+            report: false,
+            ndjson_output: None,
+            require_tokens: false,
+            strict_tokens: false,
+            allow_unauthenticated: false,
+            dry_run: true,
+            max_requests: None,
+            max_repos: None,
+            ignore_cache: false,
+            metadata_only_refresh: false,
+            offline: false,
+            progress: false,
+            filter: GitLabItemsFilter::default(),
+        };
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: server.url(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+
+        let gitlab_data = collect_gitlab_data_with_config(
+            &cache,
+            &landscape_data,
+            &args,
+            &instance_configs,
+            &BTreeMap::new(),
+            &ClientOptions::default(),
+            &[],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(gitlab_data.is_empty());
+        assert!(cache.read(GITLAB_CACHE_FILE).unwrap().is_none());
+
+        user_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn collect_gitlab_data_ignore_cache() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let repo_url = format!("{}/group/project", server.url());
+        let cached_data = GitData::from([(
+            repo_url.clone(),
+            RepositoryGitData {
+                generated_at: Utc::now(),
+                ..Default::default()
+            },
+        )]);
+        cache.write(GITLAB_CACHE_FILE, &serde_json::to_vec(&cached_data).unwrap()).unwrap();
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(vec![Repository {
+                    url: repo_url,
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let args = GitLabArgs {
+            api: GitLabApi::Rest,
+            cache_ttl: DEFAULT_GITLAB_CACHE_TTL,
+            refresh_before: None,
+            report: false,
+            ndjson_output: None,
+            require_tokens: false,
+            strict_tokens: false,
+            allow_unauthenticated: false,
+            dry_run: false,
+            max_requests: None,
+            max_repos: None,
+            ignore_cache: true,
+            metadata_only_refresh: false,
+            offline: false,
+            progress: false,
+            filter: GitLabItemsFilter::default(),
+        };
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: server.url(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+        let client_options = ClientOptions {
+            check_token_scopes: false,
+            ..Default::default()
+        };
+
+        // The fresh cached entry is not used, so the project is fetched again
+        let gitlab_data = collect_gitlab_data_with_config(
+            &cache,
+            &landscape_data,
+            &args,
+            &instance_configs,
+            &BTreeMap::new(),
+            &client_options,
+            &[],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(gitlab_data.is_empty());
+
+        project_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn collect_gitlab_data_instance_cache_ttl() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let repo_url = format!("{}/group/project", server.url());
+        let cached_data = GitData::from([(
+            repo_url.clone(),
+            RepositoryGitData {
+                generated_at: Utc::now() - chrono::Duration::days(3),
+                ..Default::default()
+            },
+        )]);
+        cache.write(GITLAB_CACHE_FILE, &serde_json::to_vec(&cached_data).unwrap()).unwrap();
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(vec![Repository {
+                    url: repo_url,
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let args = GitLabArgs {
+            api: GitLabApi::Rest,
+            cache_ttl: DEFAULT_GITLAB_CACHE_TTL,
+            refresh_before: None,
+            report: false,
+            ndjson_output: None,
+            require_tokens: false,
+            strict_tokens: false,
+            allow_unauthenticated: false,
+            dry_run: false,
+            max_requests: None,
+            max_repos: None,
+            ignore_cache: false,
+            metadata_only_refresh: false,
+            offline: false,
+            progress: false,
+            filter: GitLabItemsFilter::default(),
+        };
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: server.url(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: Some(1),
+        }];
+        let client_options = ClientOptions {
+            check_token_scopes: false,
+            ..Default::default()
+        };
+
+        // The cached entry is still fresh for the global TTL, but it has
+        // expired for the instance one, so the project is fetched again
+        collect_gitlab_data_with_config(
+            &cache,
+            &landscape_data,
+            &args,
+            &instance_configs,
+            &BTreeMap::new(),
+            &client_options,
+            &[],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        project_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn collect_gitlab_data_writes_failures_file() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let _project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .create_async()
+            .await;
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let failures_file = tmp_dir.path().join("gitlab-failures.json");
+        let repo_url = format!("{}/group/project", server.url());
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(vec![Repository {
+                    url: repo_url.clone(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let args = GitLabArgs {
+            api: GitLabApi::Rest,
+            cache_ttl: DEFAULT_GITLAB_CACHE_TTL,
+            refresh_before: None,
+            report: false,
+            ndjson_output: None,
+            require_tokens: false,
+            strict_tokens: false,
+            allow_unauthenticated: false,
+            dry_run: false,
+            max_requests: None,
+            max_repos: None,
+            ignore_cache: false,
+            metadata_only_refresh: false,
+            offline: false,
+            progress: false,
+            filter: GitLabItemsFilter::default(),
+        };
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: server.url(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+        let client_options = ClientOptions {
+            check_token_scopes: false,
+            ..Default::default()
+        };
+
+        let gitlab_data = collect_gitlab_data_with_config(
+            &cache,
+            &landscape_data,
+            &args,
+            &instance_configs,
+            &BTreeMap::new(),
+            &client_options,
+            &[],
+            Some(&failures_file),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(gitlab_data.is_empty());
+
+        let failures: serde_json::Value = serde_json::from_slice(&fs::read(&failures_file).unwrap()).unwrap();
+        let failures = failures.as_array().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0]["url"], repo_url);
+        assert_eq!(failures[0]["kind"], "status_404");
+        assert!(failures[0]["message"].as_str().unwrap().contains("404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn stream_gitlab_data_yields_each_repository_of_all_instances() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let _project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .create_async()
+            .await;
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let repos_urls = [
+            format!("{}/group/project", server.url()),
+            "https://gitlab.com/group/project".to_string(),
+        ];
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(
+                    repos_urls
+                        .iter()
+                        .map(|url| Repository {
+                            url: url.clone(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let args = GitLabArgs {
+            api: GitLabApi::Rest,
+            cache_ttl: DEFAULT_GITLAB_CACHE_TTL,
+            refresh_before: None,
+            report: false,
+            ndjson_output: None,
+            require_tokens: false,
+            strict_tokens: false,
+            allow_unauthenticated: false,
+            dry_run: false,
+            max_requests: None,
+            max_repos: None,
+            ignore_cache: false,
+            metadata_only_refresh: false,
+            offline: false,
+            progress: false,
+            filter: GitLabItemsFilter::default(),
+        };
+        let instance_configs = vec![GitlabInstanceConfig {
+            base_url: server.url(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+        let client_options = ClientOptions {
+            check_token_scopes: false,
+            ..Default::default()
+        };
+        let ctx = GitLabCollectionContext::with_config(
+            &cache,
+            &landscape_data,
+            &args,
+            instance_configs,
+            BTreeMap::new(),
+            client_options,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        let items: BTreeMap<_, _> = stream_gitlab_data(&ctx).collect().await;
+        assert_eq!(items.len(), 2);
+        assert!(items[&repos_urls[0]].as_ref().is_err_and(|err| error_status(err) == Some(404)));
+        assert!(items[&repos_urls[1]].is_err());
+        assert_eq!(ctx.stats.lock().unwrap().total, 2);
+    }
+
+    #[tokio::test]
+    async fn collect_gitlab_data_offline() {
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("GET", mockito::Matcher::Any)
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+        let repo_url = |name: &str| format!("{}/group/{name}", server.url());
+        let cached_repo = |days: i64| RepositoryGitData {
+            generated_at: Utc::now() - chrono::Duration::days(days),
+            stars: days,
+            ..Default::default()
+        };
+        let cached_data = GitData::from([
+            (repo_url("fresh"), cached_repo(1)),
+            (repo_url("expired"), cached_repo(365)),
+        ]);
+        cache.write(GITLAB_CACHE_FILE, &serde_json::to_vec(&cached_data).unwrap()).unwrap();
+        let landscape_data = LandscapeData {
+            items: vec![Item {
+                repositories: Some(
+                    ["fresh", "expired", "uncached"]
+                        .into_iter()
+                        .map(|name| Repository {
+                            url: repo_url(name),
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let args = GitLabArgs {
+            api: GitLabApi::Rest,
+            cache_ttl: DEFAULT_GITLAB_CACHE_TTL,
+            refresh_before: None,
+            report: false,
+            ndjson_output: None,
+            require_tokens: false,
+            strict_tokens: false,
+            allow_unauthenticated: false,
+            dry_run: false,
+            max_requests: None,
+            max_repos: None,
+            ignore_cache: false,
+            metadata_only_refresh: false,
+            offline: true,
+            progress: false,
+            filter: GitLabItemsFilter::default(),
+        };
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: server.url(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+
+        let gitlab_data = collect_gitlab_data_with_config(
+            &cache,
+            &landscape_data,
+            &args,
+            &instance_configs,
+            &BTreeMap::new(),
+            &ClientOptions::default(),
+            &[],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(gitlab_data.len(), 2);
+        assert_eq!(gitlab_data[&repo_url("fresh")].stars, 1);
+        assert_eq!(gitlab_data[&repo_url("expired")].stars, 365);
+        assert!(gitlab_data.values().all(|repo| repo.from_cache));
+        assert!(!gitlab_data.contains_key(&repo_url("uncached")));
+        api_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn create_instances_pools_require_tokens() {
+        let repos_by_instance = BTreeMap::from([(
+            "https://gitlab.com".to_string(),
+            vec!["https://gitlab.com/group/project".to_string()],
+        )]);
+
+        let result = create_instances_pools(
+            &repos_by_instance,
+            &[],
+            &BTreeMap::new(),
+            &ClientOptions::default(),
+            true,
+        )
+        .await;
+        let Err(err) = result else {
+            panic!("tokens should be required");
+        };
+        assert!(err.to_string().contains("https://gitlab.com"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn create_instances_pools_builds_clients_concurrently() {
+        // Three clients (two for the first instance and one for the second)
+        // must be created at the same time for them to succeed
+        let gate = Arc::new((std::sync::Mutex::new(0), std::sync::Condvar::new()));
+        let instance1_url = start_gated_server(gate.clone(), 3);
+        let instance2_url = start_gated_server(gate.clone(), 3);
+        let repos_by_instance = BTreeMap::from([
+            (
+                instance1_url.clone(),
+                vec![format!("{instance1_url}/group/project")],
+            ),
+            (
+                instance2_url.clone(),
+                vec![format!("{instance2_url}/group/project")],
+            ),
+        ]);
+        let instance_configs = [
+            GitlabInstanceConfig {
+                base_url: instance1_url.clone(),
+                tokens: vec!["token1".to_string(), "token2".to_string()],
+                cache_ttl_days: None,
+            },
+            GitlabInstanceConfig {
+                base_url: instance2_url.clone(),
+                tokens: vec!["token3".to_string()],
+                cache_ttl_days: None,
+            },
+        ];
+        let client_options = ClientOptions {
+            check_token_scopes: false,
+            ..Default::default()
+        };
+
+        let instance_pools = create_instances_pools(
+            &repos_by_instance,
+            &instance_configs,
+            &BTreeMap::new(),
+            &client_options,
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(instance_pools[&instance1_url].status().size, 2);
+        assert_eq!(instance_pools[&instance2_url].status().size, 1);
+        assert_eq!(*gate.0.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn create_instances_pools_skips_instances_without_tokens() {
+        let repos_by_instance = BTreeMap::from([(
+            "https://gitlab.com".to_string(),
+            vec!["https://gitlab.com/group/project".to_string()],
+        )]);
+
+        let instance_pools = create_instances_pools(
+            &repos_by_instance,
+            &[],
+            &BTreeMap::new(),
+            &ClientOptions::default(),
+            false,
+        )
+        .await
+        .unwrap();
+        assert!(instance_pools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn create_instances_pools_unauthenticated() {
+        let mut server = mockito::Server::new_async().await;
+        let user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+        let languages_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/languages")
+            .match_header("PRIVATE-TOKEN", mockito::Matcher::Missing)
+            .with_body(r#"{"Rust": 100.0}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let issues_statistics_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/issues_statistics")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+        let repos_by_instance =
+            BTreeMap::from([(server.url(), vec![format!("{}/group/project", server.url())])]);
+
+        // Unauthenticated clients are not used unless allowed
+        let instance_pools = create_instances_pools(
+            &repos_by_instance,
+            &[],
+            &BTreeMap::new(),
+            &ClientOptions::default(),
+            false,
+        )
+        .await
+        .unwrap();
+        assert!(instance_pools.is_empty());
+
+        let client_options = ClientOptions {
+            allow_unauthenticated: true,
+            ..Default::default()
+        };
+        let instance_pools =
+            create_instances_pools(&repos_by_instance, &[], &BTreeMap::new(), &client_options, false)
+                .await
+                .unwrap();
+        let gl_pool = &instance_pools[&server.url()];
+        assert_eq!(gl_pool.status().size, 1);
+        let gl = gl_pool.get().await.unwrap();
+        assert_eq!(
+            gl.get_languages("group/project").await.unwrap(),
+            Some(BTreeMap::from([("Rust".to_string(), 100.0)]))
+        );
+        assert_eq!(
+            gl.get_good_first_issues_count("group/project").await.unwrap(),
+            None
+        );
+
+        // Tokens are still required when requested
+        assert!(
+            create_instances_pools(&repos_by_instance, &[], &BTreeMap::new(), &client_options, true)
+                .await
+                .is_err()
+        );
+
+        user_mock.assert_async().await;
+        languages_mock.assert_async().await;
+        issues_statistics_mock.assert_async().await;
+    }
+
+    #[test]
+    fn collect_repositories_options_uses_normalized_urls() {
+        let repository = |url: &str, branch: Option<&str>| Repository {
+            url: url.to_string(),
+            branch: branch.map(ToString::to_string),
+            ..Default::default()
+        };
+        let group = |url: &str| Repository {
+            url: url.to_string(),
+            gitlab_group: Some(true),
+            ..Default::default()
+        };
+        let landscape_data = LandscapeData {
+            items: vec![
+                Item {
+                    repositories: Some(vec![
+                        repository("https://GitLab.com/group/project1.git", Some("release")),
+                        repository("https://gitlab.com/group/project2", None),
+                        repository("https://github.com/org/repo", Some("main")),
+                    ]),
+                    ..Default::default()
+                },
+                Item {
+                    repositories: Some(vec![
+                        repository("https://gitlab.com/group/project1", Some("other")),
+                        group("https://gitlab.com/Group2/"),
+                    ]),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            collect_repositories_options(&landscape_data),
+            HashMap::from([
+                (
+                    "https://gitlab.com/group/project1".to_string(),
+                    RepositoryOptions {
+                        branch: Some("release".to_string()),
+                        group: false,
+                    }
+                ),
+                (
+                    "https://gitlab.com/Group2".to_string(),
+                    RepositoryOptions {
+                        branch: None,
+                        group: true,
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn random_delay_within_bound() {
+        let max = Duration::from_millis(200);
+        for _ in 0..100 {
+            assert!(random_delay(max) <= max);
+        }
+        assert_eq!(random_delay(Duration::ZERO), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_uses_repository_branch() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project().returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                ..Default::default()
+            })))
+        });
+        gl.expect_get_first_commit()
+            .withf(|project_path, ref_| project_path == "group/project" && ref_ == "release")
+            .times(1)
+            .returning(|_, _| Box::pin(future::ready(Ok(None))));
+        gl.expect_get_latest_commit()
+            .withf(|project_path, ref_| project_path == "group/project" && ref_ == "release")
+            .times(1)
+            .returning(|_, _| Box::pin(future::ready(Ok(None))));
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let urls = ["https://gitlab.com/group/project".to_string()];
+        let repos_options = HashMap::from([(
+            "https://gitlab.com/group/project".to_string(),
+            RepositoryOptions {
+                branch: Some("release".to_string()),
+                group: false,
+            },
+        )]);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &repos_options,
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        assert_eq!(
+            instance_data["https://gitlab.com/group/project"].as_ref().unwrap().contributors.url,
+            "https://gitlab.com/group/project/-/graphs/release?ref_type=heads"
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_refetches_when_branch_changes() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project().times(1).returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                ..Default::default()
+            })))
+        });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        // Both repositories have a valid cache entry, but the branch of the
+        // first one has changed since it was collected
+        let urls = [
+            "https://gitlab.com/group/changed".to_string(),
+            "https://gitlab.com/group/unchanged".to_string(),
+        ];
+        let cached_repo = |branch: Option<&str>| RepositoryGitData {
+            branch: branch.map(ToString::to_string),
+            generated_at: Utc::now(),
+            ..Default::default()
+        };
+        let cached_data = GitData::from([
+            (urls[0].clone(), cached_repo(None)),
+            (urls[1].clone(), cached_repo(Some("release"))),
+        ]);
+        let repos_options: HashMap<_, _> = urls
+            .iter()
+            .map(|url| {
+                let options = RepositoryOptions {
+                    branch: Some("release".to_string()),
+                    group: false,
+                };
+                (url.clone(), options)
+            })
+            .collect();
+        let stats = Mutex::new(GitlabCollectionStats::default());
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &repos_options,
+                cached_data: Some(&cached_data),
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &stats,
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        let changed = instance_data[&urls[0]].as_ref().unwrap();
+        assert!(!changed.from_cache);
+        assert_eq!(changed.branch.as_deref(), Some("release"));
+        assert!(instance_data[&urls[1]].as_ref().unwrap().from_cache);
+        let stats = stats.into_inner().unwrap();
+        assert_eq!((stats.cache_hits, stats.fresh_fetches), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_records_repository_span() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::UNAUTHORIZED)))));
+        let gl: DynGL = Box::new(gl);
+        let gl_pool = Pool::from(vec![gl]);
+
+        let (logs, _guard) = capture_logs(tracing::Level::DEBUG);
+        let urls = ["https://gitlab.com/group/project".to_string()];
+        collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        let logs = logs.logs();
+        let failure_log = logs
+            .lines()
+            .find(|line| line.contains("check the gitlab tokens"))
+            .expect("failure to be logged");
+        assert!(
+            failure_log.contains(
+                "gitlab_repository{url=https://gitlab.com/group/project instance=https://gitlab.com}"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_retries_with_another_token() {
+        // Clients are taken from the pool in reverse order, so the first one
+        // used is the one whose token has been revoked
+        let mut gl_ok = MockGL::new();
+        gl_ok.expect_get_project().times(1).returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                ..Default::default()
+            })))
+        });
+        let gl_ok: DynGL = Box::new(add_default_expectations(gl_ok));
+        let mut gl_revoked = MockGL::new();
+        gl_revoked
+            .expect_get_project()
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::UNAUTHORIZED)))));
+        let gl_revoked: DynGL = Box::new(gl_revoked);
+        let gl_pool = Pool::from(vec![gl_ok, gl_revoked]);
+
+        let urls = ["https://gitlab.com/group/project".to_string()];
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        assert!(instance_data["https://gitlab.com/group/project"].is_ok());
+        assert_eq!(gl_pool.status().size, 1);
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_retries_rate_limited_with_another_token() {
+        // Clients are taken from the pool in reverse order, so the first one
+        // used is the one whose token has been rate limited
+        let mut gl_ok = MockGL::new();
+        gl_ok.expect_get_project().times(1).returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                ..Default::default()
+            })))
+        });
+        let gl_ok: DynGL = Box::new(add_default_expectations(gl_ok));
+        let mut gl_rate_limited = MockGL::new();
+        gl_rate_limited
+            .expect_get_project()
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::TOO_MANY_REQUESTS)))));
+        let gl_rate_limited: DynGL = Box::new(gl_rate_limited);
+        let gl_pool = Pool::from(vec![gl_ok, gl_rate_limited]);
+
+        let urls = ["https://gitlab.com/group/project".to_string()];
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        // The rate limited client is kept in the pool
+        assert!(instance_data["https://gitlab.com/group/project"].is_ok());
+        assert_eq!(gl_pool.status().size, 2);
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_keeps_last_client_on_token_error() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::UNAUTHORIZED)))));
+        let gl: DynGL = Box::new(gl);
+        let gl_pool = Pool::from(vec![gl]);
+
+        let urls = ["https://gitlab.com/group/project".to_string()];
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        assert!(instance_data["https://gitlab.com/group/project"].is_err());
+        assert_eq!(gl_pool.status().size, 1);
+    }
+
+    #[tokio::test]
+    async fn stream_instance_data_yields_each_repository() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project().times(3).returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                ..Default::default()
+            })))
+        });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let urls: Vec<String> = (1..=3).map(|i| format!("https://gitlab.com/group/project{i}")).collect();
+        let repos_options = HashMap::new();
+        let failed_fetches = FailedFetches::new();
+        let stats = Mutex::new(GitlabCollectionStats::default());
+        let latencies = Mutex::new(FetchLatencies::default());
+        let progress = ProgressBar::hidden();
+        let collection_options = GitlabCollectionOptions::default();
+        let mut items = Box::pin(stream_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &repos_options,
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &failed_fetches,
+                stats: &stats,
+                latencies: &latencies,
+                progress: &progress,
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &collection_options,
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        ));
+
+        let mut count = 0;
+        while let Some((url, result)) = items.next().await {
+            assert!(urls.contains(&url));
+            assert!(result.is_ok());
+            count += 1;
+        }
+        drop(items);
+        assert_eq!(count, 3);
+        assert_eq!(progress.position(), 3);
+        assert_eq!(stats.into_inner().unwrap().fresh_fetches, 3);
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_skips_repositories_when_circuit_breaker_opens() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .times(2)
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::SERVICE_UNAVAILABLE)))));
+        let gl: DynGL = Box::new(gl);
+        let gl_pool = Pool::from(vec![gl]);
+
+        let urls: Vec<String> = (1..=4).map(|i| format!("https://gitlab.com/group/project{i}")).collect();
+        let stats = Mutex::new(GitlabCollectionStats::default());
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &stats,
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig {
+                    threshold: 2,
+                    reset_after: None,
+                },
+            },
+            &urls,
+        )
+        .await;
+
+        assert_eq!(instance_data.len(), 4);
+        assert!(instance_data.values().all(Result::is_err));
+        let stats = stats.into_inner().unwrap();
+        assert_eq!(stats.skipped_circuit_open, 2);
+        assert_eq!(stats.failures, BTreeMap::from([("status_503".to_string(), 2)]));
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_aggregates_groups() {
+        let mut gl = MockGL::new();
+        gl.expect_get_group_projects()
+            .withf(|group_path| group_path == "group")
+            .times(1)
+            .returning(|_| {
+                Box::pin(future::ready(Ok(vec![
+                    "group/project1".to_string(),
+                    "group/subgroup/project2".to_string(),
+                ])))
+            });
+        gl.expect_get_project().times(2).returning(|project_path| {
+            let project_path = project_path.to_string();
+            Box::pin(async move {
+                Ok(GitLabProject {
+                    star_count: if project_path == "group/project1" { 10 } else { 5 },
+                    topics: vec![project_path],
+                    ..Default::default()
+                })
+            })
+        });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let urls = ["https://gitlab.com/group".to_string()];
+        let repos_options = HashMap::from([(
+            "https://gitlab.com/group".to_string(),
+            RepositoryOptions {
+                branch: None,
+                group: true,
+            },
+        )]);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &repos_options,
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        let group_data = instance_data["https://gitlab.com/group"].as_ref().unwrap();
+        assert_eq!(group_data.stars, 15);
+        assert_eq!(
+            group_data.topics,
+            vec![
+                "group/project1".to_string(),
+                "group/subgroup/project2".to_string()
+            ]
+        );
+        assert_eq!(group_data.contributors.count, 2);
+        assert_eq!(group_data.url, "https://gitlab.com/group");
+    }
+
+    #[tokio::test]
+    async fn collect_group_data_skips_failed_projects() {
+        let mut gl = MockGL::new();
+        gl.expect_get_group_projects().times(1).returning(|_| {
+            Box::pin(future::ready(Ok(vec![
+                "group/project1".to_string(),
+                "group/project2".to_string(),
+                "group/project3".to_string(),
+            ])))
+        });
+        gl.expect_get_project().times(3).returning(|project_path| {
+            let project_path = project_path.to_string();
+            Box::pin(async move {
+                if project_path == "group/project2" {
+                    return Err(api_error(StatusCode::NOT_FOUND));
+                }
+                Ok(GitLabProject {
+                    star_count: 10,
+                    topics: vec![project_path],
+                    ..Default::default()
+                })
+            })
+        });
+        let gl = gl_object(add_default_expectations(gl)).await;
+
+        let group_data = collect_group_data(
+            &gl,
+            "https://gitlab.com",
+            "https://gitlab.com/group",
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(group_data.stars, 20);
+        assert_eq!(
+            group_data.topics,
+            vec!["group/project1".to_string(), "group/project3".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_group_data_fails_when_all_projects_fail() {
+        let mut gl = MockGL::new();
+        gl.expect_get_group_projects()
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Ok(vec!["group/project1".to_string()]))));
+        gl.expect_get_project()
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::NOT_FOUND)))));
+        let gl = gl_object(add_default_expectations(gl)).await;
+
+        let result = collect_group_data(
+            &gl,
+            "https://gitlab.com",
+            "https://gitlab.com/group",
+            &GitlabCollectionOptions::default(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn collect_group_data_propagates_token_errors() {
+        let mut gl = MockGL::new();
+        gl.expect_get_group_projects().times(1).returning(|_| {
+            Box::pin(future::ready(Ok(vec![
+                "group/project1".to_string(),
+                "group/project2".to_string(),
+            ])))
+        });
+        gl.expect_get_project().returning(|project_path| {
+            let project_path = project_path.to_string();
+            Box::pin(async move {
+                if project_path == "group/project2" {
+                    return Err(api_error(StatusCode::UNAUTHORIZED));
+                }
+                Ok(GitLabProject::default())
+            })
+        });
+        let gl = gl_object(add_default_expectations(gl)).await;
+
+        let err = collect_group_data(
+            &gl,
+            "https://gitlab.com",
+            "https://gitlab.com/group",
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(is_token_error(&err));
+    }
+
+    #[test]
+    fn aggregate_projects_data_sums_and_merges() {
+        let commit = |days_ago: i64, id: &str| Commit {
+            ts: Some(Utc::now() - chrono::Duration::days(days_ago)),
+            url: format!("https://gitlab.com/group/project/-/commit/{id}"),
+            ..Default::default()
+        };
+        let project1 = RepositoryGitData {
+            first_commit: Some(commit(100, "first1")),
+            latest_commit: commit(10, "latest1"),
+            open_issues: Some(3),
+            stars: 10,
+            topics: vec!["a".to_string(), "b".to_string()],
+            ..Default::default()
+        };
+        let project2 = RepositoryGitData {
+            first_commit: Some(commit(200, "first2")),
+            latest_commit: commit(1, "latest2"),
+            open_issues: None,
+            stars: 5,
+            topics: vec!["b".to_string(), "c".to_string()],
+            ..Default::default()
+        };
+
+        let group_data = aggregate_projects_data("https://gitlab.com/group", &[project1, project2.clone()]);
+        assert_eq!(group_data.first_commit, project2.first_commit);
+        assert_eq!(group_data.latest_commit, project2.latest_commit);
+        assert_eq!(group_data.open_issues, Some(3));
+        assert_eq!(group_data.open_merge_requests, None);
+        assert_eq!(group_data.stars, 15);
+        assert_eq!(
+            group_data.topics,
+            vec!["a".to_string(), "b".to_string(), "c".to_string()]
+        );
+        assert_eq!(group_data.url, "https://gitlab.com/group");
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_pool_exhausted() {
+        let urls = [
+            "https://gitlab.com/group/project1".to_string(),
+            "https://gitlab.com/group/project2".to_string(),
+        ];
+        let stats = Mutex::new(GitlabCollectionStats::default());
+
+        // Pool without clients left
+        let empty_pool: Pool<DynGL> = Pool::from(vec![]);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&empty_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &stats,
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+        assert_eq!(instance_data.len(), 2);
+        assert!(instance_data.values().all(Result::is_err));
+
+        // Closed pool
+        let gl: DynGL = Box::new(MockGL::new());
+        let closed_pool = Pool::from(vec![gl]);
+        closed_pool.close();
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&closed_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &stats,
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+        assert_eq!(instance_data.len(), 2);
+        assert!(instance_data.values().all(Result::is_err));
+
+        let stats = stats.into_inner().unwrap();
+        assert_eq!(stats.fresh_fetches, 0);
+        assert_eq!(stats.failures, BTreeMap::from([("other".to_string(), 4)]));
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_single_token_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let mut gl = MockGL::new();
+        gl.expect_get_project().times(3).returning({
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            move |_| {
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_in_flight.clone();
+                Box::pin(async move {
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok(GitLabProject::default())
+                })
+            }
+        });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let urls = [
+            "https://gitlab.com/group/project1",
+            "https://gitlab.com/group/project2",
+            "https://gitlab.com/group/project3",
+        ]
+        .map(ToString::to_string);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        assert_eq!(instance_data.len(), 3);
+        assert!(instance_data.values().all(Result::is_ok));
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_refreshes_expired_cached_entries() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .withf(|project_path| project_path == "group/expired")
+            .times(1)
+            .returning(|_| {
+                Box::pin(future::ready(Ok(GitLabProject {
+                    description: Some("fresh".to_string()),
+                    ..Default::default()
+                })))
+            });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let cached_repo = |description: &str, age: chrono::Duration| RepositoryGitData {
+            generated_at: Utc::now() - age,
+            description: description.to_string(),
+            ..Default::default()
+        };
+        let cached_data = GitData::from([
+            (
+                "https://gitlab.com/group/expired".to_string(),
+                cached_repo("cached", chrono::Duration::days(2)),
+            ),
+            (
+                "https://gitlab.com/group/fresh".to_string(),
+                cached_repo("cached", chrono::Duration::hours(1)),
+            ),
+        ]);
+
+        let urls = [
+            "https://gitlab.com/group/expired",
+            "https://gitlab.com/group/fresh",
+        ]
+        .map(ToString::to_string);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: Some(&cached_data),
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        let description = |url: &str| instance_data[url].as_ref().unwrap().description.clone();
+        assert_eq!(description("https://gitlab.com/group/expired"), "fresh");
+        assert_eq!(description("https://gitlab.com/group/fresh"), "cached");
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_refreshes_entries_cached_before_instant() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .withf(|project_path| project_path == "group/expired" || project_path == "group/old")
+            .times(2)
+            .returning(|_| {
+                Box::pin(future::ready(Ok(GitLabProject {
+                    description: Some("fresh".to_string()),
+                    ..Default::default()
+                })))
+            });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        // Entries not expired yet are refreshed only if they were generated
+        // before the refresh instant, whereas expired ones are always
+        // refreshed
+        let cached_repo = |age: chrono::Duration| RepositoryGitData {
+            generated_at: Utc::now() - age,
+            description: "cached".to_string(),
+            ..Default::default()
+        };
+        let cached_data = GitData::from([
+            (
+                "https://gitlab.com/group/expired".to_string(),
+                cached_repo(chrono::Duration::days(8)),
+            ),
+            (
+                "https://gitlab.com/group/old".to_string(),
+                cached_repo(chrono::Duration::days(3)),
+            ),
+            (
+                "https://gitlab.com/group/recent".to_string(),
+                cached_repo(chrono::Duration::hours(1)),
+            ),
+        ]);
+
+        let urls = [
+            "https://gitlab.com/group/expired",
+            "https://gitlab.com/group/old",
+            "https://gitlab.com/group/recent",
+        ]
+        .map(ToString::to_string);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: Some(&cached_data),
+                cache_ttl: chrono::Duration::days(7),
+                refresh_before: Some(Utc::now() - chrono::Duration::days(1)),
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        let description = |url: &str| instance_data[url].as_ref().unwrap().description.clone();
+        assert_eq!(description("https://gitlab.com/group/expired"), "fresh");
+        assert_eq!(description("https://gitlab.com/group/old"), "fresh");
+        assert_eq!(description("https://gitlab.com/group/recent"), "cached");
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_metadata_only_refresh() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project().times(1).returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                description: Some("New description".to_string()),
+                star_count: 20,
+                web_url: "https://gitlab.com/group/project".to_string(),
+                ..Default::default()
+            })))
+        });
+        let gl: DynGL = Box::new(gl);
+        let gl_pool = Pool::from(vec![gl]);
+
+        let generated_at = Utc::now() - chrono::Duration::days(30);
+        let cached_data = GitData::from([(
+            "https://gitlab.com/group/project".to_string(),
+            RepositoryGitData {
+                contributors: DataContributors {
+                    count: 7,
+                    url: "https://gitlab.com/group/project/-/graphs/main?ref_type=heads".to_string(),
+                },
+                description: "Old description".to_string(),
+                generated_at,
+                open_issues: Some(3),
+                stars: 10,
+                ..Default::default()
+            },
+        )]);
+
+        let urls = ["https://gitlab.com/group/project".to_string()];
+        let collection_options = GitlabCollectionOptions {
+            metadata_only_refresh: true,
+            ..Default::default()
+        };
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: Some(&cached_data),
+                cache_ttl: chrono::Duration::days(7),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &collection_options,
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        // Only the project endpoint is used: the metadata is refreshed and
+        // the rest of the data is kept from the cache
+        let repo = instance_data["https://gitlab.com/group/project"].as_ref().unwrap();
+        assert!(repo.generated_at > generated_at);
+        assert!(!repo.from_cache);
+        assert_eq!(repo.description, "New description");
+        assert_eq!(repo.stars, 20);
+        assert_eq!(repo.contributors.count, 7);
+        assert_eq!(repo.open_issues, Some(3));
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_metadata_only_refresh_collects_uncached_repositories() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project().returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                ..Default::default()
+            })))
+        });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let urls = ["https://gitlab.com/group/project".to_string()];
+        let collection_options = GitlabCollectionOptions {
+            metadata_only_refresh: true,
+            ..Default::default()
+        };
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(7),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &collection_options,
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        let repo = instance_data["https://gitlab.com/group/project"].as_ref().unwrap();
+        assert_eq!(repo.contributors.count, 1);
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_marks_cached_entries() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .withf(|project_path| project_path == "group/expired")
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Ok(GitLabProject::default()))));
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let cached_repo = |age: chrono::Duration| RepositoryGitData {
+            generated_at: Utc::now() - age,
+            ..Default::default()
+        };
+        let cached_data = GitData::from([
+            (
+                "https://gitlab.com/group/expired".to_string(),
+                cached_repo(chrono::Duration::days(2)),
+            ),
+            (
+                "https://gitlab.com/group/fresh".to_string(),
+                cached_repo(chrono::Duration::hours(1)),
+            ),
+        ]);
+
+        let urls = [
+            "https://gitlab.com/group/expired",
+            "https://gitlab.com/group/fresh",
+        ]
+        .map(ToString::to_string);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: Some(&cached_data),
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        let from_cache = |url: &str| instance_data[url].as_ref().unwrap().from_cache;
+        assert!(!from_cache("https://gitlab.com/group/expired"));
+        assert!(from_cache("https://gitlab.com/group/fresh"));
+
+        // The flag is never written to the cache
+        let repo = instance_data["https://gitlab.com/group/fresh"].as_ref().unwrap();
+        assert!(!serde_json::to_string(repo).unwrap().contains("from_cache"));
+    }
+
+    #[tokio::test]
+    async fn collect_repository_data_from_pool_single_client() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .withf(|project_path| project_path == "group/project")
+            .times(1)
+            .returning(|_| {
+                Box::pin(future::ready(Ok(GitLabProject {
+                    description: Some("description".to_string()),
+                    star_count: 42,
+                    web_url: "https://gitlab.com/group/project".to_string(),
+                    ..Default::default()
+                })))
+            });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let repo_data = collect_repository_data_from_pool(
+            &gl_pool,
+            "https://gitlab.com",
+            "https://gitlab.com/group/project",
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo_data.description, "description");
+        assert_eq!(repo_data.stars, 42);
+        assert_eq!(repo_data.url, "https://gitlab.com/group/project");
+        assert!(!repo_data.from_cache);
+    }
+
+    #[tokio::test]
+    async fn collect_single_repository_data_invalid_input() {
+        let base_url_aliases = BTreeMap::new();
+        let client_options = ClientOptions::default();
+        let collect = |repo_url| {
+            collect_single_repository_data_with_config(
+                repo_url,
+                None,
+                &[],
+                &base_url_aliases,
+                &client_options,
+            )
+        };
+
+        let err = collect("https://github.com/org/repo").await.unwrap_err();
+        assert!(err.to_string().contains("invalid gitlab repository url"), "{err}");
+
+        let err = collect("https://gitlab.com/group/project").await.unwrap_err();
+        assert!(err.to_string().contains("no valid gitlab token"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn collect_single_repository_data_uses_client_options_and_aliases() {
+        let mut server = mockito::Server::new_async().await;
+        let user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .match_header("user-agent", "custom-agent")
+            .with_body("{}")
+            .expect_at_least(1)
+            .create_async()
+            .await;
+        let project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .match_header("user-agent", "custom-agent")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+        let instance_configs = [GitlabInstanceConfig {
+            base_url: "https://gitlab.example.com".to_string(),
+            tokens: vec!["token".to_string()],
+            cache_ttl_days: None,
+        }];
+        let base_url_aliases = BTreeMap::from([("https://gitlab.example.com".to_string(), server.url())]);
+        let client_options = ClientOptions {
+            check_token_scopes: false,
+            retry_config: RetryConfig {
+                max_retries: 0,
+                ..Default::default()
+            },
+            user_agent: "custom-agent".to_string(),
+            ..Default::default()
+        };
+
+        let err = collect_single_repository_data_with_config(
+            "https://gitlab.example.com/group/project",
+            None,
+            &instance_configs,
+            &base_url_aliases,
+            &client_options,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_status(&err), Some(404), "{err:#}");
+
+        user_mock.assert_async().await;
+        project_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_progress_does_not_alter_results() {
+        let cached_data = GitData::from([
+            (
+                "https://gitlab.com/group/project1".to_string(),
+                RepositoryGitData {
+                    generated_at: Utc::now(),
+                    stars: 10,
+                    ..Default::default()
+                },
+            ),
+            (
+                "https://gitlab.com/group/project2".to_string(),
+                RepositoryGitData {
+                    generated_at: Utc::now(),
+                    stars: 20,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let urls = [
+            "https://gitlab.com/group/project1",
+            "https://gitlab.com/group/project2",
+            "https://gitlab.com/group/project3",
+        ]
+        .map(ToString::to_string);
+
+        let collect = |progress: ProgressBar| {
+            let cached_data = &cached_data;
+            let urls = &urls;
+            async move {
+                let instance_data = collect_instance_data(
+                    InstanceCollectionContext {
+                        gl_pool: None,
+                        base_url: "https://gitlab.com",
+                        repos_options: &HashMap::new(),
+                        cached_data: Some(cached_data),
+                        cache_ttl: chrono::Duration::days(1),
+                        refresh_before: None,
+                        failed_fetches: &FailedFetches::new(),
+                        stats: &Mutex::new(GitlabCollectionStats::default()),
+                        latencies: &Mutex::new(FetchLatencies::default()),
+                        progress: &progress,
+                        startup_jitter: Duration::ZERO,
+                        github: None,
+                        collection_options: &GitlabCollectionOptions::default(),
+                        circuit_breaker: CircuitBreakerConfig::default(),
+                    },
+                    urls,
+                )
+                .await;
+                let instance_data: BTreeMap<String, Option<RepositoryGitData>> =
+                    instance_data.into_iter().map(|(url, result)| (url, result.ok())).collect();
+                (instance_data, progress.position())
+            }
+        };
+
+        let (without_progress, _) = collect(ProgressBar::hidden()).await;
+        let (with_progress, position) = collect(new_progress_bar(true, urls.len())).await;
+        assert_eq!(with_progress, without_progress);
+        assert_eq!(with_progress.values().flatten().count(), 2);
+        assert_eq!(position, 3);
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_skips_recent_failed_fetches() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .withf(|project_path| project_path == "group/expired")
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Ok(GitLabProject::default()))));
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let failed_fetch = |age: chrono::Duration| FailedFetch {
+            failed_at: Utc::now() - age,
+            status: 404,
+        };
+        let failed_fetches = FailedFetches::from([
+            (
+                "https://gitlab.com/group/expired".to_string(),
+                failed_fetch(chrono::Duration::hours(GITLAB_ERRORS_CACHE_TTL + 1)),
+            ),
+            (
+                "https://gitlab.com/group/recent".to_string(),
+                failed_fetch(chrono::Duration::hours(1)),
+            ),
+        ]);
+
+        let urls = [
+            "https://gitlab.com/group/expired",
+            "https://gitlab.com/group/recent",
+        ]
+        .map(ToString::to_string);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &failed_fetches,
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        assert!(instance_data["https://gitlab.com/group/expired"].is_ok());
+        assert!(instance_data["https://gitlab.com/group/recent"].is_err());
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_records_stats() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project()
+            .withf(|project_path| project_path == "group/not-found")
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::NOT_FOUND)))));
+        gl.expect_get_project()
+            .withf(|project_path| project_path == "group/unavailable")
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::SERVICE_UNAVAILABLE)))));
+        gl.expect_get_project()
+            .returning(|_| Box::pin(future::ready(Ok(GitLabProject::default()))));
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let cached_data = GitData::from([(
+            "https://gitlab.com/group/cached".to_string(),
+            RepositoryGitData {
+                generated_at: Utc::now(),
+                ..Default::default()
+            },
+        )]);
+        let failed_fetches = FailedFetches::from([(
+            "https://gitlab.com/group/failed-recently".to_string(),
+            FailedFetch {
+                failed_at: Utc::now(),
+                status: 404,
+            },
+        )]);
+        let stats = Mutex::new(GitlabCollectionStats::default());
+        let latencies = Mutex::new(FetchLatencies::default());
+
+        let urls = [
+            "https://gitlab.com/group/cached",
+            "https://gitlab.com/group/failed-recently",
+            "https://gitlab.com/group/fresh1",
+            "https://gitlab.com/group/fresh2",
+            "https://gitlab.com/group/not-found",
+            "https://gitlab.com/group/unavailable",
+        ]
+        .map(ToString::to_string);
+        let ctx = InstanceCollectionContext {
+            gl_pool: Some(&gl_pool),
+            base_url: "https://gitlab.com",
+            repos_options: &HashMap::new(),
+            cached_data: Some(&cached_data),
+            cache_ttl: chrono::Duration::days(1),
+            refresh_before: None,
+            failed_fetches: &failed_fetches,
+            stats: &stats,
+            latencies: &latencies,
+            progress: &ProgressBar::hidden(),
+            startup_jitter: Duration::ZERO,
+            github: None,
+            collection_options: &GitlabCollectionOptions::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+        };
+        collect_instance_data(ctx, &urls).await;
+        let urls = ["https://gitlab.example.com/group/project".to_string()];
+        let no_failed_fetches = FailedFetches::new();
+        let ctx = InstanceCollectionContext {
+            gl_pool: None,
+            cached_data: None,
+            failed_fetches: &no_failed_fetches,
+            ..ctx
+        };
+        collect_instance_data(ctx, &urls).await;
+
+        // Only the repositories actually fetched are timed
+        let latencies = latencies.into_inner().unwrap().summary();
+        assert_eq!(latencies.len(), 1);
+        assert_eq!(latencies["https://gitlab.com"].count, 4);
+
+        let stats = stats.into_inner().unwrap();
+        assert_eq!(
+            stats,
+            GitlabCollectionStats {
+                total: 7,
+                cache_hits: 1,
+                fresh_fetches: 2,
+                skipped_no_token: 1,
+                skipped_failed_recently: 1,
+                failures: BTreeMap::from([("status_404".to_string(), 1), ("status_503".to_string(), 1),]),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            stats.total,
+            stats.cache_hits
+                + stats.fresh_fetches
+                + stats.skipped_no_token
+                + stats.skipped_failed_recently
+                + stats.failures.values().sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn fetch_latencies_summary() {
+        let mut latencies = FetchLatencies::default();
+        for millis in (1..=100).rev() {
+            latencies.record("https://gitlab.com", Duration::from_millis(millis));
+        }
+        latencies.record("https://gitlab.example.com", Duration::from_millis(250));
+        latencies.record("https://gitlab.example.com", Duration::from_millis(750));
+
+        let summary = latencies.summary();
+        assert_eq!(
+            summary,
+            BTreeMap::from([
+                (
+                    "https://gitlab.com".to_string(),
+                    LatencySummary {
+                        count: 100,
+                        average: Duration::from_micros(50_500),
+                        p95: Duration::from_millis(95),
+                    }
+                ),
+                (
+                    "https://gitlab.example.com".to_string(),
+                    LatencySummary {
+                        count: 2,
+                        average: Duration::from_millis(500),
+                        p95: Duration::from_millis(750),
+                    }
+                ),
+            ])
+        );
+        assert_eq!(
+            summary["https://gitlab.com"].to_string(),
+            "100 fetches (average 50ms, p95 95ms)"
+        );
+    }
+
+    #[test]
+    fn latency_summary_single_and_no_durations() {
+        let summary = LatencySummary::new(&[Duration::from_millis(10)]).unwrap();
+        assert_eq!(summary.average, Duration::from_millis(10));
+        assert_eq!(summary.p95, Duration::from_millis(10));
+        assert!(LatencySummary::new(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_request_budget_exhausted() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let projects_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/api/v4/projects/".into()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let options = ClientOptions {
+            request_budget: Arc::new(RequestBudget::new(Some(0))),
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        let gl: DynGL = Box::new(GLRetry::new(Box::new(gl), test_retry_config(0)));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let expired_repo = RepositoryGitData {
+            generated_at: Utc::now() - chrono::Duration::days(2),
+            stars: 10,
+            ..Default::default()
+        };
+        let cached_data = GitData::from([(
+            "https://gitlab.com/group/expired".to_string(),
+            expired_repo.clone(),
+        )]);
+        let stats = Mutex::new(GitlabCollectionStats::default());
+
+        let urls = [
+            "https://gitlab.com/group/expired",
+            "https://gitlab.com/group/uncached",
+        ]
+        .map(ToString::to_string);
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: Some(&cached_data),
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &stats,
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+        projects_mock.assert_async().await;
+
+        assert_eq!(
+            instance_data["https://gitlab.com/group/expired"].as_ref().unwrap(),
+            &RepositoryGitData {
+                from_cache: true,
+                ..expired_repo
+            }
+        );
+        assert!(
+            underlying_error(instance_data["https://gitlab.com/group/uncached"].as_ref().unwrap_err())
+                .is::<RequestBudgetExhausted>()
+        );
+        let stats = stats.into_inner().unwrap();
+        assert_eq!(
+            stats,
+            GitlabCollectionStats {
+                total: 2,
+                expired_cache_hits: 1,
+                skipped_budget_exhausted: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_request_budget_exhausted_during_operation() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let project_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{
+                    "default_branch": "main",
+                    "path_with_namespace": "group/project",
+                    "star_count": 10,
+                    "web_url": "https://gitlab.com/group/project"
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let contributors_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/contributors")
+            .match_query(mockito::Matcher::Any)
+            .with_header("X-Total", "0")
+            .with_body("[]")
+            .expect_at_most(1)
+            .create_async()
+            .await;
+
+        // The repository has 5 commits and the X-Total header is not returned,
+        // so getting the first commit requires probing several pages
+        let commits_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/repository/commits")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_request(|request| {
+                let page: usize = request
+                    .path_and_query()
+                    .split(['?', '&'])
+                    .find_map(|param| param.strip_prefix("page="))
+                    .and_then(|page| page.parse().ok())
+                    .unwrap_or(1);
+                if page > 5 {
+                    return b"[]".to_vec();
+                }
+                br#"[{"web_url": "https://gitlab.com/group/project/-/commit/1", "committed_date": "2024-01-01T00:00:00Z"}]"#
+                    .to_vec()
+            })
+            .expect_at_most(3)
+            .create_async()
+            .await;
+
+        // The budget is exhausted while getting the first commit, as each of
+        // the pages requested uses one request from it
+        let collection_options = GitlabCollectionOptions {
+            commits_last_year: false,
+            good_first_issues: false,
+            help_wanted_issues: false,
+            languages: false,
+            latest_commit: false,
+            latest_pipeline_status: false,
+            latest_release: false,
+            open_issues: false,
+            open_merge_requests: false,
+            ..Default::default()
+        };
+        let options = ClientOptions {
+            collection: collection_options,
+            request_budget: Arc::new(RequestBudget::new(Some(4))),
+            ..Default::default()
+        };
+        let gl = GLApi::new(&server.url(), "token", &options).await.unwrap();
+        let gl: DynGL = Box::new(GLRetry::new(Box::new(gl), test_retry_config(0)));
+        let gl_pool = Pool::from(vec![gl]);
+        let stats = Mutex::new(GitlabCollectionStats::default());
+
+        let urls = ["https://gitlab.com/group/project".to_string()];
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &stats,
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &collection_options,
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+
+        assert!(
+            underlying_error(instance_data["https://gitlab.com/group/project"].as_ref().unwrap_err())
+                .is::<RequestBudgetExhausted>()
+        );
+        assert_eq!(stats.into_inner().unwrap().skipped_budget_exhausted, 1);
+        project_mock.assert_async().await;
+        contributors_mock.assert_async().await;
+        commits_mock.assert_async().await;
+    }
+
+    #[test]
+    fn process_results_output_does_not_depend_on_results_order() {
+        let generated_at = Utc::now();
+        let results = || {
+            vec![
+                (
+                    "https://gitlab.com/group/b".to_string(),
+                    Ok(RepositoryGitData {
+                        generated_at,
+                        languages: Some(BTreeMap::from([("Rust".to_string(), 10), ("Go".to_string(), 5)])),
+                        topics: vec!["topic1".to_string(), "topic2".to_string()],
+                        ..Default::default()
+                    }),
+                ),
+                (
+                    "https://gitlab.com/group/a".to_string(),
+                    Ok(RepositoryGitData {
+                        generated_at,
+                        ..Default::default()
+                    }),
+                ),
+                (
+                    "https://gitlab.com/group/d".to_string(),
+                    Err(api_error(StatusCode::NOT_FOUND).into()),
+                ),
+                (
+                    "https://gitlab.com/group/c".to_string(),
+                    Err(api_error(StatusCode::SERVICE_UNAVAILABLE).into()),
+                ),
+            ]
+        };
+        let collect = |results: Vec<(String, Result<RepositoryGitData>)>| {
+            let tmp_dir = tempfile::tempdir().unwrap();
+            let cache = Cache::new(Some(&tmp_dir.path().to_path_buf())).unwrap();
+            let failures = get_collection_failures(&results);
+            let (gitlab_data, _) = process_results(results, &FailedFetches::new());
+            cache
+                .write(
+                    GITLAB_CACHE_FILE,
+                    &serde_json::to_vec_pretty(&gitlab_data).unwrap(),
+                )
+                .unwrap();
+            let (_, cached_data) = cache.read(GITLAB_CACHE_FILE).unwrap().unwrap();
+            (cached_data, serde_json::to_vec_pretty(&failures).unwrap())
+        };
+
+        // Repositories are processed concurrently, so the results order may be
+        // different in each run
+        let mut reversed_results = results();
+        reversed_results.reverse();
+        let (cached_data, failures) = collect(results());
+        assert_eq!((cached_data.clone(), failures.clone()), collect(reversed_results));
+        assert_eq!((cached_data, failures), collect(results()));
+    }
+
+    #[test]
+    fn process_results_records_permanent_failures() {
+        let recent_failed_fetch = FailedFetch {
+            failed_at: Utc::now() - chrono::Duration::hours(1),
+            status: 410,
+        };
+        let cached_failed_fetches = FailedFetches::from([
+            (
+                "https://gitlab.com/group/skipped".to_string(),
+                recent_failed_fetch.clone(),
+            ),
+            (
+                "https://gitlab.com/group/expired".to_string(),
+                FailedFetch {
+                    failed_at: Utc::now() - chrono::Duration::hours(GITLAB_ERRORS_CACHE_TTL + 1),
+                    status: 404,
+                },
+            ),
+        ]);
+        let results = vec![
+            (
+                "https://gitlab.com/group/ok".to_string(),
+                Ok(RepositoryGitData::default()),
+            ),
+            (
+                "https://gitlab.com/group/not-found".to_string(),
+                Err(api_error(StatusCode::NOT_FOUND).into()),
+            ),
+            (
+                "https://gitlab.com/group/unavailable".to_string(),
+                Err(api_error(StatusCode::SERVICE_UNAVAILABLE).into()),
+            ),
+            (
+                "https://gitlab.com/group/skipped".to_string(),
+                Err(format_err!("skipped")),
+            ),
+            (
+                "https://gitlab.com/group/expired".to_string(),
+                Err(format_err!("no token configured for instance")),
+            ),
+        ];
+
+        let (gitlab_data, failed_fetches) = process_results(results, &cached_failed_fetches);
+
+        assert_eq!(
+            gitlab_data.keys().collect::<Vec<_>>(),
+            vec!["https://gitlab.com/group/ok"]
+        );
+        assert_eq!(
+            failed_fetches.keys().collect::<Vec<_>>(),
+            vec![
+                "https://gitlab.com/group/not-found",
+                "https://gitlab.com/group/skipped"
+            ]
+        );
+        assert_eq!(failed_fetches["https://gitlab.com/group/not-found"].status, 404);
+        assert_eq!(
+            failed_fetches["https://gitlab.com/group/skipped"],
+            recent_failed_fetch
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_warns_about_non_public_projects() {
+        let (logs, _guard) = capture_warnings();
+        for visibility in ["public", "private"] {
+            let gl = gl_object(add_default_expectations(MockGL::new())).await;
+            let gl_project = GitLabProject {
+                visibility: Some(visibility.to_string()),
+                ..Default::default()
+            };
+
+            let repo = collect_project_data(
+                &gl,
+                "https://gitlab.com",
+                &format!("group/{visibility}"),
+                gl_project,
+                None,
+                None,
+                &GitlabCollectionOptions::default(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(repo.visibility, Some(visibility.to_string()));
+        }
+
+        let logs = logs.logs();
+        assert!(logs.contains("https://gitlab.com/group/private is not public (visibility: private)"));
+        assert!(!logs.contains("group/public"));
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_first_commit_falls_back_to_created_at() {
+        let created_at: DateTime<Utc> = "2020-01-15T10:30:00Z".parse().unwrap();
+        let mut gl = MockGL::new();
+        gl.expect_get_first_commit().returning(|_, _| Box::pin(future::ready(Ok(None))));
+        let gl = gl_object(add_default_expectations(gl)).await;
+        let gl_project = GitLabProject {
+            created_at: Some(created_at),
+            web_url: "https://gitlab.com/group/project".to_string(),
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.project_created_at, Some(created_at));
+        assert_eq!(
+            repo.first_commit,
+            Some(Commit {
+                ts: Some(created_at),
+                url: "https://gitlab.com/group/project".to_string(),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_repository_size() {
+        let gl = gl_object(add_default_expectations(MockGL::new())).await;
+        let gl_project = GitLabProject {
+            statistics: Some(GitLabProjectStatistics {
+                repository_size: Some(1024),
+            }),
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.repository_size_bytes, Some(1024));
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_propagates_archived_and_forked() {
+        let gl = gl_object(add_default_expectations(MockGL::new())).await;
+        let gl_project = GitLabProject {
+            archived: true,
+            forked: true,
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(repo.archived);
+        assert!(repo.forked);
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_assembles_results() {
+        let first_commit = Commit {
+            ts: Some(Utc::now() - chrono::Duration::days(365)),
+            url: "https://gitlab.com/group/project/-/commit/first".to_string(),
+            ..Default::default()
+        };
+        let latest_commit = Commit {
+            ts: Some(Utc::now()),
+            url: "https://gitlab.com/group/project/-/commit/latest".to_string(),
+            ..Default::default()
+        };
+        let latest_release = landscape2_core::data::Release {
+            name: Some("v1.0.0".to_string()),
+            tag_name: Some("v1.0.0".to_string()),
+            ts: Some(Utc::now()),
+            url: "https://gitlab.com/group/project/-/releases/v1.0.0".to_string(),
+            assets_count: Some(4),
+        };
+
+        let mut gl = MockGL::new();
+        gl.expect_get_recent_commit_count()
+            .withf(|path, ref_, since| {
+                let one_year_ago = Utc::now() - chrono::Duration::days(365);
+                path == "group/project" && ref_ == "main" && (one_year_ago - *since).num_minutes().abs() < 1
+            })
+            .returning(|_, _, _| Box::pin(future::ready(Ok(Some(120)))));
+        gl.expect_get_weekly_commit_activity().returning(|_, _| Box::pin(future::ready(Ok(None))));
+        let contributors = GitLabContributors {
+            count: 25,
+            top: vec![
+                GitLabContributor {
+                    name: "user1".to_string(),
+                    commits: 5,
+                },
+                GitLabContributor {
+                    name: "user2".to_string(),
+                    commits: 20,
+                },
+            ],
+        };
+        gl.expect_get_contributors()
+            .returning(move |_| Box::pin(future::ready(Ok(contributors.clone()))));
+        let first_commit_clone = first_commit.clone();
+        gl.expect_get_first_commit()
+            .withf(|path, ref_| path == "group/project" && ref_ == "main")
+            .returning(move |_, _| Box::pin(future::ready(Ok(Some(first_commit_clone.clone())))));
+        gl.expect_get_good_first_issues_count()
+            .returning(|_| Box::pin(future::ready(Ok(Some(2)))));
+        gl.expect_get_help_wanted_count().returning(|_| Box::pin(future::ready(Ok(Some(6)))));
+        gl.expect_get_languages().returning(|_| {
+            Box::pin(future::ready(Ok(Some(BTreeMap::from([(
+                "Rust".to_string(),
+                100.0,
+            )])))))
+        });
+        let latest_commit_clone = latest_commit.clone();
+        gl.expect_get_latest_commit()
+            .withf(|path, ref_| path == "group/project" && ref_ == "main")
+            .returning(move |_, _| Box::pin(future::ready(Ok(Some(latest_commit_clone.clone())))));
+        let latest_release_clone = latest_release.clone();
+        gl.expect_get_latest_release()
+            .returning(move |_| Box::pin(future::ready(Ok(Some(latest_release_clone.clone())))));
+        gl.expect_get_open_issues_count().returning(|_| Box::pin(future::ready(Ok(Some(3)))));
+        gl.expect_get_latest_pipeline_status()
+            .withf(|path, ref_| path == "group/project" && ref_ == "main")
+            .returning(|_, _| Box::pin(future::ready(Ok(Some("success".to_string())))));
+        gl.expect_get_open_mrs_count().returning(|_| Box::pin(future::ready(Ok(Some(4)))));
+        let gl = gl_object(gl).await;
+        let gl_project = GitLabProject {
+            default_branch: "main".to_string(),
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.commits_last_year, Some(120));
+        assert_eq!(repo.contributors.count, 25);
+        assert_eq!(
+            repo.top_contributors.unwrap().iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            vec!["user2", "user1"]
+        );
+        assert_eq!(repo.first_commit, Some(first_commit));
+        assert_eq!(repo.good_first_issues, Some(2));
+        assert_eq!(repo.help_wanted_issues, Some(6));
+        assert_eq!(
+            repo.languages,
+            Some(BTreeMap::from([("Rust".to_string(), 100_000)]))
+        );
+        assert_eq!(repo.latest_commit, latest_commit);
+        assert_eq!(repo.latest_release, Some(latest_release));
+        assert_eq!(repo.open_issues, Some(3));
+        assert_eq!(repo.latest_pipeline_status, Some("success".to_string()));
+        assert_eq!(repo.open_merge_requests, Some(4));
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_fails_when_any_call_fails() {
+        let mut gl = MockGL::new();
+        gl.expect_get_latest_release()
+            .returning(|_| Box::pin(future::ready(Err(format_err!("error").into()))));
+        let gl = gl_object(add_default_expectations(gl)).await;
+
+        let result = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            GitLabProject::default(),
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn get_prereleases_filter_from_settings() {
+        let release = |tag_name: &str| GitLabRelease {
+            name: None,
+            tag_name: Some(tag_name.to_string()),
+            released_at: None,
+            created_at: None,
+            links: GitLabReleaseLinks { self_link: None },
+            assets: None,
+            upcoming_release: false,
+        };
+
+        let mut settings = LandscapeSettings::default();
+        assert!(get_prereleases_filter(&settings).unwrap().is_none());
+
+        // Default tags pattern
+        let mut gitlab = GitLabCollection {
+            skip_prereleases: true,
+            ..Default::default()
+        };
+        settings.collection = Some(Collection {
+            gitlab: Some(gitlab.clone()),
+            ..Default::default()
+        });
+        let filter = get_prereleases_filter(&settings).unwrap().unwrap();
+        for tag_name in ["v1.0.0-rc1", "v1.0.0-RC.2", "1.0.0-alpha", "v2.0.0_beta.1", "rc1"] {
+            assert!(filter.is_prerelease(&release(tag_name)), "{tag_name}");
+        }
+        for tag_name in ["v1.0.0", "alphabet-1.0.0", "source-1.0.0", "v1.0.0-arc"] {
+            assert!(!filter.is_prerelease(&release(tag_name)), "{tag_name}");
+        }
+
+        // Custom tags pattern
+        gitlab.prerelease_tags_pattern = Some("-dev$".to_string());
+        settings.collection.as_mut().unwrap().gitlab = Some(gitlab.clone());
+        let filter = get_prereleases_filter(&settings).unwrap().unwrap();
+        assert!(filter.is_prerelease(&release("v1.0.0-dev")));
+        assert!(!filter.is_prerelease(&release("v1.0.0-rc1")));
+
+        // Empty tags pattern: only upcoming releases are skipped
+        gitlab.prerelease_tags_pattern = Some(String::new());
+        settings.collection.as_mut().unwrap().gitlab = Some(gitlab.clone());
+        let filter = get_prereleases_filter(&settings).unwrap().unwrap();
+        assert!(!filter.is_prerelease(&release("v1.0.0-rc1")));
+        let upcoming_release = GitLabRelease {
+            upcoming_release: true,
+            ..release("v1.0.0")
+        };
+        assert!(filter.is_prerelease(&upcoming_release));
+
+        // Invalid tags pattern
+        gitlab.prerelease_tags_pattern = Some("(".to_string());
+        settings.collection.as_mut().unwrap().gitlab = Some(gitlab);
+        assert!(get_prereleases_filter(&settings).is_err());
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_license_spdx() {
+        let gl = gl_object(add_default_expectations(MockGL::new())).await;
+        let gl_project = GitLabProject {
+            license: Some(GitLabLicense {
+                name: "MIT License".to_string(),
+                key: Some("mit".to_string()),
+            }),
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.license, Some("MIT License".to_string()));
+        assert_eq!(repo.license_spdx, Some("MIT".to_string()));
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_uses_default_branch_in_contributors_url() {
+        let gl = gl_object(add_default_expectations(MockGL::new())).await;
+        let gl_project = GitLabProject {
+            default_branch: "master".to_string(),
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            repo.contributors.url,
+            "https://gitlab.com/group/project/-/graphs/master?ref_type=heads"
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_empty_repository() {
+        let mut gl = MockGL::new();
+        gl.expect_get_latest_commit().returning(|_, _| Box::pin(future::ready(Ok(None))));
+        let gl = gl_object(add_default_expectations(gl)).await;
+        let gl_project = GitLabProject {
+            description: Some("description".to_string()),
+            star_count: 5,
+            topics: vec!["topic".to_string()],
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.description, "description");
+        assert_eq!(repo.first_commit, None);
+        assert_eq!(repo.latest_commit, Commit::default());
+        assert_eq!(repo.stars, 5);
+        assert_eq!(repo.topics, vec!["topic".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_delegates_github_mirrors() {
+        // No GitLab operations are expected, as the data is collected from GitHub
+        let gl = gl_object(MockGL::new()).await;
+        let mut github = MockGitHubCollector::new();
+        github
+            .expect_collect_repository_data()
+            .withf(|repo_url| repo_url == "https://github.com/owner/repo")
+            .times(1)
+            .returning(|repo_url| {
+                Box::pin(future::ready(Ok(RepositoryGitData {
+                    stars: 100,
+                    url: repo_url.to_string(),
+                    ..Default::default()
+                })))
+            });
+        let github: DynGitHubCollector = Box::new(github);
+        let gl_project = GitLabProject {
+            import_url: Some("https://github.com/owner/repo.git".to_string()),
+            mirror: true,
+            star_count: 5,
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            Some(&github),
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.stars, 100);
+        assert_eq!(repo.url, "https://github.com/owner/repo");
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_github_mirror_falls_back_to_gitlab() {
+        let gl = gl_object(add_default_expectations(MockGL::new())).await;
+        let mut github = MockGitHubCollector::new();
+        github
+            .expect_collect_repository_data()
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Err(format_err!("no tokens provided")))));
+        let github: DynGitHubCollector = Box::new(github);
+        let gl_project = GitLabProject {
+            import_url: Some("https://github.com/owner/repo.git".to_string()),
+            mirror: true,
+            star_count: 5,
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            Some(&github),
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.stars, 5);
+    }
+
+    #[tokio::test]
+    async fn collect_repository_data_uses_new_path_of_moved_projects() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project().withf(|path| path == "group/old-project").times(1).returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                path_with_namespace: "group/new-project".to_string(),
+                web_url: "https://gitlab.com/group/new-project".to_string(),
+                ..Default::default()
+            })))
+        });
+        gl.expect_get_contributors().withf(|path| path == "group/new-project").returning(|_| {
+            Box::pin(future::ready(Ok(GitLabContributors {
+                count: 5,
+                top: vec![],
+            })))
+        });
+        let gl = gl_object(add_default_expectations(gl)).await;
+
+        let repo = collect_repository_data(
+            &gl,
+            "https://gitlab.com",
+            "https://gitlab.com/group/old-project",
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.url, "https://gitlab.com/group/new-project");
+        assert_eq!(repo.canonical_path.as_deref(), Some("group/new-project"));
+        assert_eq!(repo.contributors.count, 5);
+    }
+
+    #[tokio::test]
+    async fn collect_instance_data_keeps_url_of_moved_projects() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project().returning(|_| {
+            Box::pin(future::ready(Ok(GitLabProject {
+                default_branch: "main".to_string(),
+                path_with_namespace: "group/new-project".to_string(),
+                web_url: "https://gitlab.com/group/new-project".to_string(),
+                ..Default::default()
+            })))
+        });
+        let gl: DynGL = Box::new(add_default_expectations(gl));
+        let gl_pool = Pool::from(vec![gl]);
+
+        let urls = ["https://gitlab.com/group/old-project".to_string()];
+        let instance_data = collect_instance_data(
+            InstanceCollectionContext {
+                gl_pool: Some(&gl_pool),
+                base_url: "https://gitlab.com",
+                repos_options: &HashMap::new(),
+                cached_data: None,
+                cache_ttl: chrono::Duration::days(1),
+                refresh_before: None,
+                failed_fetches: &FailedFetches::new(),
+                stats: &Mutex::new(GitlabCollectionStats::default()),
+                latencies: &Mutex::new(FetchLatencies::default()),
+                progress: &ProgressBar::hidden(),
+                startup_jitter: Duration::ZERO,
+                github: None,
+                collection_options: &GitlabCollectionOptions::default(),
+                circuit_breaker: CircuitBreakerConfig::default(),
+            },
+            &urls,
+        )
+        .await;
+        let repo = instance_data["https://gitlab.com/group/old-project"].as_ref().unwrap();
+        assert_eq!(repo.url, "https://gitlab.com/group/new-project");
+        assert_eq!(repo.canonical_path.as_deref(), Some("group/new-project"));
+    }
+
+    #[tokio::test]
+    async fn collect_repository_data_invalid_project_path() {
+        let mut gl = MockGL::new();
+        gl.expect_get_project().times(0);
+        let gl = gl_object(gl).await;
+
+        let result = collect_repository_data(
+            &gl,
+            "https://gitlab.com",
+            "https://gitlab.com/group/my%20project",
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid gitlab project path: group/my%20project"
+        );
+    }
+
+    #[test]
+    fn bucket_weekly_commit_activity_counts_commits_per_week() {
+        let now: DateTime<Utc> = "2024-06-01T00:00:00Z".parse().unwrap();
+        let dates = [
+            now - chrono::Duration::hours(1),
+            now - chrono::Duration::days(3),
+            now + chrono::Duration::days(1),
+            now - chrono::Duration::days(8),
+            now - chrono::Duration::days(9),
+            now - chrono::Duration::weeks(51) - chrono::Duration::days(1),
+            now - chrono::Duration::weeks(53),
+        ];
+
+        let activity = bucket_weekly_commit_activity(dates, now);
+        assert_eq!(activity.len(), COMMIT_ACTIVITY_WEEKS);
+        assert_eq!(activity[0], 1);
+        assert_eq!(activity[50], 2);
+        assert_eq!(activity[51], 3);
+        assert_eq!(activity.iter().sum::<u32>(), 6);
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_skips_disabled_data() {
+        let mut gl = MockGL::new();
+        gl.expect_get_languages().times(0);
+        gl.expect_get_open_mrs_count().times(0);
+        gl.expect_get_open_issues_count().returning(|_| Box::pin(future::ready(Ok(Some(3)))));
+        let gl = gl_object(add_default_expectations(gl)).await;
+        let gl_project = GitLabProject {
+            default_branch: "main".to_string(),
+            ..Default::default()
+        };
+        let collection_options = GitlabCollectionOptions {
+            languages: false,
+            open_merge_requests: false,
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &collection_options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.languages, None);
+        assert_eq!(repo.languages_pct, None);
+        assert_eq!(repo.open_merge_requests, None);
+        assert_eq!(repo.open_issues, Some(3));
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_uses_last_activity_when_latest_commit_disabled() {
+        let mut gl = MockGL::new();
+        gl.expect_get_latest_commit().times(0);
+        let gl = gl_object(add_default_expectations(gl)).await;
+        let last_activity_at: DateTime<Utc> = "2024-05-01T10:00:00Z".parse().unwrap();
+        let gl_project = GitLabProject {
+            default_branch: "main".to_string(),
+            last_activity_at: Some(last_activity_at),
+            ..Default::default()
+        };
+        let collection_options = GitlabCollectionOptions {
+            latest_commit: false,
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &collection_options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.last_activity_at, Some(last_activity_at));
+        assert_eq!(
+            repo.latest_commit,
+            Commit {
+                ts: Some(last_activity_at),
+                url: "https://gitlab.com/group/project/-/commits/main".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_lightweight_profile_skips_issues_statistics() {
+        let mut gl = MockGL::new();
+        gl.expect_get_good_first_issues_count()
+            .returning(|_| Box::pin(future::ready(Ok(Some(1)))));
+        gl.expect_get_help_wanted_count().returning(|_| Box::pin(future::ready(Ok(Some(1)))));
+        gl.expect_get_open_issues_count().returning(|_| Box::pin(future::ready(Ok(Some(1)))));
+        let gl = gl_object(add_default_expectations(gl)).await;
+        let gl_project = GitLabProject {
+            default_branch: "main".to_string(),
+            ..Default::default()
+        };
+        let settings = LandscapeSettings {
+            collection: Some(Collection {
+                gitlab: Some(GitLabCollection {
+                    profile: Some(GitLabCollectionProfile::Lightweight),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::new(&settings),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.good_first_issues, None);
+        assert_eq!(repo.help_wanted_issues, None);
+        assert_eq!(repo.open_issues, None);
+    }
+
+    #[test]
+    fn gitlab_collection_options_from_settings() {
+        let mut settings = LandscapeSettings::default();
+        assert_eq!(
+            GitlabCollectionOptions::new(&settings),
+            GitlabCollectionOptions::default()
+        );
+
+        settings.collection = Some(Collection {
+            gitlab: Some(GitLabCollection {
+                languages: Some(false),
+                good_first_issues: Some(false),
+                open_issues: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            GitlabCollectionOptions::new(&settings),
+            GitlabCollectionOptions {
+                good_first_issues: false,
+                languages: false,
+                ..Default::default()
+            }
+        );
+
+        // The lightweight profile disables the data based on statistics
+        settings.collection = Some(Collection {
+            gitlab: Some(GitLabCollection {
+                languages: Some(false),
+                profile: Some(GitLabCollectionProfile::Lightweight),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            GitlabCollectionOptions::new(&settings),
+            GitlabCollectionOptions {
+                good_first_issues: false,
+                help_wanted_issues: false,
+                languages: false,
+                open_issues: false,
+                project_statistics: false,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_reuses_commit_activity() {
+        let mut gl = MockGL::new();
+        gl.expect_get_weekly_commit_activity().returning(|_, _| {
+            let mut activity = vec![0; COMMIT_ACTIVITY_WEEKS];
+            activity[0] = 3;
+            activity[COMMIT_ACTIVITY_WEEKS - 1] = 7;
+            Box::pin(future::ready(Ok(Some(activity))))
+        });
+        gl.expect_get_recent_commit_count().times(0);
+        let gl = gl_object(add_default_expectations(gl)).await;
+        let gl_project = GitLabProject {
+            default_branch: "main".to_string(),
+            ..Default::default()
+        };
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            gl_project,
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.commits_last_year, Some(10));
+        assert_eq!(repo.commit_activity_weekly.unwrap().len(), COMMIT_ACTIVITY_WEEKS);
+    }
+
+    #[tokio::test]
+    async fn collect_project_data_languages_are_approximate() {
+        let mut gl = MockGL::new();
+        gl.expect_get_languages().returning(|_| {
+            Box::pin(future::ready(Ok(Some(BTreeMap::from([
+                ("Go".to_string(), 62.5),
+                ("Rust".to_string(), 37.5),
+            ])))))
+        });
+        let gl = gl_object(add_default_expectations(gl)).await;
+
+        let repo = collect_project_data(
+            &gl,
+            "https://gitlab.com",
+            "group/project",
+            GitLabProject::default(),
+            None,
+            None,
+            &GitlabCollectionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            repo.languages,
+            Some(BTreeMap::from([
+                ("Go".to_string(), 62_500),
+                ("Rust".to_string(), 37_500)
+            ]))
+        );
+        assert_eq!(
+            repo.languages_pct,
+            Some(BTreeMap::from([
+                ("Go".to_string(), 62.5),
+                ("Rust".to_string(), 37.5)
+            ]))
+        );
+        assert_eq!(repo.languages_are_approximate, Some(true));
+    }
+
+    #[test]
+    fn estimate_languages_bytes_from_percentages() {
+        let languages_pct = BTreeMap::from([("Go".to_string(), 66.67), ("Shell".to_string(), 33.33)]);
+        assert_eq!(
+            estimate_languages_bytes(&languages_pct, None),
+            BTreeMap::from([("Go".to_string(), 66_670), ("Shell".to_string(), 33_330)])
+        );
+    }
+
+    #[test]
+    fn estimate_languages_bytes_scaled_to_repository_size() {
+        let languages_pct = BTreeMap::from([("Go".to_string(), 75.0), ("Shell".to_string(), 25.0)]);
+        let unscaled = estimate_languages_bytes(&languages_pct, None);
+        let scaled = estimate_languages_bytes(&languages_pct, Some(2_000_000));
+        assert_eq!(
+            unscaled,
+            BTreeMap::from([("Go".to_string(), 75_000), ("Shell".to_string(), 25_000)])
+        );
+        assert_eq!(
+            scaled,
+            BTreeMap::from([("Go".to_string(), 1_500_000), ("Shell".to_string(), 500_000)])
+        );
+
+        // An empty repository size is handled as if it was not available
+        assert_eq!(estimate_languages_bytes(&languages_pct, Some(0)), unscaled);
+    }
+}
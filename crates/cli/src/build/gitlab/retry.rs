@@ -0,0 +1,491 @@
+//! This module provides the mechanisms used to cope with transient GitLab API
+//! failures (retries, request budget and circuit breaker).
+
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use landscape2_core::data::Commit;
+use tracing::debug;
+
+use super::{
+    client::{DynGL, GL, GitlabError, is_retryable, retry_after},
+    dto::{GitLabContributors, GitLabProject},
+    random_delay,
+};
+
+/// Environment variable containing the maximum number of times a GitLab API
+/// call will be retried when it fails with a transient error.
+const GITLAB_MAX_RETRIES: &str = "GITLAB_MAX_RETRIES";
+
+/// Environment variable containing the base delay (in milliseconds) used to
+/// compute the exponential backoff between retries.
+const GITLAB_RETRY_BASE_DELAY_MS: &str = "GITLAB_RETRY_BASE_DELAY_MS";
+
+/// Environment variable containing the number of consecutive failures after
+/// which the remaining repositories of an instance are skipped (0 disables
+/// the circuit breaker).
+const GITLAB_CIRCUIT_BREAKER_THRESHOLD: &str = "GITLAB_CIRCUIT_BREAKER_THRESHOLD";
+
+/// Environment variable containing the time (in seconds) after which an open
+/// circuit breaker lets a request through to check if the instance has
+/// recovered. When not set, the breaker stays open for the rest of the build.
+const GITLAB_CIRCUIT_BREAKER_RESET_SECS: &str = "GITLAB_CIRCUIT_BREAKER_RESET_SECS";
+
+/// Default maximum number of retries for GitLab API calls.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Default base delay used to compute the backoff between retries.
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Default number of consecutive failures after which the circuit breaker of
+/// an instance opens.
+const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 10;
+
+/// Budget of requests that can be made to the GitLab API, shared by all the
+/// clients used during the build.
+#[derive(Debug, Default)]
+pub(super) struct RequestBudget {
+    max_requests: Option<u64>,
+    requests: AtomicU64,
+}
+
+impl RequestBudget {
+    /// Create a new request budget (unlimited if no maximum is provided).
+    pub fn new(max_requests: Option<u64>) -> Self {
+        Self {
+            max_requests,
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// Use one request from the budget, returning false if it is exhausted.
+    pub fn try_use(&self) -> bool {
+        let Some(max_requests) = self.max_requests else {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            return true;
+        };
+        self.requests
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |requests| {
+                (requests < max_requests).then_some(requests + 1)
+            })
+            .is_ok()
+    }
+}
+
+/// Error returned when a GitLab API call cannot be made because the request
+/// budget has been exhausted.
+#[derive(Debug)]
+pub(super) struct RequestBudgetExhausted;
+
+impl std::fmt::Display for RequestBudgetExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "gitlab request budget exhausted")
+    }
+}
+
+impl std::error::Error for RequestBudgetExhausted {}
+
+/// Configuration used to retry GitLab API calls that fail with a transient
+/// error (rate limited, server errors, network issues).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct RetryConfig {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: DEFAULT_RETRY_BASE_DELAY,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Create a new retry configuration instance from the environment,
+    /// falling back to the defaults for the values not provided.
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+        if let Ok(max_retries) = env::var(GITLAB_MAX_RETRIES) {
+            config.max_retries =
+                max_retries.parse().with_context(|| format!("invalid {GITLAB_MAX_RETRIES} value"))?;
+        }
+        if let Ok(base_delay) = env::var(GITLAB_RETRY_BASE_DELAY_MS) {
+            config.base_delay = Duration::from_millis(
+                base_delay
+                    .parse()
+                    .with_context(|| format!("invalid {GITLAB_RETRY_BASE_DELAY_MS} value"))?,
+            );
+        }
+        Ok(config)
+    }
+
+    /// Return the delay to wait before the retry attempt provided (starting
+    /// at 0), using exponential backoff with some random jitter.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(2_u32.saturating_pow(attempt));
+        delay.saturating_add(random_delay(self.base_delay))
+    }
+}
+
+/// Configuration of the circuit breaker used to stop collecting the
+/// repositories of an instance that keeps failing (i.e. it's down).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct CircuitBreakerConfig {
+    /// Consecutive failures after which the breaker opens (0 disables it).
+    pub threshold: u32,
+    /// Time after which an open breaker lets a request through to check if
+    /// the instance has recovered (never when not set).
+    pub reset_after: Option<Duration>,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_CIRCUIT_BREAKER_THRESHOLD,
+            reset_after: None,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Create a new circuit breaker configuration instance from the
+    /// environment, falling back to the defaults for the values not provided.
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+        if let Ok(threshold) = env::var(GITLAB_CIRCUIT_BREAKER_THRESHOLD) {
+            config.threshold = threshold
+                .parse()
+                .with_context(|| format!("invalid {GITLAB_CIRCUIT_BREAKER_THRESHOLD} value"))?;
+        }
+        if let Ok(reset_after) = env::var(GITLAB_CIRCUIT_BREAKER_RESET_SECS) {
+            config.reset_after =
+                Some(Duration::from_secs(reset_after.parse().with_context(|| {
+                    format!("invalid {GITLAB_CIRCUIT_BREAKER_RESET_SECS} value")
+                })?));
+        }
+        Ok(config)
+    }
+}
+
+/// Circuit breaker that tracks the consecutive failures of the requests to an
+/// instance. Once it opens, the remaining repositories of the instance are
+/// skipped without making any requests.
+#[derive(Debug)]
+pub(super) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Mutex<CircuitBreakerState>,
+}
+
+/// State of a circuit breaker.
+#[derive(Debug, Default)]
+struct CircuitBreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Create a new (closed) circuit breaker.
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(CircuitBreakerState::default()),
+        }
+    }
+
+    /// Check if a request can be made to the instance. When the breaker is
+    /// open and the reset time has elapsed, a single request is let through
+    /// to check if the instance has recovered.
+    pub fn allow_request(&self) -> bool {
+        let mut state = self.state.lock().expect("circuit breaker lock not to be poisoned");
+        let Some(opened_at) = state.opened_at else {
+            return true;
+        };
+        match self.config.reset_after {
+            Some(reset_after) if opened_at.elapsed() >= reset_after => {
+                state.opened_at = Some(Instant::now());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record that the instance replied, closing the breaker.
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().expect("circuit breaker lock not to be poisoned");
+        *state = CircuitBreakerState::default();
+    }
+
+    /// Record that a request to the instance failed, returning true if the
+    /// breaker has just opened.
+    pub fn record_failure(&self) -> bool {
+        if self.config.threshold == 0 {
+            return false;
+        }
+        let mut state = self.state.lock().expect("circuit breaker lock not to be poisoned");
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.opened_at.is_none() && state.consecutive_failures >= self.config.threshold {
+            state.opened_at = Some(Instant::now());
+            return true;
+        }
+        false
+    }
+}
+
+/// GL implementation that wraps another GL implementation, retrying the
+/// operations that fail with a transient error using exponential backoff.
+pub(super) struct GLRetry {
+    gl: DynGL,
+    config: RetryConfig,
+}
+
+impl GLRetry {
+    /// Create a new GLRetry instance.
+    pub fn new(gl: DynGL, config: RetryConfig) -> Self {
+        Self { gl, config }
+    }
+
+    /// Run the operation provided, retrying it when it fails with a transient
+    /// error until the maximum number of retries is reached.
+    async fn retry<T, F, Fut>(&self, operation: F) -> Result<T, GitlabError>
+    where
+        F: Fn() -> Fut + Send,
+        Fut: Future<Output = Result<T, GitlabError>> + Send,
+        T: Send,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt < self.config.max_retries && is_retryable(err.inner()) => {
+                    let delay = retry_after(err.inner()).unwrap_or_else(|| self.config.backoff(attempt));
+                    debug!(?delay, attempt, "transient gitlab error, retrying: {err}");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl GL for GLRetry {
+    /// [GL::get_contributors]
+    async fn get_contributors(&self, project_path: &str) -> Result<GitLabContributors, GitlabError> {
+        self.retry(|| self.gl.get_contributors(project_path)).await
+    }
+
+    /// [GL::get_first_commit]
+    async fn get_first_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError> {
+        self.retry(|| self.gl.get_first_commit(project_path, ref_)).await
+    }
+
+    /// [GL::get_good_first_issues_count]
+    async fn get_good_first_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        self.retry(|| self.gl.get_good_first_issues_count(project_path)).await
+    }
+
+    /// [GL::get_group_projects]
+    async fn get_group_projects(&self, group_path: &str) -> Result<Vec<String>, GitlabError> {
+        self.retry(|| self.gl.get_group_projects(group_path)).await
+    }
+
+    /// [GL::get_help_wanted_count]
+    async fn get_help_wanted_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        self.retry(|| self.gl.get_help_wanted_count(project_path)).await
+    }
+
+    /// [GL::get_languages]
+    async fn get_languages(&self, project_path: &str) -> Result<Option<BTreeMap<String, f64>>, GitlabError> {
+        self.retry(|| self.gl.get_languages(project_path)).await
+    }
+
+    /// [GL::get_latest_commit]
+    async fn get_latest_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError> {
+        self.retry(|| self.gl.get_latest_commit(project_path, ref_)).await
+    }
+
+    /// [GL::get_latest_pipeline_status]
+    async fn get_latest_pipeline_status(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<String>, GitlabError> {
+        self.retry(|| self.gl.get_latest_pipeline_status(project_path, ref_)).await
+    }
+
+    /// [GL::get_latest_release]
+    async fn get_latest_release(
+        &self,
+        project_path: &str,
+    ) -> Result<Option<landscape2_core::data::Release>, GitlabError> {
+        self.retry(|| self.gl.get_latest_release(project_path)).await
+    }
+
+    /// [GL::get_open_issues_count]
+    async fn get_open_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        self.retry(|| self.gl.get_open_issues_count(project_path)).await
+    }
+
+    /// [GL::get_open_mrs_count]
+    async fn get_open_mrs_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        self.retry(|| self.gl.get_open_mrs_count(project_path)).await
+    }
+
+    /// [GL::get_project]
+    async fn get_project(&self, project_path: &str) -> Result<GitLabProject, GitlabError> {
+        self.retry(|| self.gl.get_project(project_path)).await
+    }
+
+    /// [GL::get_recent_commit_count]
+    async fn get_recent_commit_count(
+        &self,
+        project_path: &str,
+        ref_: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<usize>, GitlabError> {
+        self.retry(|| self.gl.get_recent_commit_count(project_path, ref_, since)).await
+    }
+
+    /// [GL::get_weekly_commit_activity]
+    async fn get_weekly_commit_activity(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<Vec<u32>>, GitlabError> {
+        self.retry(|| self.gl.get_weekly_commit_activity(project_path, ref_)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use futures::future;
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::build::gitlab::{
+        client::MockGL,
+        dto::GitLabContributor,
+        tests::{api_error, test_retry_config},
+    };
+
+    #[tokio::test]
+    async fn gl_retry_retries_transient_errors() {
+        let mut gl = MockGL::new();
+        let mut calls = 0;
+        gl.expect_get_contributors().times(3).returning(move |_| {
+            calls += 1;
+            if calls < 3 {
+                Box::pin(future::ready(Err(api_error(StatusCode::BAD_GATEWAY))))
+            } else {
+                Box::pin(future::ready(Ok(GitLabContributors {
+                    count: 10,
+                    top: vec![GitLabContributor::default(); 10],
+                })))
+            }
+        });
+
+        let gl = GLRetry::new(Box::new(gl), test_retry_config(3));
+        assert_eq!(gl.get_contributors("group/project").await.unwrap().count, 10);
+    }
+
+    #[tokio::test]
+    async fn gl_retry_gives_up_after_max_retries() {
+        let mut gl = MockGL::new();
+        gl.expect_get_contributors()
+            .times(3)
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::TOO_MANY_REQUESTS)))));
+
+        let gl = GLRetry::new(Box::new(gl), test_retry_config(2));
+        assert!(gl.get_contributors("group/project").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn gl_retry_does_not_retry_permanent_errors() {
+        let mut gl = MockGL::new();
+        gl.expect_get_contributors()
+            .times(1)
+            .returning(|_| Box::pin(future::ready(Err(api_error(StatusCode::NOT_FOUND)))));
+
+        let gl = GLRetry::new(Box::new(gl), test_retry_config(3));
+        assert!(gl.get_contributors("group/project").await.is_err());
+    }
+
+    #[test]
+    fn retry_config_backoff_is_exponential() {
+        let config = RetryConfig {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+        };
+        for (attempt, expected_min) in [(0, 100), (1, 200), (2, 400)] {
+            let delay = config.backoff(attempt);
+            assert!(delay >= Duration::from_millis(expected_min));
+            assert!(delay <= Duration::from_millis(expected_min + 100));
+        }
+    }
+
+    #[test]
+    fn circuit_breaker_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            threshold: 2,
+            reset_after: None,
+        });
+        assert!(!breaker.record_failure());
+        breaker.record_success();
+        assert!(!breaker.record_failure());
+        assert!(breaker.allow_request());
+        assert!(breaker.record_failure());
+        assert!(!breaker.allow_request());
+        assert!(!breaker.record_failure());
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn circuit_breaker_lets_a_request_through_after_reset_time() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            threshold: 1,
+            reset_after: Some(Duration::ZERO),
+        });
+        assert!(breaker.record_failure());
+        assert!(breaker.allow_request());
+        breaker.record_success();
+        assert!(breaker.allow_request());
+
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            threshold: 1,
+            reset_after: Some(Duration::from_secs(3600)),
+        });
+        assert!(breaker.record_failure());
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn circuit_breaker_disabled() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            threshold: 0,
+            reset_after: None,
+        });
+        assert!((0..100).all(|_| !breaker.record_failure()));
+        assert!(breaker.allow_request());
+    }
+
+    #[test]
+    fn request_budget_try_use() {
+        let budget = RequestBudget::new(Some(2));
+        assert!(budget.try_use());
+        assert!(budget.try_use());
+        assert!(!budget.try_use());
+
+        let budget = RequestBudget::new(None);
+        assert!((0..100).all(|_| budget.try_use()));
+    }
+}
@@ -0,0 +1,884 @@
+//! This module provides the helpers used to parse the GitLab tokens and
+//! instances configuration, as well as the client used to refresh expired
+//! tokens.
+
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail, format_err};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use landscape2_core::data::Commit;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use super::{
+    ClientOptions, DEFAULT_GITLAB_URL,
+    client::{DynGL, GL, GLApi, GitlabError, error_status, wrap_gl_api},
+    dto::{GitLabContributors, GitLabProject},
+};
+use crate::build::tokens::parse_instances_tokens_checked;
+
+/// Environment variable containing GitLab tokens configuration.
+/// Format: "token1,token2" for the default instance (gitlab.com unless
+/// `GITLAB_DEFAULT_URL` is set) or "url1;token1;url2;token2" for multiple instances
+/// Tokens prefixed with "oauth2:" are sent as OAuth2 bearer tokens. Instances
+/// served under a relative url root must use their url including it (i.e.
+/// "https://example.com/gitlab").
+const GITLAB_TOKENS: &str = "GITLAB_TOKENS";
+
+/// Environment variable containing the path of a YAML file with the GitLab
+/// tokens configuration. When set, it takes precedence over `GITLAB_TOKENS`.
+const GITLAB_TOKENS_FILE: &str = "GITLAB_TOKENS_FILE";
+
+/// Environment variable containing the aliases of the GitLab instances whose
+/// API is reachable at a different url than the one used in the repositories
+/// urls. Format: "url1=api_url1;url2=api_url2"
+const GITLAB_BASE_URL_ALIASES: &str = "GITLAB_BASE_URL_ALIASES";
+
+/// Environment variable containing the url of the GitLab instance the tokens
+/// not associated with any instance are used for (defaults to gitlab.com).
+const GITLAB_DEFAULT_URL: &str = "GITLAB_DEFAULT_URL";
+
+/// Scopes GitLab tokens need to collect all the repositories information,
+/// along with the scopes that include them.
+const REQUIRED_TOKEN_SCOPES: &[(&str, &[&str])] = &[
+    ("read_api", &["api"]),
+    ("read_repository", &["api", "write_repository"]),
+];
+
+/// Maximum time the command used to get fresh GitLab tokens can take.
+const TOKEN_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of characters of a token displayed when it is logged.
+const TOKEN_VISIBLE_PREFIX_LEN: usize = 8;
+
+/// Configuration for a GitLab instance.
+#[derive(Debug, Clone)]
+pub(super) struct GitlabInstanceConfig {
+    pub base_url: String,
+    pub tokens: Vec<String>,
+    /// Number of days the data collected from the instance is cached for
+    /// (when not set, the global cache TTL is used).
+    pub cache_ttl_days: Option<u32>,
+}
+
+/// Parse GitLab tokens from the file referenced in the corresponding
+/// environment variable (when set) or from the tokens environment variable.
+/// When strict is enabled, malformed tokens values are rejected.
+pub(super) fn parse_gitlab_tokens_env(strict: bool) -> Result<Vec<GitlabInstanceConfig>> {
+    let default_url = get_default_gitlab_url(env::var(GITLAB_DEFAULT_URL).ok().as_deref())?;
+
+    if let Ok(file) = env::var(GITLAB_TOKENS_FILE)
+        && !file.is_empty()
+    {
+        let raw_data =
+            fs::read_to_string(&file).with_context(|| format!("error reading gitlab tokens file {file}"))?;
+        return parse_gitlab_tokens_file(&raw_data, &default_url)
+            .with_context(|| format!("invalid gitlab tokens file {file}"));
+    }
+
+    let tokens_env = match env::var(GITLAB_TOKENS) {
+        Ok(t) if !t.is_empty() => t,
+        _ => return Ok(vec![]),
+    };
+
+    parse_gitlab_tokens_checked(&tokens_env, &default_url, strict)
+}
+
+/// Get the url of the GitLab instance the tokens not associated with any
+/// instance are used for, from the override provided or the default one.
+fn get_default_gitlab_url(default_url_override: Option<&str>) -> Result<String> {
+    let Some(default_url) = default_url_override.map(str::trim).filter(|url| !url.is_empty()) else {
+        return Ok(DEFAULT_GITLAB_URL.to_string());
+    };
+    if !default_url.starts_with("http://") && !default_url.starts_with("https://") {
+        bail!("invalid {GITLAB_DEFAULT_URL} value: url must start with http:// or https:// ({default_url})");
+    }
+    Ok(default_url.trim_end_matches('/').to_string())
+}
+
+/// Parse GitLab tokens from the raw value of the tokens environment variable.
+/// Tokens not preceded by an instance url are used for the default instance.
+/// A warning is logged for each malformed segment found or, when strict is
+/// enabled, an error including all of them is returned instead.
+fn parse_gitlab_tokens_checked(
+    raw_data: &str,
+    default_url: &str,
+    strict: bool,
+) -> Result<Vec<GitlabInstanceConfig>> {
+    let (instances, warnings) = parse_instances_tokens_checked(raw_data, default_url);
+    if strict && !warnings.is_empty() {
+        bail!("invalid {GITLAB_TOKENS} value: {}", warnings.join("; "));
+    }
+    for warning in &warnings {
+        warn!("invalid {GITLAB_TOKENS} value: {warning}");
+    }
+
+    let configs = instances
+        .into_iter()
+        .map(|instance| GitlabInstanceConfig {
+            base_url: instance.base_url,
+            tokens: instance.tokens,
+            cache_ttl_days: None,
+        })
+        .collect();
+
+    Ok(dedup_tokens(configs))
+}
+
+/// Parse GitLab tokens from the content of a tokens file (YAML format):
+///
+/// ```yaml
+/// instances:
+///   - url: https://gitlab.com
+///     tokens:
+///       - token1
+///       - token2
+///     cache_ttl_days: 1
+/// ```
+///
+/// Instances without url use the default instance url provided. The cache TTL
+/// (in days) is optional, and overrides the global one for the instance.
+fn parse_gitlab_tokens_file(raw_data: &str, default_url: &str) -> Result<Vec<GitlabInstanceConfig>> {
+    let tokens_file: GitlabTokensFile = serde_yaml::from_str(raw_data)?;
+
+    let mut configs = vec![];
+    for (i, instance) in tokens_file.instances.into_iter().enumerate() {
+        let base_url = instance.url.unwrap_or_else(|| default_url.to_string());
+        if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+            bail!("instance {i}: url must start with http:// or https:// ({base_url})");
+        }
+        if instance.tokens.is_empty() {
+            bail!("instance {i}: at least one token must be provided ({base_url})");
+        }
+        if instance.tokens.iter().any(|token| token.trim().is_empty()) {
+            bail!("instance {i}: tokens cannot be empty ({base_url})");
+        }
+
+        configs.push(GitlabInstanceConfig {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            tokens: instance.tokens.into_iter().map(|token| token.trim().to_string()).collect(),
+            cache_ttl_days: instance.cache_ttl_days,
+        });
+    }
+
+    Ok(dedup_tokens(configs))
+}
+
+/// Remove the duplicated tokens of each of the instances configurations
+/// provided, so that no more clients than tokens available are used.
+fn dedup_tokens(mut configs: Vec<GitlabInstanceConfig>) -> Vec<GitlabInstanceConfig> {
+    for config in &mut configs {
+        let mut seen = HashSet::new();
+        config.tokens.retain(|token| {
+            if seen.insert(token.clone()) {
+                return true;
+            }
+            warn!(
+                "duplicated gitlab token {} for instance {} (it will be used only once)",
+                mask_token(token),
+                config.base_url
+            );
+            false
+        });
+    }
+    configs
+}
+
+/// GitLab tokens file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GitlabTokensFile {
+    instances: Vec<GitlabTokensFileInstance>,
+}
+
+/// GitLab instance entry in the tokens file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GitlabTokensFileInstance {
+    url: Option<String>,
+    tokens: Vec<String>,
+    cache_ttl_days: Option<u32>,
+}
+
+/// Get how long the data collected from the GitLab instance provided is cached
+/// for, using the instance cache TTL when configured or the default one (both
+/// in days) otherwise.
+pub(super) fn get_instance_cache_ttl(
+    base_url: &str,
+    configs: &[GitlabInstanceConfig],
+    default_cache_ttl: u32,
+) -> chrono::Duration {
+    let cache_ttl = find_config_for_instance(base_url, configs)
+        .and_then(|config| config.cache_ttl_days)
+        .unwrap_or(default_cache_ttl);
+    chrono::Duration::days(i64::from(cache_ttl))
+}
+
+/// Find the configuration for a given GitLab instance.
+pub(super) fn find_config_for_instance<'a>(
+    base_url: &str,
+    configs: &'a [GitlabInstanceConfig],
+) -> Option<&'a GitlabInstanceConfig> {
+    let normalized_url = base_url.trim_end_matches('/').to_lowercase();
+    configs
+        .iter()
+        .find(|c| c.base_url.trim_end_matches('/').to_lowercase() == normalized_url)
+}
+
+/// Parse the GitLab instances base url aliases from the corresponding
+/// environment variable (when set).
+pub(super) fn parse_gitlab_base_url_aliases_env() -> Result<BTreeMap<String, String>> {
+    match env::var(GITLAB_BASE_URL_ALIASES) {
+        Ok(aliases) => parse_gitlab_base_url_aliases(&aliases)
+            .with_context(|| format!("invalid {GITLAB_BASE_URL_ALIASES} value")),
+        Err(_) => Ok(BTreeMap::new()),
+    }
+}
+
+/// Parse GitLab instances base url aliases. The aliases are returned indexed
+/// by the normalized url used in the landscape data.
+/// Format: "url1=api_url1;url2=api_url2"
+fn parse_gitlab_base_url_aliases(raw_aliases: &str) -> Result<BTreeMap<String, String>> {
+    let mut aliases = BTreeMap::new();
+    for alias in raw_aliases.split(';').map(str::trim).filter(|alias| !alias.is_empty()) {
+        let Some((url, api_url)) = alias.split_once('=') else {
+            bail!("alias must use the url=api_url format ({alias})");
+        };
+        let (url, api_url) = (url.trim(), api_url.trim());
+        for url in [url, api_url] {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                bail!("url must start with http:// or https:// ({url})");
+            }
+        }
+        aliases.insert(
+            url.trim_end_matches('/').to_lowercase(),
+            api_url.trim_end_matches('/').to_string(),
+        );
+    }
+    Ok(aliases)
+}
+
+/// Return the url that should be used to access the api of the GitLab
+/// instance provided, taking into account the aliases configured.
+pub(super) fn resolve_api_url<'a>(base_url: &'a str, aliases: &'a BTreeMap<String, String>) -> &'a str {
+    aliases
+        .get(&base_url.trim_end_matches('/').to_lowercase())
+        .map_or(base_url, String::as_str)
+}
+
+/// Return the required scopes that are missing from the token scopes provided.
+pub(super) fn missing_token_scopes(scopes: &[String]) -> Vec<&'static str> {
+    REQUIRED_TOKEN_SCOPES
+        .iter()
+        .filter(|(required_scope, including_scopes)| {
+            !scopes
+                .iter()
+                .any(|scope| scope == required_scope || including_scopes.contains(&scope.as_str()))
+        })
+        .map(|(required_scope, _)| *required_scope)
+        .collect()
+}
+
+/// Mask the token provided so that it can be safely logged, keeping only a
+/// short prefix to help identifying it.
+pub(super) fn mask_token(token: &str) -> String {
+    let prefix: String = token.chars().take(TOKEN_VISIBLE_PREFIX_LEN).collect();
+    if prefix.len() == token.len() {
+        return "****".to_string();
+    }
+    format!("{prefix}****")
+}
+
+/// Provider of fresh GitLab tokens, used to replace the tokens that expire
+/// during the build (i.e. short-lived tokens issued by a secrets manager).
+#[async_trait]
+pub(crate) trait TokenProvider: std::fmt::Debug + Send + Sync {
+    /// Return a fresh token for the instance provided to replace the expired
+    /// token given, or None when no fresh token is available.
+    async fn refresh_token(&self, base_url: &str, expired_token: &str) -> Result<Option<String>>;
+}
+
+/// Type alias to represent a TokenProvider trait object.
+pub(crate) type DynTokenProvider = Arc<dyn TokenProvider>;
+
+/// TokenProvider implementation that gets the fresh tokens running a command,
+/// which receives the url of the instance as its last argument and writes the
+/// token to its output (an empty output means no fresh token is available).
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct CommandTokenProvider {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandTokenProvider {
+    /// Create a new CommandTokenProvider instance.
+    pub fn new(program: String, args: Vec<String>) -> Self {
+        Self { program, args }
+    }
+
+    /// Create a new CommandTokenProvider instance from the command line
+    /// provided, which is split into the program and its arguments following
+    /// the shell quoting rules. None is returned if the command is empty.
+    pub fn from_command(command: &str) -> Result<Option<Self>> {
+        let mut parts = shlex::split(command)
+            .ok_or_else(|| format_err!("invalid token command: unbalanced quotes or trailing escape"))?
+            .into_iter();
+        Ok(parts.next().map(|program| Self::new(program, parts.collect())))
+    }
+}
+
+#[async_trait]
+impl TokenProvider for CommandTokenProvider {
+    /// [TokenProvider::refresh_token]
+    async fn refresh_token(&self, base_url: &str, _expired_token: &str) -> Result<Option<String>> {
+        let command = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .arg(base_url)
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(TOKEN_COMMAND_TIMEOUT, command)
+            .await
+            .map_err(|_| format_err!("token command {} timed out", self.program))?
+            .with_context(|| format!("error running token command {}", self.program))?;
+        if !output.status.success() {
+            bail!(
+                "token command {} failed ({}): {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let token = String::from_utf8(output.stdout).context("invalid token command output")?;
+        let token = token.trim();
+        Ok((!token.is_empty()).then(|| token.to_string()))
+    }
+}
+
+/// GL implementation that wraps another GL implementation, rebuilding it with
+/// a fresh token from the token provider when the token it uses expires. The
+/// operation that failed is then run again using the new client.
+pub(super) struct GLTokenRefresh {
+    base_url: String,
+    client: Mutex<(String, Arc<DynGL>)>,
+    options: ClientOptions,
+    refresh_lock: tokio::sync::Mutex<()>,
+    token_provider: DynTokenProvider,
+}
+
+impl GLTokenRefresh {
+    /// Create a new GLTokenRefresh instance.
+    pub fn new(
+        base_url: &str,
+        token: &str,
+        gl: DynGL,
+        options: &ClientOptions,
+        token_provider: DynTokenProvider,
+    ) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            client: Mutex::new((token.to_string(), Arc::new(gl))),
+            options: options.clone(),
+            refresh_lock: tokio::sync::Mutex::new(()),
+            token_provider,
+        }
+    }
+
+    /// Return the client currently used along with its token.
+    fn current(&self) -> (String, Arc<DynGL>) {
+        self.client.lock().expect("client lock not to be poisoned").clone()
+    }
+
+    /// Replace the client using the expired token provided with a new one
+    /// using a fresh token, returning false if none is available. Concurrent
+    /// operations that fail with the same token only refresh it once.
+    async fn refresh(&self, expired_token: &str) -> Result<bool> {
+        let _guard = self.refresh_lock.lock().await;
+        if self.current().0 != expired_token {
+            return Ok(true);
+        }
+        let Some(token) = self.token_provider.refresh_token(&self.base_url, expired_token).await? else {
+            return Ok(false);
+        };
+        let gl = GLApi::new(&self.base_url, &token, &self.options).await?;
+        debug!(
+            "gitlab token {} expired, using fresh token {}",
+            mask_token(expired_token),
+            mask_token(&token)
+        );
+        *self.client.lock().expect("client lock not to be poisoned") =
+            (token, Arc::new(wrap_gl_api(gl, &self.options)));
+        Ok(true)
+    }
+
+    /// Run the operation provided, running it again with a new client when it
+    /// fails because the token used has expired.
+    async fn run<T, F, Fut>(&self, operation: F) -> Result<T, GitlabError>
+    where
+        F: Fn(Arc<DynGL>) -> Fut + Send,
+        Fut: Future<Output = Result<T, GitlabError>> + Send,
+        T: Send,
+    {
+        let (token, gl) = self.current();
+        match operation(gl).await {
+            Err(err) if error_status(err.inner()) == Some(401) => match self.refresh(&token).await {
+                Ok(true) => operation(self.current().1).await,
+                Ok(false) => Err(err),
+                Err(refresh_err) => {
+                    warn!(
+                        "error refreshing gitlab token {}: {refresh_err:#}",
+                        mask_token(&token)
+                    );
+                    Err(err)
+                }
+            },
+            result => result,
+        }
+    }
+}
+
+#[async_trait]
+impl GL for GLTokenRefresh {
+    /// [GL::get_contributors]
+    async fn get_contributors(&self, project_path: &str) -> Result<GitLabContributors, GitlabError> {
+        self.run(|gl| async move { gl.get_contributors(project_path).await }).await
+    }
+
+    /// [GL::get_first_commit]
+    async fn get_first_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError> {
+        self.run(|gl| async move { gl.get_first_commit(project_path, ref_).await }).await
+    }
+
+    /// [GL::get_good_first_issues_count]
+    async fn get_good_first_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        self.run(|gl| async move { gl.get_good_first_issues_count(project_path).await }).await
+    }
+
+    /// [GL::get_group_projects]
+    async fn get_group_projects(&self, group_path: &str) -> Result<Vec<String>, GitlabError> {
+        self.run(|gl| async move { gl.get_group_projects(group_path).await }).await
+    }
+
+    /// [GL::get_help_wanted_count]
+    async fn get_help_wanted_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        self.run(|gl| async move { gl.get_help_wanted_count(project_path).await }).await
+    }
+
+    /// [GL::get_languages]
+    async fn get_languages(&self, project_path: &str) -> Result<Option<BTreeMap<String, f64>>, GitlabError> {
+        self.run(|gl| async move { gl.get_languages(project_path).await }).await
+    }
+
+    /// [GL::get_latest_commit]
+    async fn get_latest_commit(&self, project_path: &str, ref_: &str) -> Result<Option<Commit>, GitlabError> {
+        self.run(|gl| async move { gl.get_latest_commit(project_path, ref_).await }).await
+    }
+
+    /// [GL::get_latest_pipeline_status]
+    async fn get_latest_pipeline_status(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<String>, GitlabError> {
+        self.run(|gl| async move { gl.get_latest_pipeline_status(project_path, ref_).await })
+            .await
+    }
+
+    /// [GL::get_latest_release]
+    async fn get_latest_release(
+        &self,
+        project_path: &str,
+    ) -> Result<Option<landscape2_core::data::Release>, GitlabError> {
+        self.run(|gl| async move { gl.get_latest_release(project_path).await }).await
+    }
+
+    /// [GL::get_open_issues_count]
+    async fn get_open_issues_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        self.run(|gl| async move { gl.get_open_issues_count(project_path).await }).await
+    }
+
+    /// [GL::get_open_mrs_count]
+    async fn get_open_mrs_count(&self, project_path: &str) -> Result<Option<usize>, GitlabError> {
+        self.run(|gl| async move { gl.get_open_mrs_count(project_path).await }).await
+    }
+
+    /// [GL::get_project]
+    async fn get_project(&self, project_path: &str) -> Result<GitLabProject, GitlabError> {
+        self.run(|gl| async move { gl.get_project(project_path).await }).await
+    }
+
+    /// [GL::get_recent_commit_count]
+    async fn get_recent_commit_count(
+        &self,
+        project_path: &str,
+        ref_: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<usize>, GitlabError> {
+        self.run(|gl| async move { gl.get_recent_commit_count(project_path, ref_, since).await })
+            .await
+    }
+
+    /// [GL::get_weekly_commit_activity]
+    async fn get_weekly_commit_activity(
+        &self,
+        project_path: &str,
+        ref_: &str,
+    ) -> Result<Option<Vec<u32>>, GitlabError> {
+        self.run(|gl| async move { gl.get_weekly_commit_activity(project_path, ref_).await })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::build::gitlab::{client::create_gitlab_pool, get_client};
+
+    #[tokio::test]
+    async fn create_gitlab_pool_refreshes_expired_tokens() {
+        #[derive(Debug, Default)]
+        struct TestTokenProvider {
+            refreshes: AtomicU64,
+        }
+
+        #[async_trait]
+        impl TokenProvider for TestTokenProvider {
+            async fn refresh_token(&self, _base_url: &str, expired_token: &str) -> Result<Option<String>> {
+                assert_eq!(expired_token, "expired-token");
+                self.refreshes.fetch_add(1, Ordering::Relaxed);
+                Ok(Some("fresh-token".to_string()))
+            }
+        }
+
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let expired_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .match_header("PRIVATE-TOKEN", "expired-token")
+            .with_status(401)
+            .with_body(r#"{"message": "401 Unauthorized"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let fresh_mock = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_query(mockito::Matcher::Any)
+            .match_header("PRIVATE-TOKEN", "fresh-token")
+            .with_body(
+                r#"{
+                    "default_branch": "main",
+                    "path_with_namespace": "group/project",
+                    "star_count": 10,
+                    "web_url": "https://gitlab.com/group/project"
+                }"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+
+        let token_provider = Arc::new(TestTokenProvider::default());
+        let options = ClientOptions {
+            check_token_scopes: false,
+            token_provider: Some(token_provider.clone()),
+            ..Default::default()
+        };
+        let tokens = ["expired-token".to_string()];
+        let gl_pool = create_gitlab_pool(&server.url(), &tokens, &options).await.unwrap();
+        let gl = get_client(&gl_pool).await.unwrap();
+
+        // The client is rebuilt once, and the fresh token is used afterwards
+        assert!(gl.get_project("group/project").await.is_ok());
+        assert!(gl.get_project("group/project").await.is_ok());
+        assert_eq!(token_provider.refreshes.load(Ordering::Relaxed), 1);
+        expired_mock.assert_async().await;
+        fresh_mock.assert_async().await;
+    }
+
+    #[test]
+    fn command_token_provider_from_command() {
+        assert_eq!(CommandTokenProvider::from_command("  ").unwrap(), None);
+        assert_eq!(
+            CommandTokenProvider::from_command(" token-helper  gitlab --ttl 1h ").unwrap(),
+            Some(CommandTokenProvider {
+                program: "token-helper".to_string(),
+                args: vec!["gitlab".to_string(), "--ttl".to_string(), "1h".to_string()],
+            })
+        );
+        assert_eq!(
+            CommandTokenProvider::from_command(r#""/opt/token helper/bin" --label 'build token' a\ b"#)
+                .unwrap(),
+            Some(CommandTokenProvider {
+                program: "/opt/token helper/bin".to_string(),
+                args: vec![
+                    "--label".to_string(),
+                    "build token".to_string(),
+                    "a b".to_string()
+                ],
+            })
+        );
+        assert!(CommandTokenProvider::from_command("token-helper 'unbalanced").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_token_provider_refresh_token() {
+        let refresh_token = |command: &str| {
+            let token_provider = CommandTokenProvider::from_command(command).unwrap().unwrap();
+            async move { token_provider.refresh_token("https://gitlab.com", "expired-token").await }
+        };
+
+        // The instance url is passed as the last argument
+        assert_eq!(
+            refresh_token("printf 'fresh-token-for-%s\\n'").await.unwrap(),
+            Some("fresh-token-for-https://gitlab.com".to_string())
+        );
+        assert_eq!(refresh_token("printf %.0s").await.unwrap(), None);
+        assert!(refresh_token("false").await.is_err());
+        assert!(refresh_token("/nonexistent/token-helper").await.is_err());
+    }
+
+    #[test]
+    fn missing_token_scopes_detected() {
+        let scopes = |scopes: &[&str]| scopes.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!(
+            missing_token_scopes(&scopes(&["read_user"])),
+            vec!["read_api", "read_repository"]
+        );
+        assert_eq!(
+            missing_token_scopes(&scopes(&["read_api"])),
+            vec!["read_repository"]
+        );
+        assert!(missing_token_scopes(&scopes(&["read_api", "read_repository"])).is_empty());
+        assert!(missing_token_scopes(&scopes(&["api"])).is_empty());
+    }
+
+    #[test]
+    fn mask_token_keeps_short_prefix() {
+        assert_eq!(mask_token("glpat-1234567890abcdef"), "glpat-12****");
+        assert_eq!(mask_token("short"), "****");
+    }
+
+    #[test]
+    fn parse_gitlab_base_url_aliases_valid() {
+        let aliases = parse_gitlab_base_url_aliases(
+            "https://Git.example.com/=https://gitlab-internal.example.com/; ;http://git.other.com=http://10.0.0.1",
+        )
+        .unwrap();
+        assert_eq!(
+            aliases,
+            BTreeMap::from([
+                (
+                    "https://git.example.com".to_string(),
+                    "https://gitlab-internal.example.com".to_string()
+                ),
+                ("http://git.other.com".to_string(), "http://10.0.0.1".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_gitlab_base_url_aliases_invalid() {
+        for (raw_aliases, expected_err) in [
+            ("https://git.example.com", "url=api_url format"),
+            (
+                "git.example.com=https://gitlab-internal.example.com",
+                "must start with",
+            ),
+            (
+                "https://git.example.com=gitlab-internal.example.com",
+                "must start with",
+            ),
+        ] {
+            let err = parse_gitlab_base_url_aliases(raw_aliases).unwrap_err();
+            assert!(err.to_string().contains(expected_err), "{raw_aliases}: {err}");
+        }
+    }
+
+    #[test]
+    fn resolve_api_url_alias() {
+        let aliases =
+            parse_gitlab_base_url_aliases("https://git.example.com=https://gitlab-internal.example.com")
+                .unwrap();
+        assert_eq!(
+            resolve_api_url("https://git.example.com", &aliases),
+            "https://gitlab-internal.example.com"
+        );
+    }
+
+    #[test]
+    fn resolve_api_url_no_alias() {
+        let aliases =
+            parse_gitlab_base_url_aliases("https://git.example.com=https://gitlab-internal.example.com")
+                .unwrap();
+        assert_eq!(
+            resolve_api_url("https://gitlab.com", &aliases),
+            "https://gitlab.com"
+        );
+        assert_eq!(
+            resolve_api_url("https://gitlab.com", &BTreeMap::new()),
+            "https://gitlab.com"
+        );
+    }
+
+    #[test]
+    fn parse_gitlab_tokens_file_two_instances() {
+        let raw_data = r"
+instances:
+  - tokens:
+      - token1
+      - token2
+  - url: https://gitlab.example.com/
+    tokens:
+      - token3
+    cache_ttl_days: 1
+";
+        let configs = parse_gitlab_tokens_file(raw_data, DEFAULT_GITLAB_URL).unwrap();
+
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].base_url, DEFAULT_GITLAB_URL);
+        assert_eq!(
+            configs[0].tokens,
+            vec!["token1".to_string(), "token2".to_string()]
+        );
+        assert_eq!(configs[0].cache_ttl_days, None);
+        assert_eq!(configs[1].base_url, "https://gitlab.example.com");
+        assert_eq!(configs[1].tokens, vec!["token3".to_string()]);
+        assert_eq!(configs[1].cache_ttl_days, Some(1));
+    }
+
+    #[test]
+    fn get_instance_cache_ttl_overrides_default() {
+        let configs = [
+            GitlabInstanceConfig {
+                base_url: "https://gitlab.com".to_string(),
+                tokens: vec!["token1".to_string()],
+                cache_ttl_days: None,
+            },
+            GitlabInstanceConfig {
+                base_url: "https://gitlab.example.com".to_string(),
+                tokens: vec!["token2".to_string()],
+                cache_ttl_days: Some(1),
+            },
+        ];
+
+        for (base_url, expected_days) in [
+            ("https://gitlab.com", 7),
+            ("https://gitlab.example.com", 1),
+            ("https://GitLab.example.com/", 1),
+            ("https://gitlab.other.com", 7),
+        ] {
+            assert_eq!(
+                get_instance_cache_ttl(base_url, &configs, 7),
+                chrono::Duration::days(expected_days),
+                "{base_url}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_gitlab_tokens_default_url_override() {
+        let default_url = get_default_gitlab_url(Some("https://gitlab.example.com/")).unwrap();
+        let configs =
+            parse_gitlab_tokens_checked("token1;https://gitlab.other.com;token2", &default_url, false)
+                .unwrap();
+
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].base_url, "https://gitlab.example.com");
+        assert_eq!(configs[0].tokens, vec!["token1".to_string()]);
+        assert_eq!(configs[1].base_url, "https://gitlab.other.com");
+
+        let raw_data = "instances:\n  - tokens:\n      - token1\n";
+        let configs = parse_gitlab_tokens_file(raw_data, &default_url).unwrap();
+        assert_eq!(configs[0].base_url, "https://gitlab.example.com");
+    }
+
+    #[test]
+    fn parse_gitlab_tokens_checked_strict() {
+        let raw_data = "token1;https://gitlab.example.com;https://gitlab.other.com;token2";
+
+        let configs = parse_gitlab_tokens_checked(raw_data, DEFAULT_GITLAB_URL, false).unwrap();
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[1].base_url, "https://gitlab.other.com");
+
+        let err = parse_gitlab_tokens_checked(raw_data, DEFAULT_GITLAB_URL, true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid GITLAB_TOKENS value: instance url https://gitlab.example.com is not followed by any \
+             token (ignored)"
+        );
+        assert!(
+            parse_gitlab_tokens_checked("token1;https://gitlab.other.com;token2", DEFAULT_GITLAB_URL, true)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn get_default_gitlab_url_fallback_and_validation() {
+        assert_eq!(get_default_gitlab_url(None).unwrap(), DEFAULT_GITLAB_URL);
+        assert_eq!(get_default_gitlab_url(Some("")).unwrap(), DEFAULT_GITLAB_URL);
+        assert!(get_default_gitlab_url(Some("gitlab.example.com")).is_err());
+    }
+
+    #[tokio::test]
+    async fn parse_gitlab_tokens_file_dedups_tokens() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/api/v4/user")
+            .match_query(mockito::Matcher::Any)
+            .with_body("{}")
+            .create_async()
+            .await;
+        let raw_data = format!(
+            "instances: [{{url: {}, tokens: [token1, token2, ' token1 ']}}]",
+            server.url()
+        );
+
+        let configs = parse_gitlab_tokens_file(&raw_data, DEFAULT_GITLAB_URL).unwrap();
+        assert_eq!(
+            configs[0].tokens,
+            vec!["token1".to_string(), "token2".to_string()]
+        );
+
+        let configs = dedup_tokens(vec![GitlabInstanceConfig {
+            base_url: server.url(),
+            tokens: vec!["token1".to_string(), "token1".to_string()],
+            cache_ttl_days: None,
+        }]);
+        let options = ClientOptions {
+            check_token_scopes: false,
+            ..Default::default()
+        };
+        let gl_pool = create_gitlab_pool(&server.url(), &configs[0].tokens, &options).await.unwrap();
+        assert_eq!(gl_pool.status().size, 1);
+    }
+
+    #[test]
+    fn parse_gitlab_tokens_file_invalid() {
+        for (raw_data, expected_err) in [
+            (
+                "instances: [{url: gitlab.com, tokens: [token]}]",
+                "url must start with",
+            ),
+            ("instances: [{tokens: []}]", "at least one token"),
+            ("instances: [{tokens: ['  ']}]", "tokens cannot be empty"),
+            ("instances: [{token: token}]", "unknown field"),
+        ] {
+            let err = parse_gitlab_tokens_file(raw_data, DEFAULT_GITLAB_URL).unwrap_err();
+            assert!(err.to_string().contains(expected_err), "{raw_data}: {err}");
+        }
+    }
+}
@@ -21,7 +21,10 @@ use headless_chrome::{
     types::PrintToPdfOptions,
 };
 use landscape2_core::{
-    data::{self, CrunchbaseData, DataSource, GitData, Item, LandscapeData},
+    data::{
+        self, CrunchbaseData, DataSource, GitData, Item, LandscapeData, normalize_gitlab_url,
+        normalize_topics,
+    },
     datasets::{Datasets, NewDatasetsInput, embed::EmbedView, full::Full},
     games::{GamesSource, LandscapeGames},
     guide::{GuideSource, LandscapeGuide},
@@ -46,21 +49,22 @@ use self::{
     cache::Cache,
     crunchbase::collect_crunchbase_data,
     export::generate_items_csv,
-    github::collect_github_data,
-    gitlab::collect_gitlab_data,
+    github::{GitHubArgs, collect_github_data},
+    gitlab::{GitLabArgs, collect_gitlab_data},
     logos::{LogosSource, prepare_logo},
     projects::{ProjectsMd, generate_projects_csv},
 };
 
 mod api;
-mod cache;
+pub(crate) mod cache;
 mod clomonitor;
 mod crunchbase;
 mod export;
 mod github;
-mod gitlab;
+pub(crate) mod gitlab;
 mod logos;
 mod projects;
+mod tokens;
 
 /// Maximum number of CLOMonitor reports summaries to fetch concurrently.
 const CLOMONITOR_MAX_CONCURRENCY: usize = 10;
@@ -114,6 +118,12 @@ pub struct BuildArgs {
     #[arg(long)]
     pub cache_dir: Option<PathBuf>,
 
+    /// Cache namespace (i.e. the landscape id). When provided, the cache
+    /// files are stored in a subdirectory of the cache directory with this
+    /// name, allowing multiple landscapes to share the same cache directory.
+    #[arg(long)]
+    pub cache_namespace: Option<String>,
+
     /// Data source.
     #[command(flatten)]
     pub data_source: DataSource,
@@ -122,6 +132,14 @@ pub struct BuildArgs {
     #[command(flatten)]
     pub games_source: GamesSource,
 
+    /// GitHub data collection options.
+    #[command(flatten)]
+    pub github: GitHubArgs,
+
+    /// GitLab data collection options.
+    #[command(flatten)]
+    pub gitlab: GitLabArgs,
+
     /// Guide source.
     #[command(flatten)]
     pub guide_source: GuideSource,
@@ -152,7 +170,10 @@ pub async fn build(args: &BuildArgs) -> Result<()> {
     setup_output_dir(&args.output_dir)?;
 
     // Setup cache
-    let cache = Cache::new(args.cache_dir.as_ref())?;
+    let mut cache = Cache::new(args.cache_dir.as_ref())?;
+    if let Some(namespace) = &args.cache_namespace {
+        cache = cache.namespaced(namespace)?;
+    }
 
     // Get landscape data from the source provided
     let mut landscape_data = LandscapeData::new(&args.data_source).await?;
@@ -181,14 +202,22 @@ pub async fn build(args: &BuildArgs) -> Result<()> {
     // Collect data from external services
     let (crunchbase_data, git_data_github, git_data_gitlab) = tokio::try_join!(
         collect_crunchbase_data(&cache, &landscape_data),
-        collect_github_data(&cache, &landscape_data),
-        collect_gitlab_data(&cache, &landscape_data)
+        collect_github_data(&cache, &landscape_data, &args.github),
+        collect_gitlab_data(&cache, &landscape_data, &settings, &args.gitlab, &args.github)
     )?;
 
     // Merge GitHub and GitLab data into a single git_data collection
     let mut git_data = git_data_github;
     git_data.extend(git_data_gitlab);
 
+    // Normalize the repositories topics, so that the ones collected from
+    // GitHub and GitLab are consistent (unless their case must be preserved)
+    if !settings.collection.as_ref().is_some_and(|collection| collection.preserve_topics_case) {
+        for repo_git_data in git_data.values_mut() {
+            repo_git_data.topics = normalize_topics(&repo_git_data.topics);
+        }
+    }
+
     // Enrich landscape data with some extra information from the settings and
     // external services
     landscape_data.add_crunchbase_data(&crunchbase_data);
@@ -874,12 +903,14 @@ fn prepare_view_full_dataset(full: &Full, view: &EmbedView) -> Full {
 
     // Git data
     let mut git_data: GitData = BTreeMap::new();
-    
+
     // Collect from Git repo data
     for (url, repo_git_data) in &full.git_data {
         if items.iter().any(|i| {
             if let Some(repositories) = &i.repositories {
-                return repositories.iter().any(|r| r.url == *url);
+                return repositories
+                    .iter()
+                    .any(|r| r.url == *url || normalize_gitlab_url(&r.url).as_ref() == Some(url));
             }
             false
         }) {
@@ -994,3 +1025,15 @@ mod filters {
         Ok(analytics.as_ref().and_then(|a| a.gtm.as_ref()).and_then(|gtm| gtm.container_id.clone()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::{Args, Command};
+
+    use super::*;
+
+    #[test]
+    fn build_args_are_valid() {
+        BuildArgs::augment_args(Command::new("build")).debug_assert();
+    }
+}
@@ -0,0 +1,222 @@
+//! This module provides some helpers to parse the tokens used to collect data
+//! from git hosting services that may be deployed in multiple instances (i.e.
+//! GitHub Enterprise Server or self-hosted GitLab).
+
+/// Tokens provided for an instance.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct InstanceTokens {
+    pub base_url: String,
+    pub tokens: Vec<String>,
+}
+
+/// Parse the instances tokens from the raw value provided. Instances urls are
+/// followed by their comma separated tokens, using semicolons as separator
+/// (i.e. "token1,token2;url1;token3;url2;token4"). Tokens not preceded by an
+/// url are used for the default instance provided.
+pub(crate) fn parse_instances_tokens(raw: &str, default_url: &str) -> Vec<InstanceTokens> {
+    parse_instances_tokens_checked(raw, default_url).0
+}
+
+/// Parse the instances tokens from the raw value provided (see
+/// `parse_instances_tokens`), returning as well a warning for each of the
+/// segments that were dropped or whose meaning is ambiguous. Warnings refer to
+/// segments by position, so that tokens are never included in them.
+pub(crate) fn parse_instances_tokens_checked(
+    raw: &str,
+    default_url: &str,
+) -> (Vec<InstanceTokens>, Vec<String>) {
+    let mut instances_tokens = vec![];
+    let mut warnings = vec![];
+
+    // Split by semicolon for different instances/tokens
+    let parts: Vec<&str> = raw.split(';').collect();
+
+    let mut i = 0;
+    let mut url_seen = false;
+    while i < parts.len() {
+        let part = parts[i].trim();
+        if part.is_empty() {
+            i += 1;
+            continue;
+        }
+
+        // Check if this part looks like a URL (starts with http:// or https://)
+        if is_url(part) {
+            url_seen = true;
+            let base_url = part.trim_end_matches('/').to_string();
+
+            // Next part should be the token(s)
+            let tokens = match parts.get(i + 1).map(|next| next.trim()) {
+                Some(next) if !is_url(next) => {
+                    i += 2; // Skip both URL and token parts
+                    split_tokens(next)
+                }
+                _ => {
+                    i += 1;
+                    vec![]
+                }
+            };
+            if tokens.is_empty() {
+                warnings.push(format!(
+                    "instance url {base_url} is not followed by any token (ignored)"
+                ));
+            } else {
+                instances_tokens.push(InstanceTokens { base_url, tokens });
+            }
+            continue;
+        }
+
+        // No URL prefix - tokens for the default instance
+        let tokens = split_tokens(part);
+        if tokens.iter().any(|token| token.contains('/')) {
+            warnings.push(format!(
+                "segment {} looks like an instance url without http:// or https:// scheme",
+                i + 1
+            ));
+        }
+        if url_seen {
+            warnings.push(format!(
+                "segment {} follows the tokens of another instance, but is not preceded by an url \
+                 (used for the default instance {default_url})",
+                i + 1
+            ));
+        }
+        if !tokens.is_empty() {
+            instances_tokens.push(InstanceTokens {
+                base_url: default_url.to_string(),
+                tokens,
+            });
+        }
+
+        i += 1;
+    }
+
+    (instances_tokens, warnings)
+}
+
+/// Check if the segment provided is an instance url.
+fn is_url(segment: &str) -> bool {
+    segment.starts_with("http://") || segment.starts_with("https://")
+}
+
+/// Split the comma separated tokens provided, ignoring empty ones.
+fn split_tokens(tokens: &str) -> Vec<String> {
+    tokens.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_URL: &str = "https://default.example.com";
+
+    #[test]
+    fn parse_instances_tokens_bare_tokens_use_default_instance() {
+        assert_eq!(
+            parse_instances_tokens("token1, token2,", DEFAULT_URL),
+            vec![InstanceTokens {
+                base_url: DEFAULT_URL.to_string(),
+                tokens: vec!["token1".to_string(), "token2".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_instances_tokens_multiple_instances() {
+        assert_eq!(
+            parse_instances_tokens(
+                "token1;https://one.example.com/;token2,token3;http://two.example.com;token4",
+                DEFAULT_URL
+            ),
+            vec![
+                InstanceTokens {
+                    base_url: DEFAULT_URL.to_string(),
+                    tokens: vec!["token1".to_string()],
+                },
+                InstanceTokens {
+                    base_url: "https://one.example.com".to_string(),
+                    tokens: vec!["token2".to_string(), "token3".to_string()],
+                },
+                InstanceTokens {
+                    base_url: "http://two.example.com".to_string(),
+                    tokens: vec!["token4".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_instances_tokens_url_without_tokens_is_ignored() {
+        assert!(
+            parse_instances_tokens("https://one.example.com;;https://two.example.com", DEFAULT_URL)
+                .is_empty()
+        );
+        assert!(parse_instances_tokens("", DEFAULT_URL).is_empty());
+    }
+
+    #[test]
+    fn parse_instances_tokens_checked_valid_input_has_no_warnings() {
+        let (instances, warnings) =
+            parse_instances_tokens_checked("token1;https://one.example.com;token2,token3", DEFAULT_URL);
+        assert_eq!(instances.len(), 2);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn parse_instances_tokens_checked_url_at_the_end() {
+        let (instances, warnings) =
+            parse_instances_tokens_checked("token1;https://one.example.com/", DEFAULT_URL);
+        assert_eq!(instances.len(), 1);
+        assert_eq!(
+            warnings,
+            vec!["instance url https://one.example.com is not followed by any token (ignored)".to_string()]
+        );
+    }
+
+    #[test]
+    fn parse_instances_tokens_checked_url_followed_by_empty_segment() {
+        let (instances, warnings) = parse_instances_tokens_checked("https://one.example.com; ,", DEFAULT_URL);
+        assert!(instances.is_empty());
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn parse_instances_tokens_checked_url_followed_by_url() {
+        let (instances, warnings) = parse_instances_tokens_checked(
+            "https://one.example.com;https://two.example.com;token1",
+            DEFAULT_URL,
+        );
+        assert_eq!(
+            instances,
+            vec![InstanceTokens {
+                base_url: "https://two.example.com".to_string(),
+                tokens: vec!["token1".to_string()],
+            }]
+        );
+        assert_eq!(
+            warnings,
+            vec!["instance url https://one.example.com is not followed by any token (ignored)".to_string()]
+        );
+    }
+
+    #[test]
+    fn parse_instances_tokens_checked_trailing_tokens_after_url() {
+        let (instances, warnings) =
+            parse_instances_tokens_checked("https://one.example.com;token1;token2", DEFAULT_URL);
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[1].base_url, DEFAULT_URL);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("segment 3 follows the tokens of another instance"));
+        assert!(!warnings[0].contains("token2"));
+    }
+
+    #[test]
+    fn parse_instances_tokens_checked_url_without_scheme() {
+        let (instances, warnings) = parse_instances_tokens_checked("gitlab.example.com/;token1", DEFAULT_URL);
+        assert_eq!(instances.len(), 2);
+        assert_eq!(
+            warnings,
+            vec!["segment 1 looks like an instance url without http:// or https:// scheme".to_string()]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! This module defines the functionality of the cache CLI subcommand.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use tracing::instrument;
+
+use crate::build::cache::Cache;
+
+/// Cache command arguments.
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct CacheArgs {
+    /// Cache operation to run.
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+/// Cache operations available.
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// List the files in the cache, summarizing their entries (useful to
+    /// decide if some of the data should be refreshed).
+    Index(CacheIndexArgs),
+}
+
+/// Cache index arguments.
+#[derive(Args)]
+pub struct CacheIndexArgs {
+    /// Cache directory.
+    #[arg(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Cache namespace (i.e. the landscape id) used when building the
+    /// landscape.
+    #[arg(long)]
+    pub cache_namespace: Option<String>,
+}
+
+/// Print a summary of the entries in each of the files in the cache.
+#[instrument(skip_all, err)]
+pub fn index(args: &CacheIndexArgs) -> Result<()> {
+    let mut cache = Cache::new(args.cache_dir.as_ref())?;
+    if let Some(namespace) = &args.cache_namespace {
+        cache = cache.namespaced(namespace)?;
+    }
+
+    let index = cache.index()?;
+    if index.is_empty() {
+        println!("The cache is empty");
+    }
+    for file_index in index {
+        println!("{file_index}");
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! This module defines the functionality of the debug CLI subcommand.
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use landscape2_core::settings::{LandscapeSettings, SettingsSource};
+use tracing::instrument;
+
+use crate::build::gitlab::{GitLabArgs, collect_single_repository_data};
+
+/// Debug command arguments.
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct DebugArgs {
+    /// Debug operation to run.
+    #[command(subcommand)]
+    pub command: DebugCommand,
+}
+
+/// Debug operations available.
+#[derive(Subcommand)]
+pub enum DebugCommand {
+    /// Collect the data of a single GitLab repository, as it'd be collected
+    /// during the build, and print it in json format.
+    GitlabRepository(GitLabRepositoryArgs),
+}
+
+/// GitLab repository debug arguments.
+#[derive(Args)]
+pub struct GitLabRepositoryArgs {
+    /// GitLab repository url.
+    #[arg(long)]
+    pub url: String,
+
+    /// Branch to collect the data from (the project's default branch is used
+    /// when not provided).
+    #[arg(long)]
+    pub branch: Option<String>,
+
+    /// GitLab data collection options.
+    #[command(flatten)]
+    pub gitlab: GitLabArgs,
+
+    /// Settings source.
+    #[command(flatten)]
+    pub settings_source: SettingsSource,
+}
+
+/// Collect the data of the GitLab repository provided and print it.
+#[instrument(skip_all, err)]
+pub async fn collect_gitlab_repository(args: &GitLabRepositoryArgs) -> Result<()> {
+    let settings = LandscapeSettings::new(&args.settings_source).await?;
+    let repo =
+        collect_single_repository_data(&args.url, args.branch.as_deref(), &settings, &args.gitlab).await?;
+
+    println!("{}", serde_json::to_string_pretty(&repo)?);
+    Ok(())
+}
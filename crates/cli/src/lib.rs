@@ -6,6 +6,8 @@
 )]
 
 pub mod build;
+pub mod cache;
+pub mod debug;
 pub mod deploy;
 pub mod new;
 pub mod serve;
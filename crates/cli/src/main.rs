@@ -8,6 +8,8 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use landscape2::build::{BuildArgs, build};
+use landscape2::cache::{self, CacheArgs, CacheCommand};
+use landscape2::debug::{DebugArgs, DebugCommand, collect_gitlab_repository};
 use landscape2::deploy::s3::{self};
 use landscape2::deploy::{DeployArgs, Provider};
 use landscape2::new::{NewArgs, new};
@@ -37,6 +39,12 @@ enum Command {
     /// Build landscape website.
     Build(BuildArgs),
 
+    /// Inspect the data collected from external sources in the cache.
+    Cache(CacheArgs),
+
+    /// Debug the collection of data from external sources.
+    Debug(DebugArgs),
+
     /// Deploy landscape website (experimental).
     Deploy(DeployArgs),
 
@@ -56,17 +64,23 @@ async fn main() -> Result<()> {
 
     // Setup logging
     match &cli.command {
-        Command::Build(_) | Command::Deploy(_) | Command::New(_) | Command::Serve(_) => {
+        Command::Build(_) | Command::Debug(_) | Command::Deploy(_) | Command::New(_) | Command::Serve(_) => {
             let env_filter =
                 EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("landscape2=debug"));
             tracing_subscriber::fmt().with_env_filter(env_filter).init();
         }
-        Command::Validate(_) => {}
+        Command::Cache(_) | Command::Validate(_) => {}
     }
 
     // Run command
     match &cli.command {
         Command::Build(args) => build(args).await?,
+        Command::Cache(args) => match &args.command {
+            CacheCommand::Index(args) => cache::index(args)?,
+        },
+        Command::Debug(args) => match &args.command {
+            DebugCommand::GitlabRepository(args) => collect_gitlab_repository(args).await?,
+        },
         Command::Deploy(args) => match &args.provider {
             Provider::S3(args) => s3::deploy(args).await?,
         },
@@ -11,14 +11,17 @@ use std::{
     collections::{BTreeMap, HashMap},
     fs,
     path::{Path, PathBuf},
+    sync::LazyLock,
 };
 
 use anyhow::{Context, Result, bail};
 use chrono::{DateTime, NaiveDate, Utc};
 use clap::Args;
+use regex::Regex;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, instrument, warn};
+use url::Url;
 
 use crate::util::normalize_name;
 
@@ -207,7 +210,8 @@ impl LandscapeData {
         }
     }
 
-    /// Add items repositories GitLab data.
+    /// Add items repositories GitLab data. GitLab data is keyed by the
+    /// normalized repositories urls.
     #[instrument(skip_all)]
     pub fn add_gitlab_data(&mut self, gitlab_data: &GitData) {
         for item in &mut self.items {
@@ -215,7 +219,9 @@ impl LandscapeData {
             if item.repositories.is_some() {
                 let mut repositories = vec![];
                 for mut repo in item.repositories.clone().unwrap_or_default() {
-                    if let Some(repo_data) = gitlab_data.get(&repo.url) {
+                    if let Some(repo_data) =
+                        normalize_gitlab_url(&repo.url).and_then(|url| gitlab_data.get(&url))
+                    {
                         repo.git_data = Some(repo_data.clone());
                     }
                     repositories.push(repo);
@@ -418,6 +424,7 @@ impl From<legacy::LandscapeData> for LandscapeData {
                             url,
                             branch: legacy_item.branch,
                             git_data: None,
+                            gitlab_group: legacy_item.gitlab_group,
                             license: legacy_item.license,
                             primary: Some(true),
                         });
@@ -428,6 +435,7 @@ impl From<legacy::LandscapeData> for LandscapeData {
                                 url: entry.repo_url,
                                 branch: entry.branch,
                                 git_data: None,
+                                gitlab_group: entry.gitlab_group,
                                 license: entry.license,
                                 primary: Some(false),
                             });
@@ -800,6 +808,13 @@ pub struct AdditionalCategory {
 pub struct Commit {
     pub ts: Option<DateTime<Utc>>,
     pub url: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_name: Option<String>,
+
+    /// Summary of the commit message (its first line, truncated).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 /// Contributors information.
@@ -940,8 +955,17 @@ pub struct Organization {
 /// Release information.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Release {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_name: Option<String>,
+
     pub ts: Option<DateTime<Utc>>,
     pub url: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets_count: Option<usize>,
 }
 
 /// Repository information.
@@ -955,6 +979,11 @@ pub struct Repository {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub git_data: Option<RepositoryGitData>,
 
+    /// Whether the url points to a GitLab group, whose projects data should
+    /// be aggregated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gitlab_group: Option<bool>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub license: Option<String>,
 
@@ -974,25 +1003,194 @@ pub struct RepositoryGitData {
     pub topics: Vec<String>,
     pub url: String,
 
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub first_commit: Option<Commit>,
 
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub forked: bool,
+
+    /// Whether the data was taken from the cache instead of being freshly
+    /// fetched. It is only meant to be used during the build, so it is never
+    /// serialized.
+    #[serde(skip)]
+    pub from_cache: bool,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub languages: Option<BTreeMap<String, i64>>,
 
+    /// Whether the languages bytes counts are estimations (i.e. computed from
+    /// the percentages provided by GitLab) rather than real values.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub languages_are_approximate: Option<bool>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub latest_release: Option<Release>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub license: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_spdx: Option<String>,
+
     // GitHub-specific fields
     #[serde(skip_serializing_if = "Option::is_none")]
     pub participation_stats: Option<Vec<i64>>,
 
     // GitLab-specific fields
+    /// Url of the project avatar, which may be used as a fallback when the
+    /// item has no logo (projects without an avatar don't have one).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+
+    /// Branch the data was collected from, when it isn't the repository's
+    /// default one. Cached data collected from a different branch than the
+    /// one currently configured is not reused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+
+    /// Current path of the project in the GitLab instance, when it's different
+    /// from the one in the repository url (i.e. the project has been renamed
+    /// or moved to a different namespace since it was added to the landscape).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical_path: Option<String>,
+
+    /// Number of commits in each of the last 52 weeks, from the oldest week
+    /// to the most recent one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_activity_weekly: Option<Vec<u32>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commits_last_year: Option<usize>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub good_first_issues: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help_wanted_issues: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub languages_pct: Option<BTreeMap<String, f64>>,
+
+    /// Last time there was some activity in the project (i.e. new commits,
+    /// issues or merge requests), which is a cheaper freshness indicator than
+    /// the latest commit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_activity_at: Option<DateTime<Utc>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_pipeline_status: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_issues: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_merge_requests: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_created_at: Option<DateTime<Utc>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository_size_bytes: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_contributors: Option<Vec<TopContributor>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<String>,
+}
+
+/// Top repository contributor information.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TopContributor {
+    pub name: String,
+    pub commits: usize,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+}
+
+/// GitLab repository url regular expression.
+static GITLAB_REPO_URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<base>https?://[^/]+)/(?P<path>.+?)/?$").expect("exprs in GITLAB_REPO_URL to be valid")
+});
+
+/// GitLab project path suffix regular expression. Repository urls may point
+/// to a specific route inside the project (i.e. `/-/tree/main` or `/-/issues`),
+/// which is not part of the project path (`-` cannot be used as a name).
+static GITLAB_PATH_SUFFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"/-(?:/.*)?$").expect("exprs in GITLAB_PATH_SUFFIX to be valid"));
+
+/// GitLab legacy project path suffix regular expression (i.e. `/tree/main` or
+/// `/blob/main/README.md`). As groups can be named `tree` or `blob`, these
+/// routes are only stripped when they are the last segments of the url and
+/// follow a path including at least a namespace and a project.
+static GITLAB_LEGACY_PATH_SUFFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<path>[^/]+/.+?)/(?:tree/[^/]+|blob/[^/]+/.+)$")
+        .expect("exprs in GITLAB_LEGACY_PATH_SUFFIX to be valid")
+});
+
+/// GitLab project id path regular expression. Projects can also be referenced
+/// by their numeric id (i.e. `https://gitlab.com/-/project/12345`).
+static GITLAB_PROJECT_ID_PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^-/project/(?P<id>\d+)(?:/.*)?$").expect("exprs in GITLAB_PROJECT_ID_PATH to be valid")
+});
+
+/// Parse GitLab URL to extract base URL and project path. For urls that
+/// reference the project by its id, the path keeps the `-/project/<id>` form,
+/// so that it never collides with the path of a namespace.
+#[must_use]
+pub fn parse_gitlab_url(repo_url: &str) -> Option<(String, String)> {
+    // Skip GitHub URLs
+    let host = Url::parse(repo_url).ok().and_then(|url| url.host_str().map(ToString::to_string));
+    if host.as_deref() == Some("github.com") {
+        return None;
+    }
+
+    GITLAB_REPO_URL.captures(repo_url).map(|c| {
+        let base = c["base"].to_string();
+        if let Some(id) = GITLAB_PROJECT_ID_PATH.captures(&c["path"]) {
+            return (base, format!("-/project/{}", &id["id"]));
+        }
+        let path = GITLAB_PATH_SUFFIX.replace(&c["path"], "");
+        let path = GITLAB_LEGACY_PATH_SUFFIX.replace(&path, "$path");
+        let path = path.trim_end_matches('/').trim_end_matches(".git").to_string();
+        (base, path)
+    })
+}
+
+/// Parse GitLab group URL to extract base URL and group path. Group urls may
+/// include the `groups/` route prefix (i.e. `https://gitlab.com/groups/group`).
+#[must_use]
+pub fn parse_gitlab_group_url(group_url: &str) -> Option<(String, String)> {
+    let (base, path) = parse_gitlab_url(group_url)?;
+    let path = path.strip_prefix("groups/").unwrap_or(&path).to_string();
+    Some((base, path))
+}
+
+/// Normalize the GitLab repository url provided, so that different spellings
+/// of the same repository url (trailing slash, `.git` suffix, host case, etc)
+/// produce the same url.
+#[must_use]
+pub fn normalize_gitlab_url(repo_url: &str) -> Option<String> {
+    let (base_url, path) = parse_gitlab_url(repo_url)?;
+    Some(format!("{}/{path}", base_url.to_lowercase()))
+}
+
+/// Normalize the repository topics provided (trimmed and lowercased, as GitHub
+/// does), removing the empty and duplicated ones.
+#[must_use]
+pub fn normalize_topics(topics: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = vec![];
+    for topic in topics {
+        let topic = topic.trim().to_lowercase();
+        if !topic.is_empty() && !normalized.contains(&topic) {
+            normalized.push(topic);
+        }
+    }
+    normalized
 }
 
 #[cfg(test)]
@@ -1166,6 +1364,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn landscape_data_add_gitlab_data() {
+        let mut landscape_data = LandscapeData::default();
+        let repository = Repository {
+            url: "https://GitLab.com/group/project.git".to_string(),
+            primary: Some(true),
+            ..Default::default()
+        };
+        landscape_data.items.push(Item {
+            repositories: Some(vec![repository.clone()]),
+            ..Default::default()
+        });
+
+        let mut git_data = GitData::default();
+        let repository_data = RepositoryGitData {
+            description: "test".to_string(),
+            license: Some("Apache-2.0".to_string()),
+            ..Default::default()
+        };
+        git_data.insert(
+            "https://gitlab.com/group/project".to_string(),
+            repository_data.clone(),
+        );
+
+        landscape_data.add_gitlab_data(&git_data);
+        assert_eq!(
+            landscape_data.items[0].repositories,
+            Some(vec![Repository {
+                git_data: Some(repository_data),
+                ..repository
+            }])
+        );
+        assert_eq!(landscape_data.items[0].oss, Some(true));
+    }
+
     #[test]
     fn landscape_data_add_github_data() {
         let mut landscape_data = LandscapeData::default();
@@ -1410,6 +1643,7 @@ mod tests {
                         additional_repos: Some(vec![legacy::Repository {
                             repo_url: "additional_repo_url".to_string(),
                             branch: Some("branch".to_string()),
+                            gitlab_group: Some(true),
                             license: Some("license".to_string()),
                         }]),
                         branch: Some("branch".to_string()),
@@ -1469,6 +1703,7 @@ mod tests {
                             training_type: Some("training_type".to_string()),
                             youtube_url: Some("youtube_url".to_string()),
                         }),
+                        gitlab_group: Some(false),
                         joined: Some(date),
                         license: Some("license".to_string()),
                         project: Some("graduated".to_string()),
@@ -1554,6 +1789,7 @@ mod tests {
                         url: "repo_url".to_string(),
                         branch: Some("branch".to_string()),
                         git_data: None,
+                        gitlab_group: Some(false),
                         license: Some("license".to_string()),
                         primary: Some(true),
                     },
@@ -1561,6 +1797,7 @@ mod tests {
                         url: "additional_repo_url".to_string(),
                         branch: Some("branch".to_string()),
                         git_data: None,
+                        gitlab_group: Some(true),
                         license: Some("license".to_string()),
                         primary: Some(false),
                     },
@@ -1747,4 +1984,156 @@ mod tests {
         item.set_website();
         assert_eq!(item.website, "homepage_url".to_string());
     }
+
+    #[test]
+    fn parse_gitlab_url_strips_path_suffixes() {
+        let expected = Some((
+            "https://gitlab.com".to_string(),
+            "group/subgroup1/subgroup2/project".to_string(),
+        ));
+        for url in [
+            "https://gitlab.com/group/subgroup1/subgroup2/project",
+            "https://gitlab.com/group/subgroup1/subgroup2/project/",
+            "https://gitlab.com/group/subgroup1/subgroup2/project.git",
+            "https://gitlab.com/group/subgroup1/subgroup2/project/-/tree/main",
+            "https://gitlab.com/group/subgroup1/subgroup2/project/-/blob/main/src/main.rs",
+            "https://gitlab.com/group/subgroup1/subgroup2/project/-/issues",
+            "https://gitlab.com/group/subgroup1/subgroup2/project/-/merge_requests/1",
+            "https://gitlab.com/group/subgroup1/subgroup2/project/-/",
+            "https://gitlab.com/group/subgroup1/subgroup2/project/tree/main",
+            "https://gitlab.com/group/subgroup1/subgroup2/project/blob/main/README.md",
+        ] {
+            assert_eq!(parse_gitlab_url(url), expected, "{url}");
+        }
+    }
+
+    #[test]
+    fn parse_gitlab_url_keeps_groups_named_like_legacy_routes() {
+        for (url, expected_path) in [
+            ("https://gitlab.com/org/tree/project", "org/tree/project"),
+            ("https://gitlab.com/org/blob/project", "org/blob/project"),
+            (
+                "https://gitlab.com/org/tree/project/-/tree/main",
+                "org/tree/project",
+            ),
+            (
+                "https://gitlab.com/org/tree/project/tree/main",
+                "org/tree/project",
+            ),
+        ] {
+            assert_eq!(
+                parse_gitlab_url(url),
+                Some(("https://gitlab.com".to_string(), expected_path.to_string())),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_gitlab_url_project_id() {
+        let expected = Some((
+            "https://gitlab.example.com".to_string(),
+            "-/project/12345".to_string(),
+        ));
+        for url in [
+            "https://gitlab.example.com/-/project/12345",
+            "https://gitlab.example.com/-/project/12345/",
+            "https://gitlab.example.com/-/project/12345/-/tree/main",
+        ] {
+            assert_eq!(parse_gitlab_url(url), expected, "{url}");
+        }
+        assert_eq!(
+            normalize_gitlab_url("https://GitLab.example.com/-/project/12345/"),
+            Some("https://gitlab.example.com/-/project/12345".to_string())
+        );
+    }
+
+    #[test]
+    fn normalize_gitlab_url_project_id_does_not_collide_with_namespaces() {
+        let id_url = normalize_gitlab_url("https://gitlab.example.com/-/project/12345").unwrap();
+        let namespace_url = normalize_gitlab_url("https://gitlab.example.com/12345").unwrap();
+        assert_ne!(id_url, namespace_url);
+        assert_ne!(parse_gitlab_url(&id_url), parse_gitlab_url(&namespace_url));
+        assert_eq!(normalize_gitlab_url(&id_url), Some(id_url));
+    }
+
+    #[test]
+    fn parse_gitlab_url_skips_github_urls_only() {
+        assert_eq!(parse_gitlab_url("https://github.com/org/repo"), None);
+        assert_eq!(parse_gitlab_url("https://GitHub.com/org/repo"), None);
+        assert_eq!(
+            parse_gitlab_url("https://gitlab.com/github.com/project"),
+            Some(("https://gitlab.com".to_string(), "github.com/project".to_string()))
+        );
+        assert_eq!(
+            parse_gitlab_url("https://gitlab.example.com/mirrors/github.com-project"),
+            Some((
+                "https://gitlab.example.com".to_string(),
+                "mirrors/github.com-project".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_gitlab_url_keeps_scheme() {
+        assert_eq!(
+            parse_gitlab_url("http://gitlab.example.com/group/project"),
+            Some((
+                "http://gitlab.example.com".to_string(),
+                "group/project".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_gitlab_url("https://gitlab.com/group/project.git"),
+            Some(("https://gitlab.com".to_string(), "group/project".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_gitlab_group_url_variants() {
+        for url in [
+            "https://gitlab.com/group/subgroup",
+            "https://gitlab.com/groups/group/subgroup/",
+            "https://gitlab.com/groups/group/subgroup/-/shared",
+        ] {
+            assert_eq!(
+                parse_gitlab_group_url(url),
+                Some(("https://gitlab.com".to_string(), "group/subgroup".to_string())),
+                "{url}"
+            );
+        }
+        assert_eq!(parse_gitlab_group_url("https://github.com/org"), None);
+    }
+
+    #[test]
+    fn normalize_topics_lowercases_trims_and_dedups() {
+        let topics = vec![
+            "Kubernetes".to_string(),
+            " cloud-native ".to_string(),
+            "kubernetes".to_string(),
+            " ".to_string(),
+            "Cloud-Native".to_string(),
+        ];
+        assert_eq!(
+            normalize_topics(&topics),
+            vec!["kubernetes".to_string(), "cloud-native".to_string()]
+        );
+        assert!(normalize_topics(&[]).is_empty());
+    }
+
+    #[test]
+    fn normalize_gitlab_url_variants() {
+        for url in [
+            "https://gitlab.com/group/project",
+            "https://GitLab.com/group/project/",
+            "https://gitlab.com/group/project.git",
+        ] {
+            assert_eq!(
+                normalize_gitlab_url(url),
+                Some("https://gitlab.com/group/project".to_string()),
+                "{url}"
+            );
+        }
+        assert_eq!(normalize_gitlab_url("https://github.com/org/repo"), None);
+    }
 }
@@ -138,6 +138,7 @@ pub(super) struct Item {
     pub description: Option<String>,
     pub enduser: Option<bool>,
     pub extra: Option<ItemExtra>,
+    pub gitlab_group: Option<bool>,
     pub joined: Option<NaiveDate>,
     pub license: Option<String>,
     pub project: Option<String>,
@@ -201,6 +202,7 @@ pub(super) struct ItemExtra {
 pub(super) struct Repository {
     pub repo_url: String,
     pub branch: Option<String>,
+    pub gitlab_group: Option<bool>,
     pub license: Option<String>,
 }
 
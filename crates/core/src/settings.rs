@@ -65,6 +65,9 @@ pub struct LandscapeSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub categories: Option<Vec<Category>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<Collection>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub colors: Option<Colors>,
 
@@ -227,6 +230,7 @@ impl LandscapeSettings {
         self.validate_base_path()?;
         self.validate_description()?;
         self.validate_categories()?;
+        self.validate_collection()?;
         self.validate_colors()?;
         self.validate_featured_items()?;
         self.validate_footer()?;
@@ -287,6 +291,49 @@ impl LandscapeSettings {
         Ok(())
     }
 
+    /// Check data collection configuration is valid.
+    fn validate_collection(&self) -> Result<()> {
+        let Some(collection) = &self.collection else {
+            return Ok(());
+        };
+
+        // Exclude patterns
+        if let Some(patterns) = &collection.exclude {
+            for pattern in patterns {
+                if pattern.trim().is_empty() {
+                    bail!("collection exclude pattern cannot be empty");
+                }
+            }
+        }
+
+        // GitLab
+        if let Some(labels) =
+            collection.gitlab.as_ref().and_then(|gitlab| gitlab.good_first_issues_labels.as_ref())
+        {
+            if labels.is_empty() {
+                bail!("gitlab good first issues labels cannot be empty");
+            }
+            for label in labels {
+                if label.trim().is_empty() {
+                    bail!("gitlab good first issues label cannot be empty");
+                }
+            }
+        }
+        if let Some(labels) = collection.gitlab.as_ref().and_then(|gitlab| gitlab.help_wanted_labels.as_ref())
+        {
+            if labels.is_empty() {
+                bail!("gitlab help wanted labels cannot be empty");
+            }
+            for label in labels {
+                if label.trim().is_empty() {
+                    bail!("gitlab help wanted label cannot be empty");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Check colors format.
     fn validate_colors(&self) -> Result<()> {
         if let Some(colors) = &self.colors {
@@ -556,6 +603,19 @@ static RGBA: LazyLock<Regex> = LazyLock::new(|| {
         .expect("exprs in RGBA to be valid")
 });
 
+/// Data collection configuration.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Collection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gitlab: Option<GitLabCollection>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preserve_topics_case: bool,
+}
+
 /// Colors used across the landscape UI.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Colors {
@@ -652,6 +712,72 @@ pub struct FooterLinks {
     pub youtube: Option<String>,
 }
 
+/// GitLab data collection configuration.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GitLabCollection {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub commit_activity: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commits_last_year: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follow_github_mirrors: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub good_first_issues: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub good_first_issues_labels: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help_wanted_issues: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help_wanted_labels: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub languages: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_commit: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_pipeline_status: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_release: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_issues: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_merge_requests: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prerelease_tags_pattern: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<GitLabCollectionProfile>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip_prereleases: bool,
+}
+
+/// GitLab data collection profile.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GitLabCollectionProfile {
+    /// Collect all the data enabled.
+    Full,
+    /// Only use the endpoints available to regular users, skipping the ones
+    /// based on statistics (that may be locked down on some instances).
+    Lightweight,
+}
+
 /// Google Tag Manager configuration.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct GoogleTagManager {
@@ -1033,6 +1159,79 @@ mod tests {
         settings.validate().unwrap();
     }
 
+    #[test]
+    fn settings_validate_collection_succeeds() {
+        let settings = LandscapeSettings {
+            foundation: "Foundation".to_string(),
+            url: "https://example.url".to_string(),
+            collection: Some(Collection {
+                exclude: Some(vec!["https://gitlab.com/group/*".to_string()]),
+                gitlab: Some(GitLabCollection {
+                    good_first_issues_labels: Some(vec!["help wanted".to_string()]),
+                    ..Default::default()
+                }),
+                preserve_topics_case: true,
+            }),
+            ..Default::default()
+        };
+
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "gitlab good first issues label cannot be empty")]
+    fn settings_validate_collection_gitlab_empty_label() {
+        let settings = LandscapeSettings {
+            foundation: "Foundation".to_string(),
+            url: "https://example.url".to_string(),
+            collection: Some(Collection {
+                gitlab: Some(GitLabCollection {
+                    good_first_issues_labels: Some(vec![" ".to_string()]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "gitlab help wanted labels cannot be empty")]
+    fn settings_validate_collection_gitlab_no_help_wanted_labels() {
+        let settings = LandscapeSettings {
+            foundation: "Foundation".to_string(),
+            url: "https://example.url".to_string(),
+            collection: Some(Collection {
+                gitlab: Some(GitLabCollection {
+                    help_wanted_labels: Some(vec![]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "collection exclude pattern cannot be empty")]
+    fn settings_validate_collection_empty_exclude_pattern() {
+        let settings = LandscapeSettings {
+            foundation: "Foundation".to_string(),
+            url: "https://example.url".to_string(),
+            collection: Some(Collection {
+                exclude: Some(vec![String::new()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        settings.validate().unwrap();
+    }
+
     #[test]
     fn settings_validate_colors_succeeds() {
         let settings = LandscapeSettings {
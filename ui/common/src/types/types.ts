@@ -120,14 +120,18 @@ export interface Repository {
 }
 
 export interface GitRepository {
+  archived?: boolean;
   contributors: Contributors;
   description: string;
   first_commit?: Commit;
+  forked?: boolean;
   generated_at: number;
   languages?: { [key: string]: number };
+  languages_are_approximate?: boolean;
   latest_commit: Commit;
   latest_release?: Release;
   license?: string;
+  license_spdx?: string;
   stars: number;
   topics: string[];
   url: string;
@@ -136,7 +140,22 @@ export interface GitRepository {
   participation_stats?: number[];
   
   // GitLab-specific fields
+  avatar_url?: string;
+  branch?: string;
+  canonical_path?: string;
+  commit_activity_weekly?: number[];
+  commits_last_year?: number;
   good_first_issues?: number;
+  help_wanted_issues?: number;
+  languages_pct?: { [key: string]: number };
+  last_activity_at?: string;
+  latest_pipeline_status?: string;
+  open_issues?: number;
+  open_merge_requests?: number;
+  project_created_at?: string;
+  repository_size_bytes?: number;
+  top_contributors?: TopContributor[];
+  visibility?: string;
 }
 
 export interface Contributors {
@@ -144,14 +163,25 @@ export interface Contributors {
   url: string;
 }
 
+export interface TopContributor {
+  name: string;
+  commits: number;
+  avatar_url?: string;
+}
+
 export interface Commit {
   ts: string;
   url: string;
+  author_name?: string;
+  message?: string;
 }
 
 export interface Release {
+  name?: string;
+  tag_name?: string;
   ts: string;
   url: string;
+  assets_count?: number;
 }
 
 export interface Acquisition {
@@ -204,19 +204,38 @@ export interface Repository {
 }
 
 export interface GitRepository {
+  archived?: boolean;
+  avatar_url?: string;
+  branch?: string;
+  canonical_path?: string;
+  commit_activity_weekly?: number[];
+  commits_last_year?: number;
   contributors: Contributors;
   description: string;
   first_commit: Commit;
+  forked?: boolean;
   generated_at: number;
   good_first_issues?: number;
+  help_wanted_issues?: number;
   languages?: { [key: string]: number };
+  languages_are_approximate?: boolean;
+  languages_pct?: { [key: string]: number };
+  last_activity_at?: string;
   latest_commit: Commit;
+  latest_pipeline_status?: string;
   latest_release?: Release;
   license: string;
+  license_spdx?: string;
+  open_issues?: number;
+  open_merge_requests?: number;
   participation_stats?: number[];
+  project_created_at?: string;
+  repository_size_bytes?: number;
   stars: number;
+  top_contributors?: TopContributor[];
   topics: string[];
   url: string;
+  visibility?: string;
 }
 
 export interface Contributors {
@@ -224,14 +243,25 @@ export interface Contributors {
   url: string;
 }
 
+export interface TopContributor {
+  name: string;
+  commits: number;
+  avatar_url?: string;
+}
+
 export interface Commit {
   ts: string;
   url: string;
+  author_name?: string;
+  message?: string;
 }
 
 export interface Release {
+  name?: string;
+  tag_name?: string;
   ts: string;
   url: string;
+  assets_count?: number;
 }
 
 export interface LandscapeData {